@@ -0,0 +1,715 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use filetime::FileTime;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, Metadata};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tar::{Archive, Builder, EntryType, Header, HeaderMode};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::utils;
+
+/// Extended attribute namespaces that are never archived: restoring them
+/// needs privileges, and their values rarely mean anything on another host
+const SKIPPED_XATTR_NAMESPACES: [&str; 3] = ["security.", "system.", "trusted."];
+
+const PAX_XATTR_PREFIX: &str = "SCHILY.xattr.";
+
+/// Modification time of every entry in a deterministic archive. Not zero,
+/// which some tools take for a missing timestamp.
+pub const DETERMINISTIC_MTIME: u64 = 1;
+
+/// What an archive keeps of an installed tree.
+///
+/// - Symlinks are stored as links with their target verbatim, whether it is
+///   relative, absolute or points outside the tree; they are never followed.
+/// - A file with several names inside the tree is stored once and its other
+///   names as hard links to the first.
+/// - The read, write and execute bits are kept. Setuid, setgid and sticky
+///   bits and ownership are not.
+/// - File and directory modification times are kept to the second, unless
+///   `deterministic` is set.
+/// - Extended attributes are kept when `xattrs` is set, except in the
+///   `security.`, `system.` and `trusted.` namespaces.
+/// - Sockets, FIFOs and device files are skipped.
+/// - Paths matching an `exclude` pattern are left out, and so is everything
+///   below an excluded directory.
+///
+/// Entries are always stored in sorted order. With `deterministic` set,
+/// owners are stored as root and every modification time as
+/// `DETERMINISTIC_MTIME`, so an unchanged tree always gives the same tarball.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    pub xattrs: bool,
+    /// Normalize owners and modification times
+    pub deterministic: bool,
+    /// Glob patterns of paths relative to the archived directory
+    pub exclude: Vec<String>,
+}
+
+impl ArchiveOptions {
+    /// Options with the global exclude patterns only
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            xattrs: config.preserve_xattrs,
+            deterministic: config.deterministic_archives,
+            exclude: config.exclude.clone(),
+        }
+    }
+
+    /// Options for an install of `tool`: the global exclude patterns
+    /// followed by the tool's own
+    pub fn for_tool(config: &Config, tool: &str) -> Self {
+        let mut options = Self::from_config(config);
+        for pattern in config.tool_excludes.get(tool).into_iter().flatten() {
+            if !options.exclude.contains(pattern) {
+                options.exclude.push(pattern.clone());
+            }
+        }
+        options
+    }
+}
+
+/// Compile exclude patterns. `*` and `?` stop at `/` while `**` crosses
+/// directories, and a pattern ending in `/**` also excludes the directory
+/// itself.
+pub fn compile_excludes(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let stems = pattern.strip_suffix("/**").into_iter();
+        for pattern in std::iter::once(pattern.as_str()).chain(stems) {
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("Invalid exclude pattern: {}", pattern))?;
+            builder.add(glob);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+/// Sees every entry `append_tree_with` stores as it goes into the archive,
+/// so what is learned about a tree comes from the read that archives it.
+/// Directories and skipped entries are not shown.
+pub trait TreeVisitor {
+    /// A symlink at `relative` pointing to `target`
+    fn symlink(&mut self, _relative: &Path, _target: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// A further name of the file first stored at `first`
+    fn hard_link(&mut self, _relative: &Path, _meta: &Metadata, _first: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// A file at `relative`, whose contents follow through `file_data`
+    fn file_start(&mut self, _relative: &Path, _meta: &Metadata) {}
+
+    /// The next bytes of the current file
+    fn file_data(&mut self, _data: &[u8]) {}
+
+    /// The end of the current file
+    fn file_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl TreeVisitor for () {}
+
+impl<A: TreeVisitor, B: TreeVisitor> TreeVisitor for (A, B) {
+    fn symlink(&mut self, relative: &Path, target: &Path) -> Result<()> {
+        self.0.symlink(relative, target)?;
+        self.1.symlink(relative, target)
+    }
+
+    fn hard_link(&mut self, relative: &Path, meta: &Metadata, first: &Path) -> Result<()> {
+        self.0.hard_link(relative, meta, first)?;
+        self.1.hard_link(relative, meta, first)
+    }
+
+    fn file_start(&mut self, relative: &Path, meta: &Metadata) {
+        self.0.file_start(relative, meta);
+        self.1.file_start(relative, meta);
+    }
+
+    fn file_data(&mut self, data: &[u8]) {
+        self.0.file_data(data);
+        self.1.file_data(data);
+    }
+
+    fn file_end(&mut self) -> Result<()> {
+        self.0.file_end()?;
+        self.1.file_end()
+    }
+}
+
+/// Reader adapter that shows a file's contents to a visitor as the archive
+/// reads them
+struct VisitedReader<'a, R, V> {
+    inner: R,
+    visitor: &'a mut V,
+}
+
+impl<R: Read, V: TreeVisitor> Read for VisitedReader<'_, R, V> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.visitor.file_data(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Append every entry below `source_dir` to `builder` under paths relative
+/// to it, following the fidelity rules of `ArchiveOptions`
+pub fn append_tree<W: Write>(
+    builder: &mut Builder<W>,
+    source_dir: &Path,
+    options: &ArchiveOptions,
+) -> Result<()> {
+    append_tree_with(builder, source_dir, options, &mut ())
+}
+
+/// `append_tree`, showing each entry to `visitor` as it is stored
+pub fn append_tree_with<W: Write, V: TreeVisitor>(
+    builder: &mut Builder<W>,
+    source_dir: &Path,
+    options: &ArchiveOptions,
+    visitor: &mut V,
+) -> Result<()> {
+    let mut first_links: HashMap<_, PathBuf> = HashMap::new();
+    let excludes = compile_excludes(&options.exclude)?;
+
+    for relative in walk(source_dir, &excludes)? {
+        let path = source_dir.join(&relative);
+        let meta = fs::symlink_metadata(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let file_type = meta.file_type();
+
+        let mut header = Header::new_gnu();
+        header.set_metadata_in_mode(&meta, HeaderMode::Complete);
+        header.set_mode(header.mode()? & 0o777);
+        if options.deterministic {
+            normalize_header(&mut header)?;
+        }
+
+        if file_type.is_symlink() {
+            let target = fs::read_link(&path)
+                .with_context(|| format!("Failed to read link {}", path.display()))?;
+            visitor.symlink(&relative, &target)?;
+            builder.append_link(&mut header, &relative, target)?;
+            continue;
+        }
+
+        if file_type.is_file() {
+            if let Some(inode) = shared_inode(&meta) {
+                if let Some(first) = first_links.get(&inode) {
+                    visitor.hard_link(&relative, &meta, first)?;
+                    header.set_entry_type(EntryType::Link);
+                    header.set_size(0);
+                    builder.append_link(&mut header, &relative, first)?;
+                    continue;
+                }
+                first_links.insert(inode, relative.clone());
+            }
+        } else if !file_type.is_dir() {
+            warn!("Skipping special file {}", path.display());
+            continue;
+        }
+
+        if options.xattrs {
+            let records = read_xattrs(&path)?;
+            builder.append_pax_extensions(
+                records
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_slice())),
+            )?;
+        }
+
+        if file_type.is_dir() {
+            builder.append_data(&mut header, &relative, io::empty())?;
+        } else {
+            let file = fs::File::open(&path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            visitor.file_start(&relative, &meta);
+            let reader = VisitedReader {
+                inner: file,
+                visitor: &mut *visitor,
+            };
+            builder.append_data(&mut header, &relative, reader)?;
+            visitor.file_end()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Clear everything but the path, type, size and permission bits of an
+/// entry, as a deterministic archive stores it
+fn normalize_header(header: &mut Header) -> Result<()> {
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_username("")?;
+    header.set_groupname("")?;
+    header.set_mtime(DETERMINISTIC_MTIME);
+    if let Some(gnu) = header.as_gnu_mut() {
+        gnu.set_atime(0);
+        gnu.set_ctime(0);
+    }
+    Ok(())
+}
+
+/// SHA-256 of the uncompressed tarball `append_tree` makes of `source_dir`.
+/// With `options.deterministic` it only changes when the tree does.
+pub fn tree_digest(source_dir: &Path, options: &ArchiveOptions) -> Result<String> {
+    visit_tree(source_dir, options, &mut ())
+}
+
+/// Show every entry of the archive of `source_dir` to `visitor` without
+/// keeping the archive, returning the SHA-256 of the uncompressed tarball
+pub fn visit_tree<V: TreeVisitor>(
+    source_dir: &Path,
+    options: &ArchiveOptions,
+    visitor: &mut V,
+) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut builder = Builder::new(&mut hasher);
+    append_tree_with(&mut builder, source_dir, options, visitor)?;
+    builder.finish()?;
+    drop(builder);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Unpack `archive` into `target_dir`, restoring what `append_tree` keeps.
+///
+/// The archive is refused as a whole, rather than entry by entry, if any
+/// entry has an absolute path or one with `..`, would be written through a
+/// symlink, replaces an earlier entry, hard links to a path that is not
+/// itself safe, or is a device file or FIFO. What was unpacked before the
+/// refusal is left for the caller to discard.
+pub fn unpack_tree<R: Read>(archive: &mut Archive<R>, target_dir: &Path) -> Result<()> {
+    archive.set_preserve_permissions(false);
+    archive.set_preserve_mtime(true);
+    archive.set_unpack_xattrs(true);
+    archive.set_overwrite(false);
+
+    // Directories get their permissions and times once their contents are
+    // in place: unpacking into them changes their mtime, and a read-only
+    // directory would refuse its contents
+    let mut directories = Vec::new();
+    let entries = archive
+        .entries()
+        .with_context(|| format!("Failed to read archive for {}", target_dir.display()))?;
+    for entry in entries {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        let relative = entry.path()?.into_owned();
+        let path = check_entry(target_dir, &relative, entry_type)
+            .with_context(|| format!("Refusing archive entry {}", relative.display()))?;
+        if entry_type.is_hard_link() {
+            let target = entry.link_name()?.unwrap_or_default().into_owned();
+            safe_path(target_dir, &target).with_context(|| {
+                format!(
+                    "Refusing hard link {} to {}",
+                    relative.display(),
+                    target.display()
+                )
+            })?;
+        }
+
+        let unpacked = entry
+            .unpack_in(target_dir)
+            .with_context(|| format!("Failed to extract archive to {}", target_dir.display()))?;
+        if !unpacked {
+            return Err(anyhow::anyhow!(
+                "Failed to extract {} to {}",
+                relative.display(),
+                target_dir.display()
+            ));
+        }
+        if !entry_type.is_dir() {
+            continue;
+        }
+
+        set_permission_bits(&path, 0o700)?;
+        directories.push((
+            path,
+            entry.header().mode()? & 0o777,
+            entry.header().mtime()?,
+        ));
+    }
+
+    for (path, mode, mtime) in directories.into_iter().rev() {
+        set_permission_bits(&path, mode)?;
+        filetime::set_file_mtime(&path, FileTime::from_unix_time(mtime as i64, 0))
+            .with_context(|| format!("Failed to set times of {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Where an entry of `entry_type` at `relative` unpacks to below `root`,
+/// if it is safe to unpack there
+fn check_entry(root: &Path, relative: &Path, entry_type: EntryType) -> Result<PathBuf> {
+    if matches!(
+        entry_type,
+        EntryType::Char | EntryType::Block | EntryType::Fifo
+    ) {
+        return Err(anyhow::anyhow!("special files are not restored"));
+    }
+
+    let path = safe_path(root, relative)?;
+    if path == root {
+        return Ok(path);
+    }
+
+    // Nothing is unpacked over an earlier entry, except that directories
+    // created on the way to earlier entries may still get their own
+    match fs::symlink_metadata(&path) {
+        Ok(meta) if meta.is_dir() && entry_type.is_dir() => Ok(path),
+        Ok(_) => Err(anyhow::anyhow!("path is already taken by an earlier entry")),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(path),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// `root` joined with `relative`, provided that stays within `root`:
+/// `relative` may not be absolute, contain `..` or lead through a symlink
+pub(crate) fn safe_path(root: &Path, relative: &Path) -> Result<PathBuf> {
+    let mut path = root.to_path_buf();
+    let mut components = relative.components().peekable();
+
+    while let Some(component) = components.next() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => continue,
+            Component::ParentDir => {
+                return Err(anyhow::anyhow!("path contains `..`"));
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(anyhow::anyhow!("path is absolute"));
+            }
+        }
+
+        if components.peek().is_some()
+            && fs::symlink_metadata(&path).is_ok_and(|meta| meta.is_symlink())
+        {
+            return Err(anyhow::anyhow!(
+                "path leads through the symlink {}",
+                path.display()
+            ));
+        }
+    }
+
+    Ok(path)
+}
+
+/// Populate `root` with one of each kind of entry `ArchiveOptions` covers:
+/// executables, private files and directories, relative, absolute and
+/// outward-pointing symlinks, hard links, old mtimes and, when enabled and
+/// supported, an extended attribute
+pub fn create_sample_tree(root: &Path, options: &ArchiveOptions) -> Result<()> {
+    let files = [
+        ("bin/tool", "#!/bin/sh\necho sample\n", 0o755),
+        ("lib/libsample.so.1.2.3", "not really a library\n", 0o644),
+        ("lib/private/data", "secret\n", 0o600),
+        ("share/doc/README", "sample tree\n", 0o444),
+    ];
+    for (name, content, mode) in files {
+        let path = root.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        set_permission_bits(&path, mode)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+        symlink("libsample.so.1.2.3", root.join("lib/libsample.so"))?;
+        symlink("../lib", root.join("bin/lib"))?;
+        symlink("/usr/bin/env", root.join("bin/env"))?;
+        symlink("../../outside-the-tree", root.join("lib/outside"))?;
+        fs::hard_link(root.join("bin/tool"), root.join("bin/tool-alias"))?;
+    }
+
+    if options.xattrs {
+        #[cfg(unix)]
+        if let Err(e) = xattr::set(root.join("bin/tool"), "user.mise-s3-cache", b"sample") {
+            warn!(
+                "Cannot set extended attributes in {}: {}",
+                root.display(),
+                e
+            );
+        }
+    }
+
+    set_permission_bits(&root.join("lib/private"), 0o750)?;
+
+    // Directories last, since adding entries to them updates their mtime
+    let sample_time = FileTime::from_unix_time(1_600_000_000, 0);
+    for relative in walk(root, &GlobSet::empty())?.into_iter().rev() {
+        let path = root.join(relative);
+        if !fs::symlink_metadata(&path)?.is_symlink() {
+            filetime::set_file_mtime(&path, sample_time)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Paths below `root`, relative to it, with every directory listed before
+/// its contents and names in sorted order. Symlinked directories are not
+/// descended into, and neither paths matching `excludes` nor anything below
+/// them are listed.
+pub(crate) fn walk(root: &Path, excludes: &GlobSet) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(dir) = pending.pop() {
+        let absolute = root.join(&dir);
+        let mut names: Vec<_> = fs::read_dir(&absolute)
+            .with_context(|| format!("Failed to read {}", absolute.display()))?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<io::Result<_>>()?;
+        names.sort();
+
+        let mut subdirs = Vec::new();
+        for name in names {
+            let relative = dir.join(name);
+            if excludes.is_match(&relative) {
+                continue;
+            }
+            if fs::symlink_metadata(root.join(&relative))?.is_dir() {
+                subdirs.push(relative.clone());
+            }
+            paths.push(relative);
+        }
+        pending.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(paths)
+}
+
+/// Device and inode of a file with more than one name
+#[cfg(unix)]
+pub(crate) fn shared_inode(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (meta.nlink() > 1).then(|| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn shared_inode(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Archivable extended attributes of `path` as PAX records
+#[cfg(unix)]
+fn read_xattrs(path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut records = Vec::new();
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        // The filesystem has no extended attributes to keep
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => return Ok(records),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to list xattrs of {}", path.display()))
+        }
+    };
+
+    for name in names {
+        let name = name.to_string_lossy().to_string();
+        if SKIPPED_XATTR_NAMESPACES
+            .iter()
+            .any(|namespace| name.starts_with(namespace))
+        {
+            continue;
+        }
+        if let Some(value) = xattr::get(path, &name)? {
+            records.push((format!("{}{}", PAX_XATTR_PREFIX, name), value));
+        }
+    }
+
+    records.sort();
+    Ok(records)
+}
+
+#[cfg(not(unix))]
+fn read_xattrs(_path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    Ok(Vec::new())
+}
+
+/// Everything the fidelity policy keeps about one entry of a tree
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    kind: String,
+    mode: Option<u32>,
+    mtime: Option<u64>,
+    xattrs: Vec<(String, Vec<u8>)>,
+    /// Other names of the same file within the tree
+    links: Vec<PathBuf>,
+}
+
+fn snapshot_tree(root: &Path, options: &ArchiveOptions) -> Result<BTreeMap<PathBuf, Snapshot>> {
+    let mut snapshots = BTreeMap::new();
+    let mut inodes: HashMap<(u64, u64), Vec<PathBuf>> = HashMap::new();
+    let excludes = compile_excludes(&options.exclude)?;
+
+    for relative in walk(root, &excludes)? {
+        let path = root.join(&relative);
+        let meta = fs::symlink_metadata(&path)?;
+        let file_type = meta.file_type();
+
+        let kind = if file_type.is_symlink() {
+            format!("symlink to {}", fs::read_link(&path)?.display())
+        } else if file_type.is_dir() {
+            "directory".to_string()
+        } else if file_type.is_file() {
+            format!(
+                "file of {} bytes with SHA-256 {}",
+                meta.len(),
+                utils::calculate_file_hash(&path)?
+            )
+        } else {
+            // Not archived, so not compared either
+            continue;
+        };
+
+        if let Some(inode) = shared_inode(&meta).filter(|_| file_type.is_file()) {
+            inodes.entry(inode).or_default().push(relative.clone());
+        }
+
+        let (mode, mtime, xattrs) = if file_type.is_symlink() {
+            (None, None, Vec::new())
+        } else {
+            let mtime = meta
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .ok();
+            let xattrs = if options.xattrs {
+                read_xattrs(&path)?
+            } else {
+                Vec::new()
+            };
+            (permission_bits(&meta), mtime, xattrs)
+        };
+
+        snapshots.insert(
+            relative,
+            Snapshot {
+                kind,
+                mode,
+                mtime,
+                xattrs,
+                links: Vec::new(),
+            },
+        );
+    }
+
+    for names in inodes.into_values() {
+        for name in &names {
+            let others = names.iter().filter(|other| *other != name).cloned();
+            if let Some(snapshot) = snapshots.get_mut(name) {
+                snapshot.links.extend(others);
+            }
+        }
+    }
+
+    Ok(snapshots)
+}
+
+#[cfg(unix)]
+pub(crate) fn permission_bits(meta: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(meta.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn permission_bits(_meta: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_permission_bits(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to set permissions of {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn set_permission_bits(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Describe every way the tree at `actual` differs from `expected` in what
+/// `ArchiveOptions` promises to keep. Returns nothing if they match.
+pub fn compare_trees(
+    expected: &Path,
+    actual: &Path,
+    options: &ArchiveOptions,
+) -> Result<Vec<String>> {
+    let expected = snapshot_tree(expected, options)?;
+    let mut actual = snapshot_tree(actual, options)?;
+    let mut differences = Vec::new();
+
+    for (path, want) in expected {
+        let Some(got) = actual.remove(&path) else {
+            differences.push(format!("{}: missing", path.display()));
+            continue;
+        };
+
+        if want.kind != got.kind {
+            differences.push(format!(
+                "{}: expected {}, found {}",
+                path.display(),
+                want.kind,
+                got.kind
+            ));
+            continue;
+        }
+        if want.mode != got.mode {
+            differences.push(format!(
+                "{}: mode {:o} instead of {:o}",
+                path.display(),
+                got.mode.unwrap_or_default(),
+                want.mode.unwrap_or_default()
+            ));
+        }
+        // Deterministic archives store one fixed time for everything
+        let want_mtime = if options.deterministic {
+            want.mtime.map(|_| DETERMINISTIC_MTIME)
+        } else {
+            want.mtime
+        };
+        if want_mtime != got.mtime {
+            differences.push(format!(
+                "{}: modified at {} instead of {}",
+                path.display(),
+                got.mtime.unwrap_or_default(),
+                want_mtime.unwrap_or_default()
+            ));
+        }
+        if want.xattrs != got.xattrs {
+            differences.push(format!("{}: extended attributes differ", path.display()));
+        }
+        if want.links != got.links {
+            differences.push(format!(
+                "{}: hard linked with {:?} instead of {:?}",
+                path.display(),
+                got.links,
+                want.links
+            ));
+        }
+    }
+
+    for path in actual.keys() {
+        differences.push(format!("{}: unexpected", path.display()));
+    }
+
+    Ok(differences)
+}
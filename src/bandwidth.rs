@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::Bytes;
+use futures::stream::{self, Stream, StreamExt};
+use http_body::Frame;
+use http_body_util::StreamBody;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::config::Config;
+
+/// Request bodies are handed to the limiter in pieces of this size, so a
+/// throttled transfer trickles out instead of sending in bursts
+pub const PIECE_SIZE: usize = 64 * 1024;
+
+/// Token bucket capping the combined throughput of every transfer sharing it.
+///
+/// Each caller reserves the bytes it is about to move and sleeps off any
+/// deficit, so concurrent parts and ranges split the rate between them.
+pub struct RateLimiter {
+    bytes_per_second: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    available: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: u64) -> Self {
+        let bytes_per_second = bytes_per_second.max(1) as f64;
+        Self {
+            bytes_per_second,
+            bucket: Mutex::new(Bucket {
+                available: bytes_per_second,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// A shared limiter for `bytes_per_second`, or none when it is 0
+    pub fn shared(bytes_per_second: u64) -> Option<Arc<Self>> {
+        (bytes_per_second > 0).then(|| Arc::new(Self::new(bytes_per_second)))
+    }
+
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second as u64
+    }
+
+    /// Reserve `bytes`, returning how long to wait before moving them
+    pub fn reserve(&self, bytes: u64) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+
+        // Refill for the time passed, allowing at most one second of burst
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * self.bytes_per_second;
+        bucket.available = (bucket.available + refill).min(self.bytes_per_second);
+        bucket.updated = now;
+
+        bucket.available -= bytes as f64;
+        if bucket.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.available / self.bytes_per_second)
+        }
+    }
+
+    /// Wait until `bytes` may be transferred
+    pub async fn consume(&self, bytes: u64) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// The upload and download limiters of one run, shared by every backend it
+/// talks to so replicas and fallbacks split the configured rates instead of
+/// each getting the full amount
+#[derive(Clone, Default)]
+pub struct RateLimits {
+    pub upload: Option<Arc<RateLimiter>>,
+    pub download: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            upload: RateLimiter::shared(config.upload_limit),
+            download: RateLimiter::shared(config.download_limit),
+        }
+    }
+}
+
+/// Wait until `bytes` may be transferred, if there is a limiter at all
+pub async fn throttle(limiter: Option<&RateLimiter>, bytes: u64) {
+    if let Some(limiter) = limiter {
+        limiter.consume(bytes).await;
+    }
+}
+
+/// Request body that releases `data` no faster than `limiter` allows
+pub fn throttled_bytes(data: Bytes, limiter: Arc<RateLimiter>) -> ByteStream {
+    let pieces = (0..data.len())
+        .step_by(PIECE_SIZE)
+        .map(move |start| data.slice(start..(start + PIECE_SIZE).min(data.len())));
+
+    throttled_body(stream::iter(pieces).map(Ok), limiter)
+}
+
+/// Request body reading `length` bytes of `path` from `offset`, no faster
+/// than `limiter` allows
+pub fn throttled_file(
+    path: PathBuf,
+    offset: u64,
+    length: u64,
+    limiter: Arc<RateLimiter>,
+) -> ByteStream {
+    let pieces = stream::try_unfold(
+        (None::<tokio::io::Take<tokio::fs::File>>, path),
+        move |(reader, path)| async move {
+            let mut reader = match reader {
+                Some(reader) => reader,
+                None => {
+                    let mut file = tokio::fs::File::open(&path).await?;
+                    file.seek(io::SeekFrom::Start(offset)).await?;
+                    file.take(length)
+                }
+            };
+
+            let mut piece = vec![0u8; PIECE_SIZE];
+            let n = reader.read(&mut piece).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            piece.truncate(n);
+            Ok::<_, io::Error>(Some((Bytes::from(piece), (Some(reader), path))))
+        },
+    );
+
+    throttled_body(pieces, limiter)
+}
+
+fn throttled_body<S>(pieces: S, limiter: Arc<RateLimiter>) -> ByteStream
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+{
+    let frames = pieces.then(move |piece| {
+        let limiter = limiter.clone();
+        async move {
+            let piece = piece?;
+            limiter.consume(piece.len() as u64).await;
+            Ok::<_, io::Error>(Frame::data(piece))
+        }
+    });
+
+    ByteStream::from_body_1_x(StreamBody::new(Box::pin(frames)))
+}
@@ -1,25 +1,33 @@
 #![allow(dead_code)]
 
 use anyhow::{Context, Result};
-use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-// use std::io::{Read, Write};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 // use std::time::{SystemTime, UNIX_EPOCH};
 use tar::{Archive, Builder};
 use tempfile::TempDir;
 use tokio::fs;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
+use crate::archive::{self, ArchiveOptions, TreeVisitor};
+use crate::chunks::{self, ChunkEncoding, ChunkIndex};
+use crate::compression::{self, Compression};
 use crate::config::Config;
-use crate::s3_operations::S3Client;
+use crate::encryption::{self, DecryptingReader, EncryptingWriter, EncryptionKey};
+use crate::local_cache::LocalCache;
+use crate::manifest::{self, ManifestBuilder, ManifestEntry, ManifestReport, Relocation};
+use crate::partial_download;
+use crate::relocate::{self, PrefixReference, PrefixScan, PrefixScanner};
+use crate::storage::{ObjectInfo, RemoteTier, StorageBackend};
+use crate::streaming::{self, ChunkReader, HashingReader, HashingWriter};
 use crate::tool_detection::ToolDetector;
 use crate::utils;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheMetadata {
     pub tool: String,
     pub version: String,
@@ -30,6 +38,105 @@ pub struct CacheMetadata {
     pub checksum: String,
     pub mise_version: String,
     pub compressed: bool,
+    /// Archive format; entries written before it was recorded are gzip
+    #[serde(default = "default_compression")]
+    pub compression: String,
+    /// Compression level the archive was written with, when one was set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<i32>,
+    /// Set when the archive was encrypted on the client before upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<ArchiveEncryption>,
+    /// `archive` or `chunked`; entries written before it was recorded are
+    /// archives
+    #[serde(default = "default_storage_mode")]
+    pub storage_mode: String,
+    /// Absolute path the tool was installed at when it was stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_path: Option<String>,
+    /// Entries that refer to `install_path`, rewritten on restores to
+    /// another path
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefix_references: Vec<PrefixReference>,
+    /// Exclude patterns whose matches were left out of the archive
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// SHA-256 of the uncompressed tarball, recorded for deterministic
+    /// archives so unchanged installs can be recognized before uploading
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_digest: Option<String>,
+    /// Every file and symlink in the archive, to check installs against.
+    /// Absent from entries written before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Vec<ManifestEntry>>,
+    /// The prefix references, content digest and manifest of an encrypted
+    /// entry, sealed with its key so the file names and hashes of the
+    /// install stay private. Hex-encoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<String>,
+}
+
+/// The fields of `CacheMetadata` that describe the files of an install
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SealedFields {
+    #[serde(default)]
+    prefix_references: Vec<PrefixReference>,
+    #[serde(default)]
+    content_digest: Option<String>,
+    #[serde(default)]
+    manifest: Option<Vec<ManifestEntry>>,
+}
+
+impl CacheMetadata {
+    /// Move the fields that describe the files of the install into `sealed`,
+    /// encrypted with `key`
+    pub fn seal(&mut self, key: Arc<EncryptionKey>) -> Result<()> {
+        let fields = SealedFields {
+            prefix_references: std::mem::take(&mut self.prefix_references),
+            content_digest: self.content_digest.take(),
+            manifest: self.manifest.take(),
+        };
+
+        let mut writer = EncryptingWriter::new(Vec::new(), key)?;
+        serde_json::to_writer(&mut writer, &fields)?;
+        self.sealed = Some(hex::encode(writer.finish()?));
+        Ok(())
+    }
+
+    /// Decrypt the fields moved into `sealed` by `seal` with `key`
+    pub fn unseal(&mut self, key: Arc<EncryptionKey>) -> Result<()> {
+        let Some(sealed) = &self.sealed else {
+            return Ok(());
+        };
+
+        let data = hex::decode(sealed).context("Sealed metadata is not hex-encoded")?;
+        let mut json = Vec::new();
+        DecryptingReader::new(data.as_slice(), key)?
+            .read_to_end(&mut json)
+            .context("Failed to decrypt sealed metadata")?;
+        let fields: SealedFields = serde_json::from_slice(&json)?;
+
+        self.prefix_references = fields.prefix_references;
+        self.content_digest = fields.content_digest;
+        self.manifest = fields.manifest;
+        self.sealed = None;
+        Ok(())
+    }
+}
+
+fn default_compression() -> String {
+    Compression::Gzip.name().to_string()
+}
+
+fn default_storage_mode() -> String {
+    chunks::ARCHIVE.to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEncryption {
+    pub algorithm: String,
+    /// Fingerprint of the key the archive was sealed with
+    pub key_id: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -54,43 +161,119 @@ pub struct ToolStats {
 #[derive(Clone)]
 pub struct CacheManager {
     config: Config,
-    s3_client: S3Client,
+    backend: Arc<dyn StorageBackend>,
+    fallbacks: Vec<RemoteTier>,
+    replicas: Vec<RemoteTier>,
+    local_cache: LocalCache,
     tool_detector: ToolDetector,
+    encryption_key: Option<Arc<EncryptionKey>>,
 }
 
 impl CacheManager {
-    pub fn new(config: Config, s3_client: S3Client) -> Self {
+    pub fn new(config: Config, backend: Arc<dyn StorageBackend>) -> Self {
         let tool_detector = ToolDetector::new();
+        let local_cache = LocalCache::new(
+            config.get_cache_dir().join("archives"),
+            config.local_cache_max_size,
+        );
 
         Self {
             config,
-            s3_client,
+            backend,
+            fallbacks: Vec::new(),
+            replicas: Vec::new(),
+            local_cache,
             tool_detector,
+            encryption_key: None,
         }
     }
 
+    /// Remote caches tried in order after the primary backend misses
+    pub fn with_fallbacks(mut self, fallbacks: Vec<RemoteTier>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    /// Additional caches that every store is copied to
+    pub fn with_replicas(mut self, replicas: Vec<RemoteTier>) -> Self {
+        self.replicas = replicas;
+        self
+    }
+
+    /// Key that stored archives are encrypted with and that encrypted
+    /// entries are decrypted with on restore
+    pub fn with_encryption_key(mut self, key: Option<Arc<EncryptionKey>>) -> Self {
+        self.encryption_key = key;
+        self
+    }
+
     pub async fn check_cache(&self, tool: &str, version: &str) -> Result<bool> {
         self.validate_tool_version(tool, version).await?;
 
-        let cache_key = self.config.get_cache_key(tool, version);
-        let metadata_key = format!("{}/metadata.json", cache_key);
+        Ok(self.find_cached_tier(tool, version).await?.is_some())
+    }
 
-        self.s3_client.object_exists(&metadata_key).await
+    /// Find the first cache, in priority order, holding an entry for the tool.
+    ///
+    /// Returns the index of the tier (0 is the primary backend). Unreachable
+    /// caches are skipped; an error is only returned if every cache failed.
+    async fn find_cached_tier(&self, tool: &str, version: &str) -> Result<Option<usize>> {
+        let mut first_error = None;
+        let mut reachable = false;
+
+        for (index, (config, backend)) in self.tiers().enumerate() {
+            let metadata_key = format!("{}/metadata.json", config.get_cache_key(tool, version));
+
+            match backend.exists(&metadata_key).await {
+                Ok(true) => {
+                    if index > 0 {
+                        debug!("Found {tool}@{version} in fallback {}", backend.location());
+                    }
+                    return Ok(Some(index));
+                }
+                Ok(false) => reachable = true,
+                Err(e) => {
+                    warn!("Failed to check {}: {}", backend.location(), e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) if !reachable => Err(e),
+            _ => Ok(None),
+        }
+    }
+
+    /// The primary backend followed by the fallbacks, in priority order
+    fn tiers(&self) -> impl Iterator<Item = (&Config, &Arc<dyn StorageBackend>)> {
+        std::iter::once((&self.config, &self.backend)).chain(
+            self.fallbacks
+                .iter()
+                .map(|tier| (&tier.config, &tier.backend)),
+        )
     }
 
     /// Restore tool from cache using standard mise install path
     pub async fn restore_tool_from_cache(&self, tool: &str, version: &str) -> Result<bool> {
-        // Get the standard mise install path for this tool
-        let install_path = match self.get_tool_install_path(tool, version).await {
-            Ok(path) => path.to_string_lossy().to_string(),
+        let install_path = self.standard_install_path(tool, version).await?;
+        self.restore_from_cache(tool, version, &install_path.to_string_lossy())
+            .await
+    }
+
+    /// Where mise installs the tool, or would by default
+    async fn standard_install_path(&self, tool: &str, version: &str) -> Result<PathBuf> {
+        match self.get_tool_install_path(tool, version).await {
+            Ok(path) => Ok(path),
             Err(_) => {
                 // If we can't get the path, use the standard mise pattern
                 let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Cannot determine home directory"))?;
-                format!("{}/.local/share/mise/installs/{}/{}", home.display(), tool, version)
+                Ok(home
+                    .join(".local/share/mise/installs")
+                    .join(tool)
+                    .join(version))
             }
-        };
-        
-        self.restore_from_cache(tool, version, &install_path).await
+        }
     }
 
     pub async fn restore_from_cache(
@@ -102,78 +285,490 @@ impl CacheManager {
         let start_time = std::time::Instant::now();
         self.validate_tool_version(tool, version).await?;
 
-        let cache_key = self.config.get_cache_key(tool, version);
-        let archive_key = format!("{}/archive.tar.gz", cache_key);
-        let metadata_key = format!("{}/metadata.json", cache_key);
-        let checksum_key = format!("{}/checksum.sha256", cache_key);
-
         // Check if cache entry exists
-        if !self.s3_client.object_exists(&metadata_key).await? {
+        let Some(tier) = self.find_cached_tier(tool, version).await? else {
             debug!("Cache miss: {tool}@{version} - metadata not found");
             self.update_stats(tool, version, false, 0, "not_found")
                 .await?;
             return Ok(false);
-        }
+        };
+        let (tier_config, backend) = self.tiers().nth(tier).expect("tier index in range");
 
-        info!("📦 Restoring {tool}@{version} from S3 cache");
+        let cache_key = tier_config.get_cache_key(tool, version);
+        let checksum_key = format!("{}/checksum.sha256", cache_key);
 
-        // Create temp directory for downloads
-        let temp_dir = TempDir::new()?;
-        let temp_archive = temp_dir.path().join("archive.tar.gz");
+        info!(
+            "📦 Restoring {tool}@{version} from cache ({})",
+            backend.location()
+        );
 
-        // Download archive and checksum
-        match self
-            .s3_client
-            .download_file(&archive_key, &temp_archive)
-            .await
-        {
-            Ok(_) => {}
+        // The metadata says how the archive was written, so nothing is
+        // unpacked without it
+        let mut metadata = match self.fetch_metadata(backend, &cache_key).await {
+            Ok(metadata) => metadata,
             Err(e) => {
-                warn!("Failed to download archive for {tool}@{version}: {e}");
-                self.update_stats(tool, version, false, 0, "download_failed")
+                warn!("Cannot restore {tool}@{version}: {e:#}");
+                self.update_stats(tool, version, false, 0, "invalid_metadata")
                     .await?;
                 return Ok(false);
             }
-        }
+        };
+        let compression = match Compression::parse(&metadata.compression) {
+            Ok(compression) => compression,
+            Err(e) => {
+                warn!("Cannot restore {tool}@{version}: {e}");
+                self.update_stats(tool, version, false, 0, "unsupported_format")
+                    .await?;
+                return Ok(false);
+            }
+        };
+        let archive_key = format!("{}/{}", cache_key, compression.archive_name());
+
+        let chunked = match metadata.storage_mode.as_str() {
+            chunks::CHUNKED => true,
+            chunks::ARCHIVE => false,
+            mode => {
+                warn!("Cannot restore {tool}@{version}: unsupported storage mode {mode}");
+                self.update_stats(tool, version, false, 0, "unsupported_format")
+                    .await?;
+                return Ok(false);
+            }
+        };
 
-        // Verify checksum if available
-        if let Ok(expected_checksum) = self.s3_client.download_string(&checksum_key).await {
-            let actual_checksum = utils::calculate_file_hash(&temp_archive)?;
-            if expected_checksum.trim() != actual_checksum {
-                warn!("Checksum mismatch for {tool}@{version}");
-                self.update_stats(tool, version, false, 0, "checksum_mismatch")
+        let decryption_key = match metadata.encryption.clone() {
+            Some(encryption) => match self.decryption_key(&encryption) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    warn!("Cannot restore {tool}@{version}: {e}");
+                    self.update_stats(tool, version, false, 0, "decryption_failed")
+                        .await?;
+                    return Ok(false);
+                }
+            },
+            None => None,
+        };
+        if let Some(key) = &decryption_key {
+            if let Err(e) = metadata.unseal(key.clone()) {
+                warn!("Cannot restore {tool}@{version}: {e:#}");
+                self.update_stats(tool, version, false, 0, "decryption_failed")
                     .await?;
                 return Ok(false);
             }
-            debug!("✅ Checksum verified for {tool}@{version}");
         }
 
-        // Extract archive to install path
+        // The staged tree is only promoted once it matches a checksum, taken
+        // from the metadata when checksum.sha256 cannot be read
+        let expected_checksum = match backend.get_string(&checksum_key).await {
+            Ok(checksum) => checksum.trim().to_string(),
+            Err(e) => {
+                debug!("Using the metadata checksum of {tool}@{version}: {e}");
+                metadata.checksum.trim().to_string()
+            }
+        };
+        if expected_checksum.is_empty() {
+            warn!("Cannot restore {tool}@{version}: the entry has no checksum to verify");
+            self.update_stats(tool, version, false, 0, "checksum_missing")
+                .await?;
+            return Ok(false);
+        }
+
+        // Prefer an archive already held in the local cache. Chunked entries
+        // have no single archive object to keep there.
+        let mut local_hit = if chunked {
+            None
+        } else {
+            self.local_cache
+                .lookup(&archive_key, metadata.size_bytes)
+                .await
+        };
+        if local_hit.is_some() {
+            debug!("Local cache hit for {tool}@{version}");
+        }
+
+        // Keep a copy of downloads for the local cache while they stream past
+        let mut spool_path = match (&local_hit, chunked) {
+            (None, false) => self.local_spool_path(),
+            _ => None,
+        };
+
+        // Unpack next to the install path and only move it into place once
+        // the checksum of the whole archive has been confirmed
         let install_path = PathBuf::from(install_path);
-        fs::create_dir_all(&install_path).await?;
-
-        match self.extract_archive(&temp_archive, &install_path).await {
-            Ok(_) => {
-                let duration = start_time.elapsed();
-                info!(
-                    "✅ Restored {tool}@{version} from cache in {}ms",
-                    duration.as_millis()
-                );
+        let staging_dir = sibling_path(&install_path, "staging");
+        fs::create_dir_all(&staging_dir)
+            .await
+            .with_context(|| format!("Failed to create {}", staging_dir.display()))?;
 
-                // Get file size for stats
-                let _file_size = fs::metadata(&temp_archive).await?.len();
-                self.update_stats(tool, version, true, duration.as_millis() as u64, "success")
-                    .await?;
+        let encoding = ArchiveEncoding {
+            compression,
+            threads: compression::thread_count(self.config.compression_threads),
+            key: decryption_key,
+        };
+        let mut unpacked = if chunked {
+            self.unpack_chunks_to_staging(
+                backend,
+                &tier_config.prefix,
+                &cache_key,
+                &staging_dir,
+                encoding.clone(),
+            )
+            .await
+        } else {
+            self.unpack_to_staging(
+                backend,
+                &archive_key,
+                local_hit.as_deref(),
+                spool_path.as_deref(),
+                &staging_dir,
+                encoding.clone(),
+            )
+            .await
+        };
+
+        // The local copy was only matched by name and size, so the checksum
+        // taken while unpacking it is the real check. Drop a bad copy and
+        // download the entry instead.
+        let local_verified = matches!(&unpacked, Ok((actual, _)) if *actual == expected_checksum);
+        if local_hit.is_some() && !local_verified {
+            warn!("Local cache copy of {tool}@{version} is corrupt, downloading it again");
+            self.local_cache.evict(&archive_key).await;
+            local_hit = None;
+            let _ = fs::remove_dir_all(&staging_dir).await;
+            fs::create_dir_all(&staging_dir)
+                .await
+                .with_context(|| format!("Failed to create {}", staging_dir.display()))?;
+
+            spool_path = self.local_spool_path();
+            unpacked = self
+                .unpack_to_staging(
+                    backend,
+                    &archive_key,
+                    None,
+                    spool_path.as_deref(),
+                    &staging_dir,
+                    encoding,
+                )
+                .await;
+        }
 
-                Ok(true)
+        let failure = match &unpacked {
+            Err((status, e)) => Some((*status, format!("{e:#}"))),
+            Ok((actual, _)) if *actual != expected_checksum => {
+                Some(("checksum_mismatch", "checksum mismatch".to_string()))
             }
-            Err(e) => {
-                error!("Failed to extract {tool}@{version}: {e}");
-                self.update_stats(tool, version, false, 0, "extraction_failed")
+            Ok(_) => None,
+        };
+        let failure = match failure {
+            None => self
+                .relocate_staging(tool, version, metadata, &staging_dir, &install_path)
+                .await
+                .err()
+                .map(|e| ("relocation_failed", format!("{e:#}"))),
+            failure => failure,
+        };
+        let failure = match failure {
+            Some(failure) => Some(failure),
+            None => promote_staging(&staging_dir, &install_path)
+                .await
+                .err()
+                .map(|e| ("extraction_failed", format!("{e:#}"))),
+        };
+
+        if let Some((status, reason)) = failure {
+            let _ = fs::remove_dir_all(&staging_dir).await;
+            if let Some(spool_path) = &spool_path {
+                let _ = fs::remove_file(spool_path).await;
+            }
+
+            match status {
+                "extraction_failed" => error!("Failed to extract {tool}@{version}: {reason}"),
+                _ => warn!("Failed to restore {tool}@{version}: {reason}"),
+            }
+            self.update_stats(tool, version, false, 0, status).await?;
+            return Ok(false);
+        }
+
+        let duration = start_time.elapsed();
+        info!(
+            "✅ Restored {tool}@{version} from cache in {}ms",
+            duration.as_millis()
+        );
+        self.update_stats(tool, version, true, duration.as_millis() as u64, "success")
+            .await?;
+
+        let mut archive_path = local_hit;
+        if let Some(spool_path) = &spool_path {
+            match self.local_cache.adopt(&archive_key, spool_path).await {
+                Ok(path) => archive_path = path,
+                Err(e) => {
+                    warn!("Failed to keep {tool}@{version} in local cache: {e}");
+                    let _ = fs::remove_file(spool_path).await;
+                }
+            }
+        }
+
+        // Read-through: copy entries found in a fallback into the primary
+        if tier > 0 {
+            if let Err(e) = self
+                .backfill_primary(
+                    tool,
+                    version,
+                    tier,
+                    archive_path.as_deref(),
+                    &expected_checksum,
+                )
+                .await
+            {
+                debug!("Could not backfill {tool}@{version} into primary cache: {e}");
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// A spool file for the local cache, or `None` when it is disabled or
+    /// cannot be used
+    fn local_spool_path(&self) -> Option<PathBuf> {
+        self.local_cache.spool_path().unwrap_or_else(|e| {
+            warn!("Local cache unavailable: {e}");
+            None
+        })
+    }
+
+    /// Read and parse the metadata.json of the entry at `cache_key`
+    async fn fetch_metadata(
+        &self,
+        backend: &Arc<dyn StorageBackend>,
+        cache_key: &str,
+    ) -> Result<CacheMetadata> {
+        let metadata_key = format!("{}/metadata.json", cache_key);
+        let json = backend.get_string(&metadata_key).await?;
+        serde_json::from_str(&json).with_context(|| format!("Invalid metadata in {}", metadata_key))
+    }
+
+    /// Point the references of an entry stored from another install path,
+    /// unpacked into `staging_dir`, at `install_path`
+    async fn relocate_staging(
+        &self,
+        tool: &str,
+        version: &str,
+        metadata: CacheMetadata,
+        staging_dir: &Path,
+        install_path: &Path,
+    ) -> Result<()> {
+        let Some(old_prefix) = metadata.install_path else {
+            return Ok(());
+        };
+        let new_prefix = std::path::absolute(install_path)?
+            .to_string_lossy()
+            .to_string();
+        if old_prefix == new_prefix || metadata.prefix_references.is_empty() {
+            return Ok(());
+        }
+
+        debug!(
+            "Relocating {} entries of {tool}@{version} from {old_prefix} to {new_prefix}",
+            metadata.prefix_references.len()
+        );
+        let staging_dir = staging_dir.to_path_buf();
+        let (references, old, new) = (metadata.prefix_references, old_prefix.clone(), new_prefix);
+        let skipped = tokio::task::spawn_blocking(move || {
+            relocate::relocate(&staging_dir, &references, &old, &new)
+        })
+        .await??;
+
+        if !skipped.is_empty() {
+            warn!(
+                "{} binary files of {tool}@{version} still refer to {old_prefix}, which is shorter than the restore path: {}",
+                skipped.len(),
+                summarize(&skipped)
+            );
+        }
+        Ok(())
+    }
+
+    /// The configured key, if it is the one `encryption` was sealed with
+    fn decryption_key(&self, encryption: &ArchiveEncryption) -> Result<Arc<EncryptionKey>> {
+        if encryption.algorithm != encryption::ALGORITHM {
+            return Err(anyhow::anyhow!(
+                "unsupported encryption algorithm {}",
+                encryption.algorithm
+            ));
+        }
+
+        match &self.encryption_key {
+            Some(key) if key.id() == encryption.key_id => Ok(key.clone()),
+            Some(key) => Err(anyhow::anyhow!(
+                "archive is encrypted with key {}, but key {} is configured",
+                encryption.key_id,
+                key.id()
+            )),
+            None => Err(anyhow::anyhow!(
+                "archive is encrypted with key {} and no encryption key is configured",
+                encryption.key_id
+            )),
+        }
+    }
+
+    /// Unpack an archive into `staging_dir` while it streams in, either from
+    /// the local cache or from `backend`, hashing the stored bytes on the
+    /// way. Downloads are also copied to `spool_path` when given.
+    ///
+    /// Returns the archive checksum and size, or the stats status and error.
+    async fn unpack_to_staging(
+        &self,
+        backend: &Arc<dyn StorageBackend>,
+        archive_key: &str,
+        local_archive: Option<&Path>,
+        spool_path: Option<&Path>,
+        staging_dir: &Path,
+        encoding: ArchiveEncoding,
+    ) -> std::result::Result<(String, u64), (&'static str, anyhow::Error)> {
+        let staging_dir = staging_dir.to_path_buf();
+
+        if let Some(local_archive) = local_archive {
+            let local_archive = local_archive.to_path_buf();
+            return tokio::task::spawn_blocking(move || {
+                let file = std::fs::File::open(&local_archive)
+                    .with_context(|| format!("Failed to open {}", local_archive.display()))?;
+                unpack_archive(HashingReader::new(file), encoding, &staging_dir)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result)
+            .map_err(|e| ("extraction_failed", e));
+        }
+
+        let (tx, rx) = streaming::chunk_channel();
+        let spool_path = spool_path.map(Path::to_path_buf);
+
+        let extractor = tokio::task::spawn_blocking(move || {
+            let mut reader = HashingReader::new(ChunkReader::new(rx));
+            if let Some(spool_path) = &spool_path {
+                let spool = std::fs::File::create(spool_path)
+                    .with_context(|| format!("Failed to create {}", spool_path.display()))?;
+                reader = reader.with_spool(spool);
+            }
+            unpack_archive(reader, encoding, &staging_dir)
+        });
+
+        let download = async move {
+            let result = backend.get_stream(archive_key, tx.clone()).await;
+            if let Err(e) = &result {
+                let _ = tx.send(Err(anyhow::anyhow!("{:#}", e))).await;
+            }
+            result
+        };
+
+        let (downloaded, extracted) = tokio::join!(download, extractor);
+        unpack_outcome(downloaded, extracted)
+    }
+
+    /// Reassemble a chunked entry from its chunks under `prefix` and unpack
+    /// the tarball into `staging_dir` as they stream in.
+    ///
+    /// Returns the tarball checksum and size, or the stats status and error.
+    async fn unpack_chunks_to_staging(
+        &self,
+        backend: &Arc<dyn StorageBackend>,
+        prefix: &str,
+        cache_key: &str,
+        staging_dir: &Path,
+        encoding: ArchiveEncoding,
+    ) -> std::result::Result<(String, u64), (&'static str, anyhow::Error)> {
+        let index = backend
+            .get_string(&chunks::index_key(cache_key))
+            .await
+            .and_then(|json| Ok(serde_json::from_str::<ChunkIndex>(&json)?))
+            .map_err(|e| ("download_failed", e))?;
+        let chunk_encoding = ChunkEncoding {
+            compression: encoding.compression,
+            level: None,
+            key: encoding.key,
+        };
+
+        let (tx, rx) = streaming::chunk_channel();
+        let staging_dir = staging_dir.to_path_buf();
+
+        // Chunks are decompressed and decrypted one by one, so what arrives
+        // here is the plain tarball
+        let extractor = tokio::task::spawn_blocking(move || {
+            let tarball = ArchiveEncoding {
+                compression: Compression::None,
+                threads: 1,
+                key: None,
+            };
+            unpack_archive(
+                HashingReader::new(ChunkReader::new(rx)),
+                tarball,
+                &staging_dir,
+            )
+        });
+
+        let download = async move {
+            let result = chunks::download(
+                backend,
+                prefix,
+                &index,
+                &chunk_encoding,
+                &tx,
+                self.config.parallel_downloads,
+            )
+            .await;
+            if let Err(e) = &result {
+                let _ = tx.send(Err(anyhow::anyhow!("{:#}", e))).await;
+            }
+            result
+        };
+
+        let (downloaded, extracted) = tokio::join!(download, extractor);
+        unpack_outcome(downloaded, extracted)
+    }
+
+    /// Copy an entry restored from the fallback `tier` into the primary
+    async fn backfill_primary(
+        &self,
+        tool: &str,
+        version: &str,
+        tier: usize,
+        archive_path: Option<&Path>,
+        checksum: &str,
+    ) -> Result<()> {
+        let (source_config, source) = self.tiers().nth(tier).expect("tier index in range");
+        let source_cache_key = source_config.get_cache_key(tool, version);
+        let cache_key = self.config.get_cache_key(tool, version);
+
+        match archive_path {
+            Some(archive_path) => {
+                let metadata_json = source
+                    .get_string(&format!("{}/metadata.json", source_cache_key))
                     .await?;
-                Ok(false)
+                upload_entry(
+                    &self.backend,
+                    &cache_key,
+                    archive_path,
+                    &metadata_json,
+                    checksum,
+                )
+                .await?;
+            }
+            // Not kept locally, so fetch it from the fallback again
+            None => {
+                copy_entry(
+                    (&source_config.prefix, source),
+                    &source_cache_key,
+                    (&self.config.prefix, &self.backend),
+                    &cache_key,
+                )
+                .await?
             }
         }
+
+        debug!(
+            "Backfilled {tool}@{version} into {}",
+            self.backend.location()
+        );
+        Ok(())
     }
 
     pub async fn store_in_cache(
@@ -198,23 +793,95 @@ impl CacheManager {
             return Ok(());
         }
 
-        info!("📤 Storing {tool}@{version} in S3 cache");
+        let compression = Compression::parse(&self.config.compression)?;
+        let level = self.config.compression_level;
 
-        let cache_key = self.config.get_cache_key(tool, version);
+        let chunked = self.config.storage_mode == chunks::CHUNKED;
 
-        // Create temporary archive
-        let temp_dir = TempDir::new()?;
-        let temp_archive = temp_dir.path().join("archive.tar.gz");
+        info!("📤 Storing {tool}@{version} in cache");
+        let options = ArchiveOptions::for_tool(&self.config, tool);
+        if !options.exclude.is_empty() {
+            debug!("Excluding {}", options.exclude.join(", "));
+        }
 
-        // Create compressed archive
-        let archive_size = self.create_archive(&install_path, &temp_archive).await?;
-        debug!("Created archive: {} bytes", archive_size);
+        // A deterministic tarball only changes with the tree, so when there
+        // is an entry that could be kept, hashing the tarball tells whether
+        // it already holds this install
+        let keepable = if options.deterministic {
+            self.find_keepable_entry(tool, version, compression, level)
+                .await
+        } else {
+            None
+        };
+        if let Some((metadata, stored_digest)) = keepable {
+            let (install_path, options) = (install_path.clone(), options.clone());
+            let digest =
+                tokio::task::spawn_blocking(move || archive::tree_digest(&install_path, &options))
+                    .await??;
+            if digest == stored_digest {
+                self.keep_unchanged_entry(tool, version, metadata).await;
+                info!("✅ {tool}@{version} is unchanged since it was cached, skipping store");
+                return Ok(());
+            }
+        }
 
-        // Calculate checksum
-        let checksum = utils::calculate_file_hash(&temp_archive)?;
+        // Record what refers to the install path so restores elsewhere can
+        // rewrite it
+        let prefix = std::path::absolute(&install_path)?
+            .to_string_lossy()
+            .to_string();
+
+        // Stream the archive to the primary and every replica at once; nothing
+        // is written to local disk
+        let mut targets = vec![(self.config.get_cache_key(tool, version), &self.backend)];
+        for replica in &self.replicas {
+            targets.push((
+                replica.config.get_cache_key(tool, version),
+                &replica.backend,
+            ));
+        }
+
+        let (archived, mut upload_results) = if chunked {
+            self.upload_chunks(
+                &install_path,
+                &prefix,
+                &options,
+                &targets,
+                compression,
+                level,
+            )
+            .await?
+        } else {
+            self.upload_archive(
+                &install_path,
+                &prefix,
+                &options,
+                &targets,
+                compression,
+                level,
+            )
+            .await?
+        };
+        let replica_results = upload_results.split_off(1);
+        upload_results.remove(0)?;
+        let ArchivedTree {
+            checksum,
+            size: archive_size,
+            tarball_digest,
+            scan,
+            manifest,
+        } = archived;
+        debug!("Streamed archive: {} bytes", archive_size);
+        if !scan.unrelocatable.is_empty() {
+            warn!(
+                "{} files of {tool}@{version} refer to {prefix} in a way that cannot be relocated: {}",
+                scan.unrelocatable.len(),
+                summarize(&scan.unrelocatable)
+            );
+        }
 
         // Create metadata
-        let metadata = CacheMetadata {
+        let mut metadata = CacheMetadata {
             tool: tool.to_string(),
             version: version.to_string(),
             platform: utils::get_platform().to_string(),
@@ -223,80 +890,432 @@ impl CacheManager {
             size_bytes: archive_size,
             checksum: checksum.clone(),
             mise_version: get_mise_version(),
-            compressed: true,
+            compressed: compression != Compression::None,
+            compression: compression.name().to_string(),
+            compression_level: level,
+            encryption: self.encryption_key.as_ref().map(|key| ArchiveEncryption {
+                algorithm: encryption::ALGORITHM.to_string(),
+                key_id: key.id().to_string(),
+            }),
+            storage_mode: self.config.storage_mode.clone(),
+            install_path: Some(prefix),
+            prefix_references: scan.references,
+            exclude: options.exclude,
+            content_digest: options.deterministic.then_some(tarball_digest),
+            manifest: Some(manifest),
+            sealed: None,
         };
+        if let Some(key) = &self.encryption_key {
+            metadata.seal(key.clone())?;
+        }
 
         let metadata_json = serde_json::to_string_pretty(&metadata)?;
 
-        // Upload to S3
-        let archive_key = format!("{}/archive.tar.gz", cache_key);
-        let metadata_key = format!("{}/metadata.json", cache_key);
-        let checksum_key = format!("{}/checksum.sha256", cache_key);
-
-        // Upload in parallel using tokio::try_join!
-        tokio::try_join!(
-            self.s3_client.upload_file(&temp_archive, &archive_key),
-            self.s3_client.upload_string(&metadata_json, &metadata_key),
-            self.s3_client.upload_string(&checksum, &checksum_key)
-        )?;
+        publish_entry(&self.backend, &targets[0].0, &metadata_json, &checksum).await?;
 
         info!(
             "✅ Cached {tool}@{version} ({} bytes)",
             utils::human_readable_size(archive_size)
         );
+
+        self.publish_replicas(
+            tool,
+            version,
+            &targets[1..],
+            replica_results,
+            &metadata_json,
+            &checksum,
+        )
+        .await;
         Ok(())
     }
 
-    async fn create_archive(&self, source_dir: &Path, archive_path: &Path) -> Result<u64> {
-        debug!(
-            "Creating archive from {} to {}",
-            source_dir.display(),
-            archive_path.display()
+    /// The metadata and content digest of the entry in the primary cache, if
+    /// it holds a deterministic archive stored the way this store would store
+    /// it, so can be kept when the tree has not changed. Entries without a
+    /// manifest are stored again so they get one.
+    async fn find_keepable_entry(
+        &self,
+        tool: &str,
+        version: &str,
+        compression: Compression,
+        level: Option<i32>,
+    ) -> Option<(CacheMetadata, String)> {
+        let cache_key = self.config.get_cache_key(tool, version);
+        let metadata = self.fetch_metadata(&self.backend, &cache_key).await.ok()?;
+
+        let key_id = metadata.encryption.as_ref().map(|e| e.key_id.as_str());
+        if key_id != self.encryption_key.as_ref().map(|key| key.id()) {
+            return None;
+        }
+        let mut fields = metadata.clone();
+        if let Some(key) = &self.encryption_key {
+            fields.unseal(key.clone()).ok()?;
+        }
+
+        let keepable = metadata.compression == compression.name()
+            && metadata.compression_level == level
+            && metadata.storage_mode == self.config.storage_mode
+            && fields.manifest.is_some();
+        let digest = fields.content_digest.filter(|_| keepable)?;
+        Some((metadata, digest))
+    }
+
+    /// Restart the age of an entry that a store found unchanged, so cleanup
+    /// does not expire an install that is still being stored
+    async fn keep_unchanged_entry(&self, tool: &str, version: &str, mut metadata: CacheMetadata) {
+        let metadata_key = format!("{}/metadata.json", self.config.get_cache_key(tool, version));
+        metadata.created_at = utils::current_timestamp();
+
+        let result = match serde_json::to_string_pretty(&metadata) {
+            Ok(json) => self.backend.put_string(&json, &metadata_key).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            warn!("Failed to refresh the cache entry of {tool}@{version}: {e:#}");
+        }
+    }
+
+    /// Stream the archive of `install_path` to the archive object of every
+    /// target. Returns what was archived and each upload result.
+    async fn upload_archive(
+        &self,
+        install_path: &Path,
+        prefix: &str,
+        options: &ArchiveOptions,
+        targets: &[(String, &Arc<dyn StorageBackend>)],
+        compression: Compression,
+        level: Option<i32>,
+    ) -> Result<(ArchivedTree, Vec<Result<u64>>)> {
+        let (archive_tx, archive_rx) = streaming::chunk_channel();
+        let (sinks, receivers): (Vec<_>, Vec<_>) =
+            targets.iter().map(|_| streaming::chunk_channel()).unzip();
+
+        let (source_dir, prefix) = (install_path.to_path_buf(), prefix.to_string());
+        let options = options.clone();
+        let encoding = ArchiveEncoding {
+            compression,
+            threads: compression::thread_count(self.config.compression_threads),
+            key: self.encryption_key.clone(),
+        };
+        let producer = tokio::task::spawn_blocking(move || {
+            stream_archive(&source_dir, &prefix, &options, encoding, level, archive_tx)
+        });
+        let uploads = futures::future::join_all(targets.iter().zip(receivers).map(
+            |((cache_key, backend), chunks)| async move {
+                backend
+                    .put_stream(
+                        &format!("{}/{}", cache_key, compression.archive_name()),
+                        chunks,
+                    )
+                    .await
+            },
+        ));
+
+        let (produced, _, upload_results) =
+            tokio::join!(producer, streaming::fan_out(archive_rx, sinks), uploads);
+        Ok((produced??, upload_results))
+    }
+
+    /// Split the tarball of `install_path` into chunks, upload the ones each
+    /// target is missing and then the entry's chunk index. Returns what was
+    /// archived and the result for each target.
+    async fn upload_chunks(
+        &self,
+        install_path: &Path,
+        prefix: &str,
+        options: &ArchiveOptions,
+        targets: &[(String, &Arc<dyn StorageBackend>)],
+        compression: Compression,
+        level: Option<i32>,
+    ) -> Result<(ArchivedTree, Vec<Result<u64>>)> {
+        let (archive_tx, archive_rx) = streaming::chunk_channel();
+        let (chunk_tx, chunk_rx) = tokio::sync::mpsc::channel(streaming::CHANNEL_DEPTH);
+
+        // Chunk the uncompressed tarball, since compressed streams do not
+        // keep unchanged regions byte-identical
+        let (source_dir, prefix) = (install_path.to_path_buf(), prefix.to_string());
+        let options = options.clone();
+        let encoding = ArchiveEncoding {
+            compression: Compression::None,
+            threads: 1,
+            key: None,
+        };
+        let producer = tokio::task::spawn_blocking(move || {
+            stream_archive(&source_dir, &prefix, &options, encoding, None, archive_tx)
+        });
+        let splitter = tokio::task::spawn_blocking(move || {
+            chunks::split(ChunkReader::new(archive_rx), chunk_tx)
+        });
+
+        let prefixes = std::iter::once(&self.config.prefix)
+            .chain(self.replicas.iter().map(|replica| &replica.config.prefix));
+        let chunk_targets: Vec<_> = prefixes
+            .zip(targets)
+            .map(|(prefix, (_, backend))| (prefix.as_str(), *backend))
+            .collect();
+        let encoding = ChunkEncoding {
+            compression,
+            level,
+            key: self.encryption_key.clone(),
+        };
+
+        let (produced, split, uploaded) = tokio::join!(
+            producer,
+            splitter,
+            chunks::upload(
+                &chunk_targets,
+                chunk_rx,
+                &encoding,
+                self.config.parallel_uploads
+            )
+        );
+        let (index, chunk_results) = uploaded?;
+        let archived = produced??;
+        split??;
+
+        // The index goes up only once all of its chunks are in place
+        let index_json = serde_json::to_string(&index)?;
+        let index_json = &index_json;
+        let tarball_size = archived.size;
+        let results = futures::future::join_all(targets.iter().zip(chunk_results).map(
+            |((cache_key, backend), chunk_result)| async move {
+                chunk_result?;
+                backend
+                    .put_string(index_json, &chunks::index_key(cache_key))
+                    .await?;
+                Ok(tarball_size)
+            },
+        ))
+        .await;
+
+        Ok((archived, results))
+    }
+
+    /// Publish a freshly streamed entry on every replica whose archive upload
+    /// succeeded, reporting each outcome.
+    ///
+    /// Replica failures are not fatal; `sync` can fill them in later.
+    async fn publish_replicas(
+        &self,
+        tool: &str,
+        version: &str,
+        targets: &[(String, &Arc<dyn StorageBackend>)],
+        archive_results: Vec<Result<u64>>,
+        metadata_json: &str,
+        checksum: &str,
+    ) {
+        let publishes = targets.iter().zip(archive_results).map(
+            |((cache_key, backend), archive_result)| async move {
+                let result = match archive_result {
+                    Ok(_) => publish_entry(backend, cache_key, metadata_json, checksum).await,
+                    Err(e) => Err(e),
+                };
+                (backend.location(), result)
+            },
+        );
+
+        for (location, result) in futures::future::join_all(publishes).await {
+            match result {
+                Ok(_) => info!("✅ Replicated {tool}@{version} to {location}"),
+                Err(e) => warn!("❌ Failed to replicate {tool}@{version} to {location}: {e}"),
+            }
+        }
+    }
+
+    /// Copy every entry in the primary cache that is missing from a replica
+    pub async fn sync_replicas(&self) -> Result<()> {
+        if self.replicas.is_empty() {
+            warn!("No replicas configured");
+            return Ok(());
+        }
+
+        let tools_prefix = format!("{}/tools/", self.config.prefix);
+        let entries: Vec<String> = self
+            .backend
+            .list(&tools_prefix)
+            .await?
+            .into_iter()
+            .filter_map(|object| {
+                object
+                    .key
+                    .strip_suffix("/metadata.json")
+                    .and_then(|entry| entry.strip_prefix(&tools_prefix))
+                    .map(str::to_string)
+            })
+            .collect();
+
+        info!(
+            "🔄 Syncing {} cache entries to {} replicas",
+            entries.len(),
+            self.replicas.len()
         );
 
-        // Use tokio::task::spawn_blocking for CPU-intensive work
-        let source_dir = source_dir.to_path_buf();
-        let archive_path = archive_path.to_path_buf();
+        let mut failed = 0;
+        for replica in &self.replicas {
+            let location = replica.backend.location();
+            let mut copied = 0;
+            let mut errors = 0;
+
+            for entry in &entries {
+                let source_key = format!("{}{}", tools_prefix, entry);
+                let dest_key = format!("{}/tools/{}", replica.config.prefix, entry);
+
+                match replica
+                    .backend
+                    .exists(&format!("{}/metadata.json", dest_key))
+                    .await
+                {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => {
+                        warn!("Failed to check {entry} in {location}: {e}");
+                        errors += 1;
+                        continue;
+                    }
+                }
 
-        tokio::task::spawn_blocking(move || -> Result<u64> {
-            let file = std::fs::File::create(&archive_path)?;
-            let encoder = GzEncoder::new(file, Compression::default());
-            let mut builder = Builder::new(encoder);
+                match copy_entry(
+                    (&self.config.prefix, &self.backend),
+                    &source_key,
+                    (&replica.config.prefix, &replica.backend),
+                    &dest_key,
+                )
+                .await
+                {
+                    Ok(_) => {
+                        debug!("Copied {entry} to {location}");
+                        copied += 1;
+                    }
+                    Err(e) => {
+                        warn!("Failed to copy {entry} to {location}: {e}");
+                        errors += 1;
+                    }
+                }
+            }
 
-            // Add all files from source directory
-            builder.append_dir_all(".", &source_dir).with_context(|| {
-                format!("Failed to create archive from {}", source_dir.display())
-            })?;
+            if errors == 0 {
+                info!("✅ {location}: copied {copied} missing entries");
+            } else {
+                warn!("❌ {location}: copied {copied} missing entries, {errors} failed");
+                failed += 1;
+            }
+        }
 
-            builder.finish()?;
+        if failed > 0 {
+            return Err(anyhow::anyhow!("{} replicas failed to sync", failed));
+        }
 
-            // Get final archive size
-            let metadata = std::fs::metadata(&archive_path)?;
-            Ok(metadata.len())
+        Ok(())
+    }
+
+    /// Archive `source_dir`, or a generated sample tree, with the configured
+    /// settings, restore it into a temporary directory the same way `restore`
+    /// does and return every difference between the two trees. Needs no
+    /// storage backend.
+    pub async fn self_test(config: &Config, source_dir: Option<&Path>) -> Result<Vec<String>> {
+        let options = ArchiveOptions::from_config(config);
+        let compression = Compression::parse(&config.compression)?;
+        let level = config.compression_level;
+        let threads = compression::thread_count(config.compression_threads);
+        let source_dir = source_dir.map(Path::to_path_buf);
+
+        tokio::task::spawn_blocking(move || {
+            let sample_dir = TempDir::new()?;
+            let source_dir = match source_dir {
+                Some(source_dir) => source_dir,
+                None => {
+                    let root = sample_dir.path().join("sample");
+                    std::fs::create_dir(&root)?;
+                    archive::create_sample_tree(&root, &options)?;
+                    root
+                }
+            };
+
+            let (stored, _) = write_archive(
+                &source_dir,
+                &options,
+                compression,
+                level,
+                threads,
+                &mut (),
+                Vec::new(),
+            )?;
+            debug!("Self-test archive: {} bytes", stored.len());
+
+            let restore_dir = TempDir::new()?;
+            let encoding = ArchiveEncoding {
+                compression,
+                threads,
+                key: None,
+            };
+            unpack_archive(
+                HashingReader::new(stored.as_slice()),
+                encoding,
+                restore_dir.path(),
+            )?;
+
+            archive::compare_trees(&source_dir, restore_dir.path(), &options)
         })
         .await?
     }
 
-    async fn extract_archive(&self, archive_path: &Path, target_dir: &Path) -> Result<()> {
-        debug!(
-            "Extracting {} to {}",
-            archive_path.display(),
-            target_dir.display()
-        );
-
-        let archive_path = archive_path.to_path_buf();
-        let target_dir = target_dir.to_path_buf();
+    /// Compare the install of the tool at `install_path`, or where mise
+    /// installs it, with the manifest of its cache entry
+    pub async fn verify_install(
+        &self,
+        tool: &str,
+        version: &str,
+        install_path: Option<&str>,
+    ) -> Result<ManifestReport> {
+        self.validate_tool_version(tool, version).await?;
 
-        tokio::task::spawn_blocking(move || -> Result<()> {
-            let file = std::fs::File::open(&archive_path)?;
-            let decoder = GzDecoder::new(file);
-            let mut archive = Archive::new(decoder);
+        let install_path = match install_path {
+            Some(path) => PathBuf::from(path),
+            None => self.standard_install_path(tool, version).await?,
+        };
+        if !install_path.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Install path does not exist: {}",
+                install_path.display()
+            ));
+        }
 
-            archive.unpack(&target_dir).with_context(|| {
-                format!("Failed to extract archive to {}", target_dir.display())
-            })?;
+        let Some(tier) = self.find_cached_tier(tool, version).await? else {
+            return Err(anyhow::anyhow!("{tool}@{version} is not cached"));
+        };
+        let (tier_config, backend) = self.tiers().nth(tier).expect("tier index in range");
+        let mut metadata = self
+            .fetch_metadata(backend, &tier_config.get_cache_key(tool, version))
+            .await?;
+        if let Some(encryption) = &metadata.encryption {
+            metadata.unseal(self.decryption_key(encryption)?)?;
+        }
+        let Some(entries) = metadata.manifest else {
+            return Err(anyhow::anyhow!(
+                "The cache entry of {tool}@{version} has no manifest; store it again to record one"
+            ));
+        };
 
-            Ok(())
+        let options = ArchiveOptions {
+            exclude: metadata.exclude,
+            ..Default::default()
+        };
+        let new_prefix = std::path::absolute(&install_path)?
+            .to_string_lossy()
+            .to_string();
+        let (old_prefix, references) = (metadata.install_path, metadata.prefix_references);
+
+        tokio::task::spawn_blocking(move || {
+            let relocation = old_prefix
+                .as_deref()
+                .filter(|old| *old != new_prefix)
+                .map(|old| Relocation {
+                    old,
+                    new: &new_prefix,
+                    references: &references,
+                });
+            manifest::verify(&install_path, &entries, &options, relocation)
         })
         .await?
     }
@@ -348,6 +1367,142 @@ impl CacheManager {
         Ok(())
     }
 
+    pub async fn show_status(&self) {
+        println!("📋 Cache Configuration:");
+        println!("   Enabled: {}", self.config.enabled);
+        println!("   Backend: {}", self.backend.location());
+        for tier in &self.fallbacks {
+            println!("   Fallback: {}", tier.backend.location());
+        }
+        for tier in &self.replicas {
+            println!("   Replica: {}", tier.backend.location());
+        }
+        println!("   Region: {}", self.config.region);
+        println!("   Prefix: {}", self.config.prefix);
+        if let Some(endpoint_url) = &self.config.endpoint_url {
+            println!(
+                "   Endpoint: {}{}",
+                endpoint_url,
+                if self.config.force_path_style {
+                    " (path-style)"
+                } else {
+                    ""
+                }
+            );
+        }
+        if let Some(profile) = &self.config.profile {
+            println!("   AWS profile: {}", profile);
+        }
+        if let Some(role_arn) = &self.config.role_arn {
+            println!("   Assumed role: {}", role_arn);
+        }
+        if let Some(sse) = &self.config.server_side_encryption {
+            match &self.config.sse_kms_key_id {
+                Some(key_id) => println!("   Encryption: {} ({})", sse, key_id),
+                None => println!("   Encryption: {}", sse),
+            }
+        }
+        if let Some(storage_class) = &self.config.storage_class {
+            println!("   Storage class: {}", storage_class);
+        }
+        if !self.config.object_tags.is_empty() {
+            let tags: Vec<String> = self
+                .config
+                .object_tags
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            println!("   Object tags: {}", tags.join(", "));
+        }
+        if self.config.encryption_key.is_some() {
+            println!("   Client-side encryption: key from MISE_S3_CACHE_ENCRYPTION_KEY");
+        } else if let Some(path) = &self.config.encryption_key_file {
+            println!("   Client-side encryption: key from {}", path.display());
+        }
+        println!("   TTL: {}s", self.config.ttl_seconds);
+        println!("   Parallel uploads: {}", self.config.parallel_uploads);
+        println!(
+            "   Multipart part size: {}",
+            utils::human_readable_size(self.config.multipart_part_size)
+        );
+        println!(
+            "   Parallel downloads: {} x {} ranges",
+            self.config.parallel_downloads,
+            utils::human_readable_size(self.config.download_chunk_size)
+        );
+        println!(
+            "   Bandwidth limit: {} up, {} down",
+            utils::format_rate(self.config.upload_limit),
+            utils::format_rate(self.config.download_limit)
+        );
+        println!(
+            "   Retries: {} attempts, {}ms base delay{}",
+            self.config.retry_max_attempts,
+            self.config.retry_base_delay_ms,
+            if self.config.retry_jitter {
+                " with jitter"
+            } else {
+                ""
+            }
+        );
+        match self.config.compression_level {
+            Some(level) => println!(
+                "   Compression: {} (level {})",
+                self.config.compression, level
+            ),
+            None => println!("   Compression: {}", self.config.compression),
+        }
+        println!(
+            "   Compression threads: {}",
+            compression::thread_count(self.config.compression_threads)
+        );
+        println!("   Storage mode: {}", self.config.storage_mode);
+        println!("   Preserve xattrs: {}", self.config.preserve_xattrs);
+        if !self.config.exclude.is_empty() {
+            println!("   Exclude: {}", self.config.exclude.join(", "));
+        }
+        for (tool, patterns) in &self.config.tool_excludes {
+            println!("   Exclude for {}: {}", tool, patterns.join(", "));
+        }
+        println!(
+            "   Deterministic archives: {}",
+            self.config.deterministic_archives
+        );
+        println!("   Debug: {}", self.config.debug);
+        if let Some(log_file) = &self.config.log_file {
+            println!("   Log file: {}", log_file.display());
+        }
+
+        // Test connectivity
+        match self.backend.test_connectivity().await {
+            Ok(_) => println!("   Status: ✅ Connected"),
+            Err(e) => println!("   Status: ❌ Connection failed: {}", e),
+        }
+
+        let prefix = format!("{}/tools", self.config.prefix);
+        match self.backend.list(&prefix).await {
+            Ok(objects) => {
+                let size: u64 = objects.iter().map(|o| o.size).sum();
+                println!("   Cache size: {}", utils::human_readable_size(size));
+                println!("   Cached objects: {}", objects.len());
+            }
+            Err(e) => {
+                println!("   Cache size: ❌ Failed to list: {}", e);
+            }
+        }
+
+        if self.local_cache.is_enabled() {
+            println!(
+                "   Local cache: {} of {} ({})",
+                utils::human_readable_size(self.local_cache.size()),
+                utils::human_readable_size(self.config.local_cache_max_size),
+                self.local_cache.dir().display()
+            );
+        } else {
+            println!("   Local cache: disabled");
+        }
+    }
+
     pub async fn analyze_project(&self) -> Result<()> {
         let tools = self.tool_detector.get_project_tools().await?;
 
@@ -487,22 +1642,137 @@ impl CacheManager {
 
     pub async fn cleanup_old_cache(&self, days_old: u32) -> Result<()> {
         info!(
-            "🧹 Cleaning up S3 cache entries older than {} days",
+            "🧹 Cleaning up cache entries older than {} days",
             days_old
         );
 
         let max_age_seconds = days_old as u64 * 24 * 60 * 60;
-        let deleted_keys = self
-            .s3_client
-            .cleanup_old_objects(&format!("{}/tools", self.config.prefix), max_age_seconds)
+        let cutoff_time = utils::current_timestamp().saturating_sub(max_age_seconds);
+
+        let objects = self
+            .backend
+            .list(&format!("{}/tools", self.config.prefix))
             .await?;
 
-        info!("✅ Removed {} old cache entries", deleted_keys.len());
+        // Each entry is the directory holding its objects and expires whole
+        let mut entries: BTreeMap<&str, Vec<&ObjectInfo>> = BTreeMap::new();
+        for object in &objects {
+            let cache_key = object.key.rsplit_once('/').map_or("", |(dir, _)| dir);
+            entries.entry(cache_key).or_default().push(object);
+        }
+
+        let mut removed = 0;
+        for (cache_key, objects) in entries {
+            let metadata_key = format!("{}/metadata.json", cache_key);
+            let (metadata, data): (Vec<_>, Vec<_>) = objects
+                .into_iter()
+                .partition(|object| object.key == metadata_key);
+
+            let stored_at = match metadata.first() {
+                Some(object) => match self.fetch_metadata(&self.backend, cache_key).await {
+                    Ok(metadata) => metadata.created_at,
+                    // Unreadable metadata is judged by when it was written
+                    Err(_) => object.last_modified,
+                },
+                // Left behind by a store that never published its metadata
+                None => data
+                    .iter()
+                    .map(|object| object.last_modified)
+                    .max()
+                    .unwrap_or(0),
+            };
+            if stored_at >= cutoff_time {
+                continue;
+            }
 
-        for key in &deleted_keys {
-            debug!("Removed: {}", key);
+            // metadata.json goes last, so an entry that could not be deleted
+            // completely is still found and retried by the next cleanup
+            info!("Deleting old cache entry: {}", cache_key);
+            let mut deleted = true;
+            for object in data.into_iter().chain(metadata) {
+                if let Err(e) = self.backend.delete(&object.key).await {
+                    error!("Failed to delete {}: {}", object.key, e);
+                    deleted = false;
+                    break;
+                }
+                debug!("Removed: {}", object.key);
+            }
+            if deleted {
+                removed += 1;
+            }
         }
 
+        info!("✅ Removed {} old cache entries", removed);
+
+        self.remove_unreferenced_chunks(cutoff_time).await
+    }
+
+    /// Delete chunks that no remaining chunked entry refers to.
+    ///
+    /// Chunks written after `cutoff_time` are kept even when unreferenced,
+    /// since a store still in progress uploads its chunks before its index.
+    /// Stores refresh the chunks they reuse for the same reason, and chunks
+    /// are listed again just before deleting so such a refresh made while
+    /// the indexes were read is seen.
+    async fn remove_unreferenced_chunks(&self, cutoff_time: u64) -> Result<()> {
+        let chunks_prefix = format!("{}/chunks/", self.config.prefix);
+        let chunk_objects = self.backend.list(&chunks_prefix).await?;
+        if chunk_objects.is_empty() {
+            return Ok(());
+        }
+
+        let mut referenced = HashSet::new();
+        for object in self
+            .backend
+            .list(&format!("{}/tools/", self.config.prefix))
+            .await?
+        {
+            if !object.key.ends_with("/index.json") {
+                continue;
+            }
+
+            // An unreadable index would leave its chunks unaccounted for, so
+            // nothing is deleted rather than risk breaking that entry
+            let json = self.backend.get_string(&object.key).await?;
+            let index: ChunkIndex = serde_json::from_str(&json)
+                .with_context(|| format!("Invalid chunk index {}", object.key))?;
+            referenced.extend(index.chunks.into_iter().map(|chunk| chunk.id));
+        }
+
+        let unreferenced: Vec<String> = chunk_objects
+            .into_iter()
+            .filter(|object| {
+                chunks::chunk_id_from_key(&object.key).is_some_and(|id| !referenced.contains(id))
+                    && object.last_modified < cutoff_time
+            })
+            .map(|object| object.key)
+            .collect();
+        if unreferenced.is_empty() {
+            info!("✅ Removed 0 unreferenced chunks");
+            return Ok(());
+        }
+
+        let modified: HashMap<String, u64> = self
+            .backend
+            .list(&chunks_prefix)
+            .await?
+            .into_iter()
+            .map(|object| (object.key, object.last_modified))
+            .collect();
+
+        let mut removed = 0;
+        for key in unreferenced {
+            if modified.get(&key).is_none_or(|&time| time >= cutoff_time) {
+                continue;
+            }
+
+            match self.backend.delete(&key).await {
+                Ok(_) => removed += 1,
+                Err(e) => error!("Failed to delete {}: {}", key, e),
+            }
+        }
+
+        info!("✅ Removed {} unreferenced chunks", removed);
         Ok(())
     }
 
@@ -613,12 +1883,15 @@ impl CacheManager {
         let cache_dir = self.config.get_cache_dir();
         let temp_dir = cache_dir.join("tmp");
 
+        // Partial downloads live apart from the temp files, so a restore
+        // running right now keeps its progress
+        let mut count = partial_download::remove_abandoned(&cache_dir.join("partial")).await?;
+
         if !temp_dir.exists() {
-            info!("No temporary files to clean");
+            info!("✅ Cleaned up {} temporary files", count);
             return Ok(());
         }
 
-        let mut count = 0;
         let mut entries = fs::read_dir(&temp_dir).await?;
 
         while let Some(entry) = entries.next_entry().await? {
@@ -638,6 +1911,317 @@ impl CacheManager {
     }
 }
 
+/// Upload the objects of one cache entry. The metadata goes last so the
+/// entry only becomes visible to `check_cache` once it is complete.
+async fn upload_entry(
+    backend: &Arc<dyn StorageBackend>,
+    cache_key: &str,
+    archive_path: &Path,
+    metadata_json: &str,
+    checksum: &str,
+) -> Result<()> {
+    let archive_key = format!("{}/{}", cache_key, archive_name(metadata_json)?);
+    let metadata_key = format!("{}/metadata.json", cache_key);
+    let checksum_key = format!("{}/checksum.sha256", cache_key);
+
+    tokio::try_join!(
+        backend.put(archive_path, &archive_key),
+        backend.put_string(checksum, &checksum_key)
+    )?;
+    backend.put_string(metadata_json, &metadata_key).await?;
+
+    Ok(())
+}
+
+/// Name of the archive object of the entry described by `metadata_json`.
+/// Metadata that cannot be parsed predates other formats, so means gzip.
+fn archive_name(metadata_json: &str) -> Result<&'static str> {
+    let metadata: CacheMetadata =
+        serde_json::from_str(metadata_json).context("Invalid entry metadata")?;
+    Ok(Compression::parse(&metadata.compression)?.archive_name())
+}
+
+/// Upload the checksum and then the metadata of an entry whose archive is
+/// already in place, making it visible to `check_cache`
+async fn publish_entry(
+    backend: &Arc<dyn StorageBackend>,
+    cache_key: &str,
+    metadata_json: &str,
+    checksum: &str,
+) -> Result<()> {
+    backend
+        .put_string(checksum, &format!("{}/checksum.sha256", cache_key))
+        .await?;
+    backend
+        .put_string(metadata_json, &format!("{}/metadata.json", cache_key))
+        .await
+}
+
+/// Write a tarball of `source_dir` compressed with `compression` on up to
+/// `threads` threads into `writer`, showing each entry to `visitor` on the
+/// way. Returns the writer and the SHA-256 of the uncompressed tarball.
+fn write_archive<W: Write, V: TreeVisitor>(
+    source_dir: &Path,
+    options: &ArchiveOptions,
+    compression: Compression,
+    level: Option<i32>,
+    threads: usize,
+    visitor: &mut V,
+    writer: W,
+) -> Result<(W, String)> {
+    debug!(
+        "Creating {} archive from {}",
+        compression.name(),
+        source_dir.display()
+    );
+
+    let encoder = if options.deterministic {
+        compression.deterministic_encoder(writer, level, threads)?
+    } else {
+        compression.encoder(writer, level, threads)?
+    };
+    let mut builder = Builder::new(HashingWriter::new(encoder));
+
+    archive::append_tree_with(&mut builder, source_dir, options, visitor)
+        .with_context(|| format!("Failed to create archive from {}", source_dir.display()))?;
+
+    let (encoder, tarball_digest) = builder.into_inner()?.finish();
+    Ok((encoder.finish()?, tarball_digest))
+}
+
+/// An install as `stream_archive` archived it
+struct ArchivedTree {
+    /// SHA-256 and size of the stored archive
+    checksum: String,
+    size: u64,
+    /// SHA-256 of the uncompressed tarball
+    tarball_digest: String,
+    /// What refers to the path the install was archived from
+    scan: PrefixScan,
+    manifest: Vec<ManifestEntry>,
+}
+
+/// Archive `source_dir` into a chunk stream encoded with `encoding`. What
+/// refers to `prefix` and the manifest are gathered from the same read of
+/// the tree.
+///
+/// Must run on a blocking thread. Failures are forwarded down the stream so
+/// uploads abort instead of committing a truncated archive.
+fn stream_archive(
+    source_dir: &Path,
+    prefix: &str,
+    options: &ArchiveOptions,
+    encoding: ArchiveEncoding,
+    level: Option<i32>,
+    tx: streaming::ChunkSender,
+) -> Result<ArchivedTree> {
+    let ArchiveEncoding {
+        compression,
+        threads,
+        key,
+    } = encoding;
+    let mut survey = (PrefixScanner::new(prefix), ManifestBuilder::default());
+    let writer = streaming::ChunkWriter::new(tx.clone());
+    let result = match key {
+        Some(key) => EncryptingWriter::new(writer, key)
+            .map_err(anyhow::Error::from)
+            .and_then(|writer| {
+                write_archive(
+                    source_dir,
+                    options,
+                    compression,
+                    level,
+                    threads,
+                    &mut survey,
+                    writer,
+                )
+            })
+            .and_then(|(writer, digest)| Ok((writer.finish()?.finish()?, digest))),
+        None => write_archive(
+            source_dir,
+            options,
+            compression,
+            level,
+            threads,
+            &mut survey,
+            writer,
+        )
+        .and_then(|(writer, digest)| Ok((writer.finish()?, digest))),
+    };
+
+    if let Err(e) = &result {
+        let _ = tx.blocking_send(Err(anyhow::anyhow!("{:#}", e)));
+    }
+    let ((checksum, size), tarball_digest) = result?;
+    let (scanner, manifest) = survey;
+    Ok(ArchivedTree {
+        checksum,
+        size,
+        tarball_digest,
+        scan: scanner.finish(),
+        manifest: manifest.finish(),
+    })
+}
+
+/// How a stored archive is encoded on top of the tarball
+#[derive(Clone)]
+struct ArchiveEncoding {
+    compression: Compression,
+    /// Encoder or decoder threads, for formats that can use more than one
+    threads: usize,
+    /// Encrypts the archive after compression, and decrypts it before
+    /// decompression, when set
+    key: Option<Arc<EncryptionKey>>,
+}
+
+/// Combine the outcome of a download and the extraction it fed into the
+/// unpacked checksum and size, or the stats status and error
+fn unpack_outcome(
+    downloaded: Result<u64>,
+    extracted: std::result::Result<Result<(String, u64)>, tokio::task::JoinError>,
+) -> std::result::Result<(String, u64), (&'static str, anyhow::Error)> {
+    let extracted = extracted.map_err(anyhow::Error::from).and_then(|r| r);
+
+    match (downloaded, extracted) {
+        (Err(e), _) => Err(("download_failed", e)),
+        (Ok(_), Err(e)) => Err(("extraction_failed", e)),
+        (Ok(_), Ok(unpacked)) => Ok(unpacked),
+    }
+}
+
+/// Unpack an `encoding` tarball from `reader` into `target_dir`, returning
+/// the SHA-256 and size of the stored bytes
+fn unpack_archive<R: Read>(
+    mut reader: HashingReader<R>,
+    encoding: ArchiveEncoding,
+    target_dir: &Path,
+) -> Result<(String, u64)> {
+    debug!("Extracting archive to {}", target_dir.display());
+
+    let mut source: Box<dyn Read + '_> = match encoding.key {
+        Some(key) => Box::new(DecryptingReader::new(&mut reader, key)?),
+        None => Box::new(&mut reader),
+    };
+
+    let mut archive = Archive::new(
+        encoding
+            .compression
+            .decoder(&mut source, encoding.threads)?,
+    );
+    archive::unpack_tree(&mut archive, target_dir)?;
+
+    // The checksum covers the whole object, so read past the end-of-archive
+    // blocks, the compression trailer and the last encrypted segment too
+    let mut decoder = archive.into_inner();
+    std::io::copy(&mut decoder, &mut std::io::sink())?;
+    drop(decoder);
+    std::io::copy(&mut source, &mut std::io::sink())?;
+    drop(source);
+    std::io::copy(&mut reader, &mut std::io::sink())?;
+
+    Ok(reader.finish()?)
+}
+
+/// Hidden, uniquely named sibling of `path`, on the same filesystem so it can
+/// be renamed into place
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.{}", name, Uuid::new_v4(), suffix))
+}
+
+/// Replace `install_path` with the verified `staging_dir`
+async fn promote_staging(staging_dir: &Path, install_path: &Path) -> Result<()> {
+    if !fs::try_exists(install_path).await? {
+        return fs::rename(staging_dir, install_path)
+            .await
+            .with_context(|| format!("Failed to move {} into place", install_path.display()));
+    }
+
+    let displaced = sibling_path(install_path, "old");
+    fs::rename(install_path, &displaced)
+        .await
+        .with_context(|| format!("Failed to move {} aside", install_path.display()))?;
+
+    if let Err(e) = fs::rename(staging_dir, install_path).await {
+        let _ = fs::rename(&displaced, install_path).await;
+        return Err(e)
+            .with_context(|| format!("Failed to move {} into place", install_path.display()));
+    }
+
+    let _ = fs::remove_dir_all(&displaced).await;
+    Ok(())
+}
+
+/// Copy one cache entry between backends, each given with its cache
+/// prefix. Archives go via a temporary local file; chunked entries copy the
+/// chunks the destination is missing.
+async fn copy_entry(
+    source: (&str, &Arc<dyn StorageBackend>),
+    source_key: &str,
+    dest: (&str, &Arc<dyn StorageBackend>),
+    dest_key: &str,
+) -> Result<()> {
+    let metadata_key = format!("{}/metadata.json", source_key);
+    let checksum_key = format!("{}/checksum.sha256", source_key);
+
+    let metadata_json = source.1.get_string(&metadata_key).await?;
+    let metadata: CacheMetadata = serde_json::from_str(&metadata_json)
+        .with_context(|| format!("Invalid metadata in {}", metadata_key))?;
+
+    // Entries restore with the metadata checksum when checksum.sha256 cannot
+    // be read, so they are copied with it too
+    let checksum = match source.1.get_string(&checksum_key).await {
+        Ok(checksum) => checksum,
+        Err(e) => {
+            debug!("Using the metadata checksum of {}: {}", source_key, e);
+            metadata.checksum.clone()
+        }
+    };
+    if checksum.trim().is_empty() {
+        return Err(anyhow::anyhow!("{} has no checksum to copy", source_key));
+    }
+
+    if metadata.storage_mode == chunks::CHUNKED {
+        let index_json = source.1.get_string(&chunks::index_key(source_key)).await?;
+        let index: ChunkIndex = serde_json::from_str(&index_json)?;
+        let compression = Compression::parse(&metadata.compression)?;
+
+        chunks::copy(source, dest, &index, compression).await?;
+        dest.1
+            .put_string(&index_json, &chunks::index_key(dest_key))
+            .await?;
+        return publish_entry(dest.1, dest_key, &metadata_json, &checksum).await;
+    }
+    let (source, dest) = (source.1, dest.1);
+
+    let archive_name = archive_name(&metadata_json)?;
+    let temp_dir = TempDir::new()?;
+    let temp_archive = temp_dir.path().join(archive_name);
+    source
+        .get(&format!("{}/{}", source_key, archive_name), &temp_archive)
+        .await?;
+
+    upload_entry(dest, dest_key, &temp_archive, &metadata_json, &checksum).await
+}
+
+/// The first few of `paths`, for log messages
+fn summarize(paths: &[String]) -> String {
+    const SHOWN: usize = 5;
+    let mut summary = paths
+        .iter()
+        .take(SHOWN)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if paths.len() > SHOWN {
+        summary.push_str(&format!(" and {} more", paths.len() - SHOWN));
+    }
+    summary
+}
+
 fn get_mise_version() -> String {
     std::process::Command::new("mise")
         .arg("version")
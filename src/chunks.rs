@@ -0,0 +1,346 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use fastcdc::v2020::StreamCDC;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::compression::Compression;
+use crate::encryption::{DecryptingReader, EncryptingWriter, EncryptionKey};
+use crate::storage::StorageBackend;
+use crate::streaming::{self, ChunkSender};
+
+/// Storage mode that uploads every entry as a single archive object
+pub const ARCHIVE: &str = "archive";
+
+/// Storage mode that splits entries into content-addressed chunks shared
+/// between entries under `<prefix>/chunks/`
+pub const CHUNKED: &str = "chunked";
+
+/// Bounds and target for content-defined chunk sizes
+pub const MIN_CHUNK_SIZE: u32 = 256 * 1024;
+pub const AVG_CHUNK_SIZE: u32 = 1024 * 1024;
+pub const MAX_CHUNK_SIZE: u32 = 4 * 1024 * 1024;
+
+/// Chunks copied at once when an entry is copied between caches
+pub const COPY_CONCURRENCY: usize = 8;
+
+/// Ordered chunks making up the uncompressed tarball of a chunked entry,
+/// stored as `index.json` next to its metadata
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChunkIndex {
+    pub chunks: Vec<ChunkRef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRef {
+    /// SHA-256 of the chunk, or its keyed hash for encrypted entries
+    pub id: String,
+    /// Size of the chunk before compression
+    pub size: u64,
+}
+
+pub fn index_key(cache_key: &str) -> String {
+    format!("{}/index.json", cache_key)
+}
+
+/// Object key of a chunk. The compression format is part of the name so
+/// entries stored with different formats never read each other's chunks.
+pub fn chunk_key(prefix: &str, id: &str, compression: Compression) -> String {
+    let extension = match compression {
+        Compression::Gzip => ".gz",
+        Compression::Zstd => ".zst",
+        Compression::Xz => ".xz",
+        Compression::None => "",
+    };
+    format!("{}/chunks/{}/{}{}", prefix, &id[..2], id, extension)
+}
+
+/// Id of the chunk stored under `key`
+pub fn chunk_id_from_key(key: &str) -> Option<&str> {
+    let name = key.rsplit('/').next()?;
+    name.split('.').next().filter(|id| !id.is_empty())
+}
+
+/// How chunks are named and encoded before upload
+#[derive(Clone)]
+pub struct ChunkEncoding {
+    pub compression: Compression,
+    pub level: Option<i32>,
+    /// Encrypts every chunk, and keys chunk ids, when set
+    pub key: Option<Arc<EncryptionKey>>,
+}
+
+impl ChunkEncoding {
+    pub fn id(&self, data: &[u8]) -> String {
+        match &self.key {
+            Some(key) => key.chunk_id(data),
+            None => format!("{:x}", Sha256::digest(data)),
+        }
+    }
+
+    /// Compress and then encrypt one chunk
+    pub fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match &self.key {
+            Some(key) => {
+                let writer = EncryptingWriter::new(Vec::new(), key.clone())?;
+                let mut encoder = self.compression.encoder(writer, self.level, 1)?;
+                encoder.write_all(data)?;
+                encoder.finish()?.finish()
+            }
+            None => {
+                let mut encoder = self.compression.encoder(Vec::new(), self.level, 1)?;
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Reverse `encode`
+    pub fn decode(&self, stored: &[u8]) -> io::Result<Vec<u8>> {
+        let source: Box<dyn Read + '_> = match &self.key {
+            Some(key) => Box::new(DecryptingReader::new(stored, key.clone())?),
+            None => Box::new(stored),
+        };
+
+        let mut data = Vec::new();
+        self.compression
+            .decoder(source, 1)?
+            .read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Split everything read from `reader` into content-defined chunks and send
+/// them down `tx`, so unchanged regions of a tarball produce the same chunks
+/// from one version of a tool to the next.
+///
+/// Must run on a blocking thread. A read failure is forwarded down the
+/// stream so the upload is abandoned.
+pub fn split<R: Read>(reader: R, tx: mpsc::Sender<Result<Bytes>>) -> Result<()> {
+    for chunk in StreamCDC::new(reader, MIN_CHUNK_SIZE, AVG_CHUNK_SIZE, MAX_CHUNK_SIZE) {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                let e = anyhow::anyhow!("Failed to split archive: {}", e);
+                let _ = tx.blocking_send(Err(anyhow::anyhow!("{:#}", e)));
+                return Err(e);
+            }
+        };
+
+        if tx.blocking_send(Ok(Bytes::from(chunk.data))).is_err() {
+            return Err(anyhow::anyhow!("Chunk upload stopped"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Upload every chunk arriving on `chunks` to the targets, given as chunk
+/// prefix and backend, that do not hold it yet, `concurrency` chunks at a
+/// time. Returns the index of the chunks and the outcome for each target.
+///
+/// Chunks a target already holds are refreshed instead, so cleanup does not
+/// take them for old unreferenced chunks before the new index is written.
+///
+/// The first target is the primary cache and any failure there aborts the
+/// upload. A replica that fails is reported and skipped for later chunks.
+pub async fn upload(
+    targets: &[(&str, &Arc<dyn StorageBackend>)],
+    chunks: mpsc::Receiver<Result<Bytes>>,
+    encoding: &ChunkEncoding,
+    concurrency: usize,
+) -> Result<(ChunkIndex, Vec<Result<()>>)> {
+    let failures: Vec<Mutex<Option<anyhow::Error>>> =
+        targets.iter().map(|_| Mutex::new(None)).collect();
+    let failures = &failures;
+
+    let uploaded: Vec<(ChunkRef, bool)> = stream::unfold(chunks, |mut chunks| async move {
+        chunks.recv().await.map(|chunk| (chunk, chunks))
+    })
+    .map(|chunk| async move {
+        let data = chunk?;
+        let hashed = data.clone();
+        let hash_encoding = encoding.clone();
+        let id = tokio::task::spawn_blocking(move || hash_encoding.id(&hashed)).await?;
+
+        let live: Vec<usize> = (0..targets.len())
+            .filter(|&i| failures[i].lock().unwrap().is_none())
+            .collect();
+
+        let checks = futures::future::join_all(live.iter().map(|&i| {
+            let (prefix, backend) = targets[i];
+            let key = chunk_key(prefix, &id, encoding.compression);
+            async move { (i, backend.refresh(&key).await) }
+        }))
+        .await;
+
+        let mut missing = Vec::new();
+        for (i, exists) in checks {
+            match exists {
+                Ok(true) => {}
+                Ok(false) => missing.push(i),
+                Err(e) if i == 0 => return Err(e),
+                Err(e) => {
+                    failures[i].lock().unwrap().get_or_insert(e);
+                }
+            }
+        }
+
+        let chunk = ChunkRef {
+            id,
+            size: data.len() as u64,
+        };
+        if missing.is_empty() {
+            return Ok((chunk, false));
+        }
+
+        let encode_encoding = encoding.clone();
+        let encoded =
+            Bytes::from(tokio::task::spawn_blocking(move || encode_encoding.encode(&data)).await??);
+
+        let puts = futures::future::join_all(missing.iter().map(|&i| {
+            let (prefix, backend) = targets[i];
+            let key = chunk_key(prefix, &chunk.id, encoding.compression);
+            let encoded = encoded.clone();
+            async move { (i, put_bytes(backend, &key, encoded).await) }
+        }))
+        .await;
+
+        let mut new_on_primary = false;
+        for (i, put) in puts {
+            match put {
+                Ok(_) => new_on_primary |= i == 0,
+                Err(e) if i == 0 => return Err(e),
+                Err(e) => {
+                    failures[i].lock().unwrap().get_or_insert(e);
+                }
+            }
+        }
+
+        Ok((chunk, new_on_primary))
+    })
+    .buffered(concurrency.max(1))
+    .try_collect()
+    .await?;
+
+    let new_chunks = uploaded.iter().filter(|(_, new)| *new).count();
+    debug!(
+        "Uploaded {} new chunks, {} already cached",
+        new_chunks,
+        uploaded.len() - new_chunks
+    );
+
+    let index = ChunkIndex {
+        chunks: uploaded.into_iter().map(|(chunk, _)| chunk).collect(),
+    };
+    let results = failures
+        .iter()
+        .map(|failure| match failure.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        })
+        .collect();
+
+    Ok((index, results))
+}
+
+/// Download the chunks of `index` from `prefix` on `backend`, `concurrency`
+/// at a time, and send the reassembled tarball into `tx` in order. Every
+/// chunk is checked against its id. Returns the size of the tarball.
+pub async fn download(
+    backend: &Arc<dyn StorageBackend>,
+    prefix: &str,
+    index: &ChunkIndex,
+    encoding: &ChunkEncoding,
+    tx: &ChunkSender,
+    concurrency: usize,
+) -> Result<u64> {
+    let mut chunks = stream::iter(&index.chunks)
+        .map(|chunk| async move {
+            let stored =
+                get_bytes(backend, &chunk_key(prefix, &chunk.id, encoding.compression)).await?;
+
+            let encoding = encoding.clone();
+            let chunk = chunk.clone();
+            tokio::task::spawn_blocking(move || {
+                let data = encoding
+                    .decode(&stored)
+                    .with_context(|| format!("Failed to decode chunk {}", chunk.id))?;
+                if data.len() as u64 != chunk.size || encoding.id(&data) != chunk.id {
+                    return Err(anyhow::anyhow!("Chunk {} is corrupt", chunk.id));
+                }
+                Ok(Bytes::from(data))
+            })
+            .await?
+        })
+        .buffered(concurrency.max(1));
+
+    let mut size = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk?;
+        size += chunk.len() as u64;
+        tx.send(Ok(chunk))
+            .await
+            .map_err(|_| anyhow::anyhow!("Chunk stream closed"))?;
+    }
+
+    Ok(size)
+}
+
+/// Copy the chunks of `index` that `dest` does not hold yet from `source`,
+/// as stored, and refresh the ones it does
+pub async fn copy(
+    source: (&str, &Arc<dyn StorageBackend>),
+    dest: (&str, &Arc<dyn StorageBackend>),
+    index: &ChunkIndex,
+    compression: Compression,
+) -> Result<()> {
+    let (source_prefix, source) = source;
+    let (dest_prefix, dest) = dest;
+
+    stream::iter(&index.chunks)
+        .map(Ok)
+        .try_for_each_concurrent(COPY_CONCURRENCY, |chunk| async move {
+            let dest_key = chunk_key(dest_prefix, &chunk.id, compression);
+            if dest.refresh(&dest_key).await? {
+                return Ok(());
+            }
+
+            let stored =
+                get_bytes(source, &chunk_key(source_prefix, &chunk.id, compression)).await?;
+            put_bytes(dest, &dest_key, Bytes::from(stored)).await
+        })
+        .await
+}
+
+async fn put_bytes(backend: &Arc<dyn StorageBackend>, key: &str, data: Bytes) -> Result<()> {
+    let (tx, rx) = streaming::chunk_channel();
+    // The channel has room, so this never waits
+    let _ = tx.send(Ok(data)).await;
+    drop(tx);
+
+    backend.put_stream(key, rx).await?;
+    Ok(())
+}
+
+async fn get_bytes(backend: &Arc<dyn StorageBackend>, key: &str) -> Result<Vec<u8>> {
+    let (tx, mut rx) = streaming::chunk_channel();
+    let collect = async move {
+        let mut data = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            data.extend_from_slice(&chunk?);
+        }
+        Ok::<_, anyhow::Error>(data)
+    };
+
+    let (fetched, data) = tokio::join!(backend.get_stream(key, tx), collect);
+    fetched?;
+    data
+}
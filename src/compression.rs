@@ -0,0 +1,387 @@
+#![allow(dead_code)]
+
+use anyhow::Result;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use liblzma::read::XzDecoder;
+use liblzma::stream::{Check, MtStreamBuilder};
+use liblzma::write::XzEncoder;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Uncompressed bytes per gzip member when compressing on several threads
+pub const GZIP_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Memory the multi-threaded xz decoder may use before it drops threads
+const XZ_DECODER_MEMLIMIT: u64 = 1024 * 1024 * 1024;
+
+/// Resolve a configured thread count, where 0 means one per available core
+pub fn thread_count(configured: usize) -> usize {
+    match configured {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// How archives are compressed before upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    None,
+}
+
+impl Compression {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "gzip" | "gz" => Ok(Self::Gzip),
+            "zstd" | "zst" => Ok(Self::Zstd),
+            "xz" => Ok(Self::Xz),
+            "none" => Ok(Self::None),
+            _ => Err(anyhow::anyhow!(
+                "Unsupported compression: {} (expected gzip, zstd, xz or none)",
+                name
+            )),
+        }
+    }
+
+    /// Name recorded in metadata and accepted by `parse`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+            Self::Xz => "xz",
+            Self::None => "none",
+        }
+    }
+
+    /// File name of the archive object within a cache entry
+    pub fn archive_name(self) -> &'static str {
+        match self {
+            Self::Gzip => "archive.tar.gz",
+            Self::Zstd => "archive.tar.zst",
+            Self::Xz => "archive.tar.xz",
+            Self::None => "archive.tar",
+        }
+    }
+
+    /// Accepted compression levels, or `None` if the format has no levels
+    pub fn level_range(self) -> Option<(i32, i32)> {
+        match self {
+            Self::Gzip | Self::Xz => Some((0, 9)),
+            Self::Zstd => Some((1, 22)),
+            Self::None => None,
+        }
+    }
+
+    fn default_level(self) -> i32 {
+        match self {
+            Self::Gzip => 6,
+            Self::Zstd => 3,
+            Self::Xz => 6,
+            Self::None => 0,
+        }
+    }
+
+    /// Compress everything written to the returned encoder into `writer`,
+    /// at `level` or the format's default, on up to `threads` threads
+    pub fn encoder<W: Write>(
+        self,
+        writer: W,
+        level: Option<i32>,
+        threads: usize,
+    ) -> io::Result<Encoder<W>> {
+        let level = level.unwrap_or_else(|| self.default_level());
+        let threads = threads.max(1);
+
+        Ok(match self {
+            Self::Gzip if threads > 1 => Encoder::ParallelGzip(ParallelGzEncoder::new(
+                writer,
+                flate2::Compression::new(level as u32),
+                threads,
+            )),
+            Self::Gzip => Encoder::Gzip(GzEncoder::new(
+                writer,
+                flate2::Compression::new(level as u32),
+            )),
+            Self::Zstd => {
+                let mut encoder = zstd::Encoder::new(writer, level)?;
+                if threads > 1 {
+                    encoder.multithread(threads as u32)?;
+                }
+                Encoder::Zstd(encoder)
+            }
+            Self::Xz if threads > 1 => {
+                let stream = MtStreamBuilder::new()
+                    .preset(level as u32)
+                    .check(Check::Crc64)
+                    .threads(threads as u32)
+                    .encoder()
+                    .map_err(io::Error::other)?;
+                Encoder::Xz(XzEncoder::new_stream(writer, stream))
+            }
+            Self::Xz => Encoder::Xz(XzEncoder::new(writer, level as u32)),
+            Self::None => Encoder::None(writer),
+        })
+    }
+
+    /// Like `encoder`, but laid out the same whatever `threads` is, so equal
+    /// input at an equal level always compresses to equal bytes: gzip is
+    /// always written as `GZIP_BLOCK_SIZE` members, and zstd and xz always
+    /// use their multi-threaded encoders, whose output does not depend on
+    /// the number of workers. Gzip headers never carry a name or timestamp.
+    pub fn deterministic_encoder<W: Write>(
+        self,
+        writer: W,
+        level: Option<i32>,
+        threads: usize,
+    ) -> io::Result<Encoder<W>> {
+        let threads = threads.max(1);
+
+        match self {
+            Self::Gzip => Ok(Encoder::ParallelGzip(ParallelGzEncoder::new(
+                writer,
+                flate2::Compression::new(level.unwrap_or_else(|| self.default_level()) as u32),
+                threads,
+            ))),
+            Self::Zstd => {
+                let mut encoder =
+                    zstd::Encoder::new(writer, level.unwrap_or_else(|| self.default_level()))?;
+                encoder.multithread(threads as u32)?;
+                Ok(Encoder::Zstd(encoder))
+            }
+            Self::Xz => {
+                let stream = MtStreamBuilder::new()
+                    .preset(level.unwrap_or_else(|| self.default_level()) as u32)
+                    .check(Check::Crc64)
+                    .threads(threads as u32)
+                    .encoder()
+                    .map_err(io::Error::other)?;
+                Ok(Encoder::Xz(XzEncoder::new_stream(writer, stream)))
+            }
+            Self::None => self.encoder(writer, level, threads),
+        }
+    }
+
+    /// Decompress `reader`, using up to `threads` threads where the format
+    /// allows it (xz archives written on several threads). The decoder stops
+    /// at the end of the compressed data, leaving any trailing bytes in
+    /// `reader`.
+    pub fn decoder<'a, R: Read + 'a>(
+        self,
+        reader: R,
+        threads: usize,
+    ) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            // Archives compressed on several threads hold one member per block
+            Self::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Self::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            Self::Xz if threads > 1 => {
+                let stream = MtStreamBuilder::new()
+                    .threads(threads as u32)
+                    .memlimit_threading(XZ_DECODER_MEMLIMIT)
+                    .memlimit_stop(u64::MAX)
+                    .decoder()
+                    .map_err(io::Error::other)?;
+                Box::new(XzDecoder::new_stream(reader, stream))
+            }
+            Self::Xz => Box::new(XzDecoder::new(reader)),
+            Self::None => Box::new(reader),
+        })
+    }
+}
+
+/// Writer half of a `Compression`
+pub enum Encoder<W: Write> {
+    Gzip(GzEncoder<W>),
+    ParallelGzip(ParallelGzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Xz(XzEncoder<W>),
+    None(W),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write the end of the compressed stream and return the inner writer
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Gzip(encoder) => encoder.finish(),
+            Self::ParallelGzip(encoder) => encoder.finish(),
+            Self::Zstd(encoder) => encoder.finish(),
+            Self::Xz(encoder) => encoder.finish(),
+            Self::None(writer) => Ok(writer),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Gzip(encoder) => encoder.write(data),
+            Self::ParallelGzip(encoder) => encoder.write(data),
+            Self::Zstd(encoder) => encoder.write(data),
+            Self::Xz(encoder) => encoder.write(data),
+            Self::None(writer) => writer.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::ParallelGzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+            Self::Xz(encoder) => encoder.flush(),
+            Self::None(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Gzip encoder that compresses `GZIP_BLOCK_SIZE` blocks on up to `threads`
+/// threads at once and writes them out in order as consecutive gzip members,
+/// which gzip readers decode as a single stream.
+pub struct ParallelGzEncoder<W: Write> {
+    inner: W,
+    threads: usize,
+    buffer: Vec<u8>,
+    workers: GzWorkers,
+    /// Members of the blocks being compressed, oldest first
+    pending: VecDeque<mpsc::Receiver<io::Result<Vec<u8>>>>,
+    members: usize,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    pub fn new(inner: W, level: flate2::Compression, threads: usize) -> Self {
+        let threads = threads.max(1);
+        Self {
+            inner,
+            threads,
+            buffer: Vec::with_capacity(GZIP_BLOCK_SIZE),
+            workers: GzWorkers::new(level, threads),
+            pending: VecDeque::new(),
+            members: 0,
+        }
+    }
+
+    /// Compress the last partial block, write every member and return the
+    /// inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        // Even empty input needs one member to be valid gzip
+        if !self.buffer.is_empty() || self.members == 0 {
+            let block = std::mem::take(&mut self.buffer);
+            self.submit(block)?;
+        }
+        while !self.pending.is_empty() {
+            self.write_next()?;
+        }
+        Ok(self.inner)
+    }
+
+    fn submit(&mut self, block: Vec<u8>) -> io::Result<()> {
+        if self.pending.len() >= self.threads {
+            self.write_next()?;
+        }
+
+        self.pending.push_back(self.workers.compress(block));
+        self.members += 1;
+        Ok(())
+    }
+
+    fn write_next(&mut self) -> io::Result<()> {
+        let Some(member) = self.pending.pop_front() else {
+            return Ok(());
+        };
+        let member = member
+            .recv()
+            .map_err(|_| io::Error::other("compression thread panicked"))??;
+        self.inner.write_all(&member)
+    }
+}
+
+/// A block to compress and where to send its gzip member
+type GzJob = (Vec<u8>, mpsc::Sender<io::Result<Vec<u8>>>);
+
+/// Up to `limit` long-lived threads taking blocks from one queue, started
+/// as blocks arrive and stopped when dropped
+struct GzWorkers {
+    level: flate2::Compression,
+    limit: usize,
+    jobs: Option<mpsc::Sender<GzJob>>,
+    queue: Arc<Mutex<mpsc::Receiver<GzJob>>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl GzWorkers {
+    fn new(level: flate2::Compression, limit: usize) -> Self {
+        let (jobs, queue) = mpsc::channel();
+        Self {
+            level,
+            limit,
+            jobs: Some(jobs),
+            queue: Arc::new(Mutex::new(queue)),
+            threads: Vec::new(),
+        }
+    }
+
+    /// Queue `block`, returning where its member will arrive
+    fn compress(&mut self, block: Vec<u8>) -> mpsc::Receiver<io::Result<Vec<u8>>> {
+        if self.threads.len() < self.limit {
+            let queue = self.queue.clone();
+            let level = self.level;
+            self.threads.push(thread::spawn(move || loop {
+                // The lock is only held while waiting for the next block
+                let job = queue.lock().unwrap().recv();
+                let Ok((block, member)) = job else {
+                    return;
+                };
+                let _ = member.send(compress_member(&block, level));
+            }));
+        }
+
+        let (member, receiver) = mpsc::channel();
+        if let Some(jobs) = &self.jobs {
+            // Should every worker have died, the receiver reports it
+            let _ = jobs.send((block, member));
+        }
+        receiver
+    }
+}
+
+impl Drop for GzWorkers {
+    fn drop(&mut self) {
+        // Closing the queue lets each worker exit after its current block
+        self.jobs.take();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn compress_member(block: &[u8], level: flate2::Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(block.len() / 2), level);
+    encoder.write_all(block)?;
+    encoder.finish()
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+
+        while self.buffer.len() >= GZIP_BLOCK_SIZE {
+            let rest = self.buffer.split_off(GZIP_BLOCK_SIZE);
+            let block = std::mem::replace(&mut self.buffer, rest);
+            self.submit(block)?;
+        }
+        Ok(data.len())
+    }
+
+    /// Writes out finished members; a partial block stays buffered so member
+    /// boundaries do not depend on when callers flush
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            self.write_next()?;
+        }
+        self.inner.flush()
+    }
+}
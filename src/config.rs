@@ -1,25 +1,116 @@
 #![allow(dead_code)]
 
 use anyhow::{Context, Result};
+use aws_sdk_s3::types::{ServerSideEncryption, StorageClass};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::path::{Path, PathBuf};
-use tracing::{error, info, warn};
+use tracing::{info, warn};
 
-use crate::s3_operations::S3Client;
+use crate::archive;
+use crate::chunks;
+use crate::compression::Compression;
 use crate::utils;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub enabled: bool,
     pub bucket: String,
     pub region: String,
     pub prefix: String,
+    /// Custom S3 endpoint, e.g. a MinIO or Ceph gateway
+    pub endpoint_url: Option<String>,
+    /// Address buckets as `endpoint/bucket` instead of `bucket.endpoint`
+    pub force_path_style: bool,
+    /// Named profile from the AWS config and credentials files
+    pub profile: Option<String>,
+    /// IAM role assumed with the profile's credentials before accessing S3
+    pub role_arn: Option<String>,
+    /// Server-side encryption of uploaded objects: `AES256` (SSE-S3) or `aws:kms` (SSE-KMS)
+    pub server_side_encryption: Option<String>,
+    /// KMS key for SSE-KMS; S3 uses the account's AWS-managed key when unset
+    pub sse_kms_key_id: Option<String>,
+    /// Storage class of uploaded objects, e.g. `STANDARD_IA` or `INTELLIGENT_TIERING`
+    pub storage_class: Option<String>,
+    /// Tags set on every uploaded object
+    pub object_tags: BTreeMap<String, String>,
+    /// File holding the hex key that archives are encrypted with before upload
+    pub encryption_key_file: Option<PathBuf>,
+    /// Hex encryption key, only taken from `MISE_S3_CACHE_ENCRYPTION_KEY`
+    #[serde(skip)]
+    pub encryption_key: Option<String>,
     pub ttl_seconds: u64,
+    /// Number of multipart upload parts sent concurrently
     pub parallel_uploads: usize,
+    /// Part size in bytes for multipart uploads; larger archives are split
+    pub multipart_part_size: u64,
+    /// Number of byte ranges of one object downloaded concurrently
+    pub parallel_downloads: usize,
+    /// Objects at least this large are downloaded in resumable ranges of this size
+    pub download_chunk_size: u64,
+    /// Combined upload rate cap in bytes per second (0 for unlimited)
+    pub upload_limit: u64,
+    /// Combined download rate cap in bytes per second (0 for unlimited)
+    pub download_limit: u64,
+    /// Attempts per S3 request, including the first
+    pub retry_max_attempts: u32,
+    /// Delay before the first retry; doubles with every further attempt
+    pub retry_base_delay_ms: u64,
+    /// Randomize retry delays so parallel clients do not retry in lockstep
+    pub retry_jitter: bool,
+    /// Archive format for stores: `gzip`, `zstd`, `xz` or `none`
     pub compression: String,
+    /// Compression level; each format's default when unset
+    pub compression_level: Option<i32>,
+    /// Threads used to compress and decompress archives (0 for one per core)
+    pub compression_threads: usize,
+    /// How entries are stored: `archive` (one object each) or `chunked`
+    /// (content-addressed chunks shared between entries)
+    pub storage_mode: String,
+    /// Keep extended attributes of installed files in archives
+    pub preserve_xattrs: bool,
+    /// Write archives whose bytes only depend on the installed files, and
+    /// skip stores of installs that are unchanged since they were cached
+    pub deterministic_archives: bool,
+    /// Glob patterns of paths left out of every archive, relative to the
+    /// install directory, e.g. `**/__pycache__`
+    pub exclude: Vec<String>,
+    /// Further exclude patterns for individual tools
+    pub tool_excludes: BTreeMap<String, Vec<String>>,
     pub debug: bool,
     pub log_file: Option<PathBuf>,
+    /// Size cap in bytes for archives kept in the local cache dir (0 disables it)
+    pub local_cache_max_size: u64,
+    /// Remote caches consulted in order when the primary bucket misses
+    pub fallbacks: Vec<RemoteCache>,
+    /// Remote caches that every store is copied to
+    pub replicas: Vec<RemoteCache>,
+}
+
+/// An additional cache location. Region and prefix default to the primary's.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteCache {
+    pub bucket: String,
+    pub region: Option<String>,
+    pub prefix: Option<String>,
+}
+
+impl RemoteCache {
+    /// Parse a comma-separated list of bucket locations, as used by env vars
+    pub fn parse_list(value: &str) -> Vec<RemoteCache> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|bucket| !bucket.is_empty())
+            .map(|bucket| RemoteCache {
+                bucket: bucket.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
 }
 
 impl Default for Config {
@@ -29,11 +120,39 @@ impl Default for Config {
             bucket: String::new(),
             region: "us-east-1".to_string(),
             prefix: "mise-cache".to_string(),
+            endpoint_url: None,
+            force_path_style: false,
+            profile: None,
+            role_arn: None,
+            server_side_encryption: None,
+            sse_kms_key_id: None,
+            storage_class: None,
+            object_tags: BTreeMap::new(),
+            encryption_key_file: None,
+            encryption_key: None,
             ttl_seconds: 604800, // 7 days
             parallel_uploads: 3,
+            multipart_part_size: 64 * 1024 * 1024, // 64 MiB
+            parallel_downloads: 8,
+            download_chunk_size: 16 * 1024 * 1024, // 16 MiB
+            upload_limit: 0,
+            download_limit: 0,
+            retry_max_attempts: 5,
+            retry_base_delay_ms: 200,
+            retry_jitter: true,
             compression: "gzip".to_string(),
+            compression_level: None,
+            compression_threads: 0,
+            storage_mode: chunks::ARCHIVE.to_string(),
+            preserve_xattrs: false,
+            deterministic_archives: false,
+            exclude: Vec::new(),
+            tool_excludes: BTreeMap::new(),
             debug: false,
             log_file: None,
+            local_cache_max_size: 5 * 1024 * 1024 * 1024, // 5 GiB
+            fallbacks: Vec::new(),
+            replicas: Vec::new(),
         }
     }
 }
@@ -71,6 +190,46 @@ impl Config {
             self.prefix = val;
         }
 
+        if let Ok(val) = env::var("MISE_S3_CACHE_ENDPOINT_URL") {
+            self.endpoint_url = (!val.is_empty()).then_some(val);
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_FORCE_PATH_STYLE") {
+            self.force_path_style = val.to_lowercase() == "true";
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_PROFILE") {
+            self.profile = (!val.is_empty()).then_some(val);
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_ROLE_ARN") {
+            self.role_arn = (!val.is_empty()).then_some(val);
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_SSE") {
+            self.server_side_encryption = (!val.is_empty()).then_some(val);
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_SSE_KMS_KEY_ID") {
+            self.sse_kms_key_id = (!val.is_empty()).then_some(val);
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_STORAGE_CLASS") {
+            self.storage_class = (!val.is_empty()).then_some(val);
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_OBJECT_TAGS") {
+            self.object_tags = utils::parse_key_value_list(&val);
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_ENCRYPTION_KEY_FILE") {
+            self.encryption_key_file = (!val.is_empty()).then(|| PathBuf::from(val));
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_ENCRYPTION_KEY") {
+            self.encryption_key = (!val.is_empty()).then_some(val);
+        }
+
         if let Ok(val) = env::var("MISE_S3_CACHE_TTL") {
             if let Ok(ttl) = val.parse::<u64>() {
                 self.ttl_seconds = ttl;
@@ -83,6 +242,84 @@ impl Config {
             }
         }
 
+        if let Ok(val) = env::var("MISE_S3_CACHE_MULTIPART_PART_SIZE") {
+            if let Some(size) = utils::parse_human_size(&val) {
+                self.multipart_part_size = size;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_PARALLEL_DOWNLOADS") {
+            if let Ok(parallel) = val.parse::<usize>() {
+                self.parallel_downloads = parallel;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_DOWNLOAD_CHUNK_SIZE") {
+            if let Some(size) = utils::parse_human_size(&val) {
+                self.download_chunk_size = size;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_UPLOAD_LIMIT") {
+            if let Some(rate) = utils::parse_human_rate(&val) {
+                self.upload_limit = rate;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_DOWNLOAD_LIMIT") {
+            if let Some(rate) = utils::parse_human_rate(&val) {
+                self.download_limit = rate;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_RETRY_MAX_ATTEMPTS") {
+            if let Ok(attempts) = val.parse::<u32>() {
+                self.retry_max_attempts = attempts;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_RETRY_BASE_DELAY_MS") {
+            if let Ok(delay) = val.parse::<u64>() {
+                self.retry_base_delay_ms = delay;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_RETRY_JITTER") {
+            self.retry_jitter = val.to_lowercase() == "true";
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_COMPRESSION") {
+            self.compression = val;
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_COMPRESSION_LEVEL") {
+            if let Ok(level) = val.parse::<i32>() {
+                self.compression_level = Some(level);
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_COMPRESSION_THREADS") {
+            if let Ok(threads) = val.parse::<usize>() {
+                self.compression_threads = threads;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_STORAGE_MODE") {
+            self.storage_mode = val;
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_PRESERVE_XATTRS") {
+            self.preserve_xattrs = val.to_lowercase() == "true";
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_DETERMINISTIC_ARCHIVES") {
+            self.deterministic_archives = val.to_lowercase() == "true";
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_EXCLUDE") {
+            self.exclude = utils::parse_list(&val);
+        }
+
         if let Ok(val) = env::var("MISE_S3_CACHE_DEBUG") {
             self.debug = val.to_lowercase() == "true";
         }
@@ -90,6 +327,20 @@ impl Config {
         if let Ok(val) = env::var("MISE_S3_CACHE_LOG_FILE") {
             self.log_file = Some(PathBuf::from(val));
         }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_LOCAL_CACHE_SIZE") {
+            if let Some(size) = utils::parse_human_size(&val) {
+                self.local_cache_max_size = size;
+            }
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_FALLBACKS") {
+            self.fallbacks = RemoteCache::parse_list(&val);
+        }
+
+        if let Ok(val) = env::var("MISE_S3_CACHE_REPLICAS") {
+            self.replicas = RemoteCache::parse_list(&val);
+        }
     }
 
     fn load_from_files(&mut self, config_path: Option<&str>) -> Result<()> {
@@ -159,6 +410,31 @@ impl Config {
                     "S3_CACHE_BUCKET" => self.bucket = value.to_string(),
                     "S3_CACHE_REGION" => self.region = value.to_string(),
                     "S3_CACHE_PREFIX" => self.prefix = value.to_string(),
+                    "S3_CACHE_ENDPOINT_URL" => {
+                        self.endpoint_url = (!value.is_empty()).then(|| value.to_string())
+                    }
+                    "S3_CACHE_FORCE_PATH_STYLE" => {
+                        self.force_path_style = value.to_lowercase() == "true"
+                    }
+                    "S3_CACHE_PROFILE" => {
+                        self.profile = (!value.is_empty()).then(|| value.to_string())
+                    }
+                    "S3_CACHE_ROLE_ARN" => {
+                        self.role_arn = (!value.is_empty()).then(|| value.to_string())
+                    }
+                    "S3_CACHE_SSE" => {
+                        self.server_side_encryption = (!value.is_empty()).then(|| value.to_string())
+                    }
+                    "S3_CACHE_SSE_KMS_KEY_ID" => {
+                        self.sse_kms_key_id = (!value.is_empty()).then(|| value.to_string())
+                    }
+                    "S3_CACHE_STORAGE_CLASS" => {
+                        self.storage_class = (!value.is_empty()).then(|| value.to_string())
+                    }
+                    "S3_CACHE_OBJECT_TAGS" => self.object_tags = utils::parse_key_value_list(value),
+                    "S3_CACHE_ENCRYPTION_KEY_FILE" => {
+                        self.encryption_key_file = (!value.is_empty()).then(|| PathBuf::from(value))
+                    }
                     "S3_CACHE_TTL" => {
                         if let Ok(ttl) = value.parse::<u64>() {
                             self.ttl_seconds = ttl;
@@ -169,7 +445,69 @@ impl Config {
                             self.parallel_uploads = parallel;
                         }
                     }
+                    "S3_CACHE_MULTIPART_PART_SIZE" => {
+                        if let Some(size) = utils::parse_human_size(value) {
+                            self.multipart_part_size = size;
+                        }
+                    }
+                    "S3_CACHE_PARALLEL_DOWNLOADS" => {
+                        if let Ok(parallel) = value.parse::<usize>() {
+                            self.parallel_downloads = parallel;
+                        }
+                    }
+                    "S3_CACHE_DOWNLOAD_CHUNK_SIZE" => {
+                        if let Some(size) = utils::parse_human_size(value) {
+                            self.download_chunk_size = size;
+                        }
+                    }
+                    "S3_CACHE_UPLOAD_LIMIT" => {
+                        if let Some(rate) = utils::parse_human_rate(value) {
+                            self.upload_limit = rate;
+                        }
+                    }
+                    "S3_CACHE_DOWNLOAD_LIMIT" => {
+                        if let Some(rate) = utils::parse_human_rate(value) {
+                            self.download_limit = rate;
+                        }
+                    }
+                    "S3_CACHE_RETRY_MAX_ATTEMPTS" => {
+                        if let Ok(attempts) = value.parse::<u32>() {
+                            self.retry_max_attempts = attempts;
+                        }
+                    }
+                    "S3_CACHE_RETRY_BASE_DELAY_MS" => {
+                        if let Ok(delay) = value.parse::<u64>() {
+                            self.retry_base_delay_ms = delay;
+                        }
+                    }
+                    "S3_CACHE_RETRY_JITTER" => self.retry_jitter = value.to_lowercase() == "true",
+                    "S3_CACHE_COMPRESSION" => self.compression = value.to_string(),
+                    "S3_CACHE_COMPRESSION_LEVEL" => {
+                        if let Ok(level) = value.parse::<i32>() {
+                            self.compression_level = Some(level);
+                        }
+                    }
+                    "S3_CACHE_COMPRESSION_THREADS" => {
+                        if let Ok(threads) = value.parse::<usize>() {
+                            self.compression_threads = threads;
+                        }
+                    }
+                    "S3_CACHE_STORAGE_MODE" => self.storage_mode = value.to_string(),
+                    "S3_CACHE_PRESERVE_XATTRS" => {
+                        self.preserve_xattrs = value.to_lowercase() == "true"
+                    }
+                    "S3_CACHE_DETERMINISTIC_ARCHIVES" => {
+                        self.deterministic_archives = value.to_lowercase() == "true"
+                    }
+                    "S3_CACHE_EXCLUDE" => self.exclude = utils::parse_list(value),
                     "S3_CACHE_DEBUG" => self.debug = value.to_lowercase() == "true",
+                    "S3_CACHE_LOCAL_CACHE_SIZE" => {
+                        if let Some(size) = utils::parse_human_size(value) {
+                            self.local_cache_max_size = size;
+                        }
+                    }
+                    "S3_CACHE_FALLBACKS" => self.fallbacks = RemoteCache::parse_list(value),
+                    "S3_CACHE_REPLICAS" => self.replicas = RemoteCache::parse_list(value),
                     _ => {} // Ignore unknown keys
                 }
             }
@@ -183,16 +521,182 @@ impl Config {
         }
         self.region = other.region;
         self.prefix = other.prefix;
+        if other.endpoint_url.is_some() {
+            self.endpoint_url = other.endpoint_url;
+        }
+        self.force_path_style = other.force_path_style;
+        if other.profile.is_some() {
+            self.profile = other.profile;
+        }
+        if other.role_arn.is_some() {
+            self.role_arn = other.role_arn;
+        }
+        if other.server_side_encryption.is_some() {
+            self.server_side_encryption = other.server_side_encryption;
+        }
+        if other.sse_kms_key_id.is_some() {
+            self.sse_kms_key_id = other.sse_kms_key_id;
+        }
+        if other.storage_class.is_some() {
+            self.storage_class = other.storage_class;
+        }
+        if !other.object_tags.is_empty() {
+            self.object_tags = other.object_tags;
+        }
+        if other.encryption_key_file.is_some() {
+            self.encryption_key_file = other.encryption_key_file;
+        }
         self.ttl_seconds = other.ttl_seconds;
         self.parallel_uploads = other.parallel_uploads;
+        self.multipart_part_size = other.multipart_part_size;
+        self.parallel_downloads = other.parallel_downloads;
+        self.download_chunk_size = other.download_chunk_size;
+        self.upload_limit = other.upload_limit;
+        self.download_limit = other.download_limit;
+        self.retry_max_attempts = other.retry_max_attempts;
+        self.retry_base_delay_ms = other.retry_base_delay_ms;
+        self.retry_jitter = other.retry_jitter;
         self.compression = other.compression;
+        if other.compression_level.is_some() {
+            self.compression_level = other.compression_level;
+        }
+        self.compression_threads = other.compression_threads;
+        self.storage_mode = other.storage_mode;
+        self.preserve_xattrs = other.preserve_xattrs;
+        self.deterministic_archives = other.deterministic_archives;
+        if !other.exclude.is_empty() {
+            self.exclude = other.exclude;
+        }
+        if !other.tool_excludes.is_empty() {
+            self.tool_excludes = other.tool_excludes;
+        }
         self.debug = other.debug;
         if other.log_file.is_some() {
             self.log_file = other.log_file;
         }
+        self.local_cache_max_size = other.local_cache_max_size;
+        if !other.fallbacks.is_empty() {
+            self.fallbacks = other.fallbacks;
+        }
+        if !other.replicas.is_empty() {
+            self.replicas = other.replicas;
+        }
     }
 
     fn validate(&self) -> Result<()> {
+        self.validate_location()?;
+
+        if self.parallel_uploads == 0 {
+            return Err(anyhow::anyhow!("parallel_uploads must be at least 1"));
+        }
+
+        // S3 rejects multipart parts (other than the last) below 5 MiB
+        if self.multipart_part_size < 5 * 1024 * 1024 {
+            return Err(anyhow::anyhow!(
+                "multipart_part_size must be at least 5MB, got {}",
+                utils::human_readable_size(self.multipart_part_size)
+            ));
+        }
+
+        if self.parallel_downloads == 0 {
+            return Err(anyhow::anyhow!("parallel_downloads must be at least 1"));
+        }
+
+        if self.download_chunk_size < 1024 * 1024 {
+            return Err(anyhow::anyhow!(
+                "download_chunk_size must be at least 1MB, got {}",
+                utils::human_readable_size(self.download_chunk_size)
+            ));
+        }
+
+        if let Some(endpoint_url) = &self.endpoint_url {
+            if !endpoint_url.starts_with("http://") && !endpoint_url.starts_with("https://") {
+                return Err(anyhow::anyhow!(
+                    "endpoint_url must be an http(s) URL, got {}",
+                    endpoint_url
+                ));
+            }
+        }
+
+        if let Some(role_arn) = &self.role_arn {
+            if !role_arn.starts_with("arn:") {
+                return Err(anyhow::anyhow!("Invalid role_arn: {}", role_arn));
+            }
+        }
+
+        if let Some(sse) = &self.server_side_encryption {
+            if !ServerSideEncryption::values().contains(&sse.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "server_side_encryption must be one of {}, got {}",
+                    ServerSideEncryption::values().join(", "),
+                    sse
+                ));
+            }
+        }
+
+        if self.sse_kms_key_id.is_some()
+            && self.server_side_encryption.as_deref() != Some("aws:kms")
+        {
+            return Err(anyhow::anyhow!(
+                "sse_kms_key_id requires server_side_encryption = \"aws:kms\""
+            ));
+        }
+
+        if let Some(storage_class) = &self.storage_class {
+            if !StorageClass::values().contains(&storage_class.as_str()) {
+                return Err(anyhow::anyhow!("Unknown storage_class: {}", storage_class));
+            }
+        }
+
+        let compression = Compression::parse(&self.compression)?;
+        if let (Some(level), Some((min, max))) = (self.compression_level, compression.level_range())
+        {
+            if level < min || level > max {
+                return Err(anyhow::anyhow!(
+                    "compression_level for {} must be between {} and {}, got {}",
+                    compression.name(),
+                    min,
+                    max,
+                    level
+                ));
+            }
+        }
+
+        if self.storage_mode != chunks::ARCHIVE && self.storage_mode != chunks::CHUNKED {
+            return Err(anyhow::anyhow!(
+                "Unknown storage_mode: {} (expected {} or {})",
+                self.storage_mode,
+                chunks::ARCHIVE,
+                chunks::CHUNKED
+            ));
+        }
+
+        archive::compile_excludes(&self.exclude)?;
+        for (tool, patterns) in &self.tool_excludes {
+            archive::compile_excludes(patterns)
+                .with_context(|| format!("Invalid tool_excludes for {}", tool))?;
+        }
+
+        if self.retry_max_attempts == 0 {
+            return Err(anyhow::anyhow!("retry_max_attempts must be at least 1"));
+        }
+
+        for remote in &self.fallbacks {
+            self.for_remote(remote)
+                .validate_location()
+                .with_context(|| format!("Invalid fallback cache: {}", remote.bucket))?;
+        }
+
+        for remote in &self.replicas {
+            self.for_remote(remote)
+                .validate_location()
+                .with_context(|| format!("Invalid replica: {}", remote.bucket))?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_location(&self) -> Result<()> {
         if self.bucket.is_empty() {
             return Err(anyhow::anyhow!(
                 "S3 bucket not configured. Set MISE_S3_CACHE_BUCKET environment variable"
@@ -200,8 +704,25 @@ impl Config {
         }
 
         // Validate bucket name format (basic validation)
-        if !utils::is_valid_s3_bucket_name(&self.bucket) {
-            return Err(anyhow::anyhow!("Invalid S3 bucket name: {}", self.bucket));
+        match self.bucket.split_once("://") {
+            Some(("file", path)) => {
+                if path.is_empty() {
+                    return Err(anyhow::anyhow!("Local cache path cannot be empty"));
+                }
+            }
+            Some(("http" | "https", host)) => {
+                if host.is_empty() {
+                    return Err(anyhow::anyhow!("Invalid HTTP mirror URL: {}", self.bucket));
+                }
+            }
+            Some((scheme, _)) => {
+                return Err(anyhow::anyhow!("Unsupported cache location: {}://", scheme));
+            }
+            None => {
+                if !utils::is_valid_s3_bucket_name(&self.bucket) {
+                    return Err(anyhow::anyhow!("Invalid S3 bucket name: {}", self.bucket));
+                }
+            }
         }
 
         // Validate region
@@ -217,6 +738,21 @@ impl Config {
         Ok(())
     }
 
+    /// Config for an additional cache location, inheriting every other setting
+    pub fn for_remote(&self, remote: &RemoteCache) -> Config {
+        let mut config = self.clone();
+        config.bucket = remote.bucket.clone();
+        if let Some(region) = &remote.region {
+            config.region = region.clone();
+        }
+        if let Some(prefix) = &remote.prefix {
+            config.prefix = prefix.clone();
+        }
+        config.fallbacks = Vec::new();
+        config.replicas = Vec::new();
+        config
+    }
+
     pub fn get_cache_key(&self, tool: &str, version: &str) -> String {
         let platform = utils::get_platform();
         let arch = utils::get_architecture();
@@ -226,41 +762,6 @@ impl Config {
         )
     }
 
-    pub async fn show_status(&self, s3_client: &S3Client) {
-        println!("📋 S3 Cache Configuration");
-        println!("========================");
-        println!("Enabled: {}", self.enabled);
-        println!("Bucket: {}", self.bucket);
-        println!("Region: {}", self.region);
-        println!("Prefix: {}", self.prefix);
-        println!("TTL: {}s", self.ttl_seconds);
-        println!("Parallel uploads: {}", self.parallel_uploads);
-        println!("Compression: {}", self.compression);
-        println!("Debug: {}", self.debug);
-
-        if let Some(log_file) = &self.log_file {
-            println!("Log file: {}", log_file.display());
-        }
-
-        println!();
-
-        // Test S3 connectivity
-        match s3_client.test_connectivity().await {
-            Ok(_) => {
-                println!("✅ S3 connectivity: OK");
-
-                // Get cache size
-                match s3_client.get_cache_size(&self.prefix).await {
-                    Ok(size) => println!("📊 Cache size: {}", utils::human_readable_size(size)),
-                    Err(e) => warn!("Failed to get cache size: {}", e),
-                }
-            }
-            Err(e) => {
-                error!("❌ S3 connectivity: FAILED - {}", e);
-            }
-        }
-    }
-
     pub fn get_stats_file_path(&self) -> PathBuf {
         if let Some(home) = dirs::home_dir() {
             home.join(".cache/mise-s3/stats.json")
@@ -0,0 +1,264 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use crate::config::Config;
+
+/// Algorithm name recorded in the metadata of encrypted entries
+pub const ALGORITHM: &str = "AES-256-GCM-STREAM";
+
+/// Plaintext bytes sealed per segment
+pub const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Authentication tag appended to every segment
+const TAG_LEN: usize = 16;
+
+const MAGIC: &[u8; 4] = b"MS3E";
+const FORMAT_VERSION: u8 = 1;
+const PREFIX_LEN: usize = 7;
+const HEADER_LEN: usize = MAGIC.len() + 1 + PREFIX_LEN;
+
+/// A 256-bit key for client-side archive encryption.
+///
+/// Archives are split into segments that are sealed one by one, each with a
+/// nonce made of a random per-archive prefix, the segment counter and a flag
+/// marking the last segment, so reordered, dropped or truncated segments all
+/// fail authentication.
+pub struct EncryptionKey {
+    key: LessSafeKey,
+    /// Names chunks of encrypted entries, derived so it differs from `key`
+    chunk_id_key: hmac::Key,
+    id: String,
+}
+
+impl EncryptionKey {
+    /// Parse a key given as 64 hex characters, e.g. from `openssl rand -hex 32`
+    pub fn from_hex(hex_key: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_key.trim())
+            .map_err(|_| anyhow::anyhow!("Encryption key must be 64 hex characters"))?;
+        if bytes.len() != 32 {
+            return Err(anyhow::anyhow!(
+                "Encryption key must be 32 bytes, got {}",
+                bytes.len()
+            ));
+        }
+
+        let key = UnboundKey::new(&AES_256_GCM, &bytes)
+            .map_err(|_| anyhow::anyhow!("Invalid encryption key"))?;
+
+        // Identifies the key in metadata without revealing it
+        let id = format!("{:x}", Sha256::digest(&bytes))[..16].to_string();
+
+        let chunk_id_key = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, &bytes),
+            b"mise-s3-cache chunk id",
+        );
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            chunk_id_key: hmac::Key::new(hmac::HMAC_SHA256, chunk_id_key.as_ref()),
+            id,
+        })
+    }
+
+    /// Load the configured key: `MISE_S3_CACHE_ENCRYPTION_KEY` wins over
+    /// `encryption_key_file`. Returns `None` when neither is set.
+    pub fn from_config(config: &Config) -> Result<Option<Arc<Self>>> {
+        if let Some(key) = &config.encryption_key {
+            return Ok(Some(Arc::new(Self::from_hex(key)?)));
+        }
+
+        let Some(path) = &config.encryption_key_file else {
+            return Ok(None);
+        };
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read encryption key {}", path.display()))?;
+        let key = Self::from_hex(&content)
+            .with_context(|| format!("Invalid encryption key in {}", path.display()))?;
+        Ok(Some(Arc::new(key)))
+    }
+
+    /// Short fingerprint of the key, recorded with every archive it seals
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Keyed hash naming a chunk of plaintext, so chunk names do not reveal
+    /// the content of encrypted entries to anyone without the key
+    pub fn chunk_id(&self, data: &[u8]) -> String {
+        hex::encode(hmac::sign(&self.chunk_id_key, data))
+    }
+}
+
+fn segment_nonce(prefix: &[u8], counter: u32, last: bool) -> io::Result<Nonce> {
+    if counter == u32::MAX {
+        return Err(io::Error::other("archive too large to encrypt"));
+    }
+
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..NONCE_LEN - 1].copy_from_slice(&counter.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last as u8;
+    Ok(Nonce::assume_unique_for_key(nonce))
+}
+
+/// Blocking writer that encrypts everything written to it into `inner`.
+///
+/// `finish` must be called to seal the last segment; dropping the writer
+/// leaves a truncated archive that will not decrypt.
+pub struct EncryptingWriter<W: Write> {
+    inner: W,
+    key: Arc<EncryptionKey>,
+    header: [u8; HEADER_LEN],
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    pub fn new(mut inner: W, key: Arc<EncryptionKey>) -> io::Result<Self> {
+        let mut header = [0u8; HEADER_LEN];
+        header[..MAGIC.len()].copy_from_slice(MAGIC);
+        header[MAGIC.len()] = FORMAT_VERSION;
+        SystemRandom::new()
+            .fill(&mut header[MAGIC.len() + 1..])
+            .map_err(|_| io::Error::other("failed to generate a nonce"))?;
+        inner.write_all(&header)?;
+
+        Ok(Self {
+            inner,
+            key,
+            header,
+            counter: 0,
+            buffer: Vec::with_capacity(SEGMENT_SIZE + TAG_LEN),
+        })
+    }
+
+    /// Seal the final segment and return the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let segment = std::mem::take(&mut self.buffer);
+        self.seal(segment, true)?;
+        Ok(self.inner)
+    }
+
+    fn seal(&mut self, mut segment: Vec<u8>, last: bool) -> io::Result<()> {
+        let nonce = segment_nonce(&self.header[MAGIC.len() + 1..], self.counter, last)?;
+        self.key
+            .key
+            .seal_in_place_append_tag(nonce, Aad::from(&self.header), &mut segment)
+            .map_err(|_| io::Error::other("failed to encrypt archive"))?;
+        self.counter += 1;
+        self.inner.write_all(&segment)
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+
+        // Hold back a full segment until more data shows it is not the last
+        while self.buffer.len() > SEGMENT_SIZE {
+            let rest = self.buffer.split_off(SEGMENT_SIZE);
+            let segment = std::mem::replace(&mut self.buffer, rest);
+            self.seal(segment, false)?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Blocking reader that decrypts an `EncryptingWriter` stream from `inner`,
+/// failing with `InvalidData` on a wrong key or tampered data.
+pub struct DecryptingReader<R: Read> {
+    inner: R,
+    key: Arc<EncryptionKey>,
+    header: [u8; HEADER_LEN],
+    counter: u32,
+    plaintext: Vec<u8>,
+    pos: usize,
+    /// First byte of the next segment, read to tell whether this one is last
+    lookahead: Option<u8>,
+    done: bool,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    pub fn new(mut inner: R, key: Arc<EncryptionKey>) -> io::Result<Self> {
+        let mut header = [0u8; HEADER_LEN];
+        inner.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an encrypted archive",
+            ));
+        }
+
+        Ok(Self {
+            inner,
+            key,
+            header,
+            counter: 0,
+            plaintext: Vec::new(),
+            pos: 0,
+            lookahead: None,
+            done: false,
+        })
+    }
+
+    fn next_segment(&mut self) -> io::Result<()> {
+        let mut segment = Vec::with_capacity(SEGMENT_SIZE + TAG_LEN + 1);
+        segment.extend(self.lookahead.take());
+        let wanted = SEGMENT_SIZE + TAG_LEN + 1 - segment.len();
+        (&mut self.inner)
+            .take(wanted as u64)
+            .read_to_end(&mut segment)?;
+
+        let last = segment.len() <= SEGMENT_SIZE + TAG_LEN;
+        if !last {
+            self.lookahead = segment.pop();
+        }
+
+        let nonce = segment_nonce(&self.header[MAGIC.len() + 1..], self.counter, last)?;
+        let len = self
+            .key
+            .key
+            .open_in_place(nonce, Aad::from(&self.header), &mut segment)
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "archive failed authentication: wrong key or corrupted data",
+                )
+            })?
+            .len();
+        segment.truncate(len);
+
+        self.counter += 1;
+        self.plaintext = segment;
+        self.pos = 0;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plaintext.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.next_segment()?;
+        }
+
+        let n = buf.len().min(self.plaintext.len() - self.pos);
+        buf[..n].copy_from_slice(&self.plaintext[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,222 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{Client, StatusCode};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info};
+
+use crate::bandwidth::{self, RateLimiter};
+use crate::storage::{ObjectInfo, StorageBackend};
+use crate::streaming::{ChunkReceiver, ChunkSender};
+
+/// Bytes escaped in each segment of an object URL: everything but the
+/// unreserved characters of RFC 3986, so keys with `+`, spaces or `%`
+/// reach the server as written
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Read-only cache store served over plain HTTP(S), e.g. a CDN or nginx
+/// serving a synced copy of the bucket.
+///
+/// Objects are fetched from `<base_url>/<key>` without any AWS signing.
+/// Proxies are picked up from the usual `HTTPS_PROXY`/`HTTP_PROXY` variables.
+#[derive(Clone)]
+pub struct HttpStorage {
+    client: Client,
+    base_url: String,
+    /// Caps the combined rate of all downloads, shared with the other backends
+    download_limit: Option<Arc<RateLimiter>>,
+}
+
+impl HttpStorage {
+    pub fn new(base_url: &str) -> Result<Self> {
+        let client = Client::builder()
+            .user_agent(concat!("mise-s3-cache/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .with_context(|| "Failed to create HTTP client")?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            download_limit: None,
+        })
+    }
+
+    /// Throttle downloads with `limiter`, if any
+    pub fn with_download_limit(mut self, limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.download_limit = limiter;
+        self
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        let path = key
+            .trim_start_matches('/')
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("{}/{}", self.base_url, path)
+    }
+
+    fn read_only_error(&self) -> anyhow::Error {
+        anyhow::anyhow!(
+            "HTTP mirror {} is read-only; store and cleanup require an S3 bucket",
+            self.base_url
+        )
+    }
+
+    async fn fetch(&self, key: &str) -> Result<reqwest::Response> {
+        let url = self.object_url(key);
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to download {}", url))?;
+
+        response
+            .error_for_status()
+            .with_context(|| format!("Failed to download {}", url))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for HttpStorage {
+    fn location(&self) -> String {
+        format!("{} (read-only)", self.base_url)
+    }
+
+    async fn test_connectivity(&self) -> Result<()> {
+        // Any non-5xx answer means the mirror is reachable; directory
+        // listings are usually disabled so a 403/404 is expected here
+        let response = self
+            .client
+            .head(format!("{}/", self.base_url))
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach HTTP mirror: {}", self.base_url))?;
+
+        if response.status().is_server_error() {
+            return Err(anyhow::anyhow!(
+                "HTTP mirror {} returned {}",
+                self.base_url,
+                response.status()
+            ));
+        }
+
+        info!("✅ HTTP mirror connectivity test passed");
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let url = self.object_url(key);
+        let response = self
+            .client
+            .head(&url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to check {}", url))?;
+
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND | StatusCode::GONE => Ok(false),
+            status => Err(anyhow::anyhow!("HTTP error checking {}: {}", url, status)),
+        }
+    }
+
+    async fn get(&self, key: &str, local_path: &Path) -> Result<()> {
+        debug!(
+            "Downloading {} to {}",
+            self.object_url(key),
+            local_path.display()
+        );
+
+        let mut response = self.fetch(key).await?;
+
+        if let Some(parent) = local_path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        let mut file = fs::File::create(local_path)
+            .await
+            .with_context(|| format!("Failed to create file: {}", local_path.display()))?;
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .with_context(|| "Failed to read HTTP response body")?
+        {
+            bandwidth::throttle(self.download_limit.as_deref(), chunk.len() as u64).await;
+            file.write_all(&chunk)
+                .await
+                .with_context(|| format!("Failed to write to file: {}", local_path.display()))?;
+        }
+
+        file.sync_all()
+            .await
+            .with_context(|| "Failed to sync file to disk")?;
+
+        Ok(())
+    }
+
+    async fn get_string(&self, key: &str) -> Result<String> {
+        self.fetch(key)
+            .await?
+            .text()
+            .await
+            .with_context(|| "Failed to read HTTP response body")
+    }
+
+    async fn get_stream(&self, key: &str, chunks: ChunkSender) -> Result<u64> {
+        let mut response = self.fetch(key).await?;
+
+        let mut size = 0u64;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .with_context(|| "Failed to read HTTP response body")?
+        {
+            bandwidth::throttle(self.download_limit.as_deref(), chunk.len() as u64).await;
+            size += chunk.len() as u64;
+            chunks
+                .send(Ok(chunk))
+                .await
+                .map_err(|_| anyhow::anyhow!("Download of {} was abandoned", key))?;
+        }
+
+        Ok(size)
+    }
+
+    async fn put(&self, _local_path: &Path, _key: &str) -> Result<()> {
+        Err(self.read_only_error())
+    }
+
+    async fn put_string(&self, _content: &str, _key: &str) -> Result<()> {
+        Err(self.read_only_error())
+    }
+
+    async fn put_stream(&self, _key: &str, _chunks: ChunkReceiver) -> Result<u64> {
+        Err(self.read_only_error())
+    }
+
+    async fn list(&self, _prefix: &str) -> Result<Vec<ObjectInfo>> {
+        Err(anyhow::anyhow!(
+            "HTTP mirror {} does not support listing objects",
+            self.base_url
+        ))
+    }
+
+    async fn delete(&self, _key: &str) -> Result<()> {
+        Err(self.read_only_error())
+    }
+}
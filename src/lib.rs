@@ -2,8 +2,22 @@
 #![allow(unused_imports)]
 #![allow(clippy::field_reassign_with_default)]
 
+pub mod archive;
+pub mod bandwidth;
 pub mod cache;
+pub mod chunks;
+pub mod compression;
 pub mod config;
+pub mod encryption;
+pub mod http_storage;
+pub mod local_cache;
+pub mod local_storage;
+pub mod manifest;
+pub mod partial_download;
+pub mod relocate;
+pub mod retry;
 pub mod s3_operations;
+pub mod storage;
+pub mod streaming;
 pub mod tool_detection;
 pub mod utils;
@@ -0,0 +1,185 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use filetime::FileTime;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::utils;
+
+/// Size-capped LRU cache of downloaded archives on local disk.
+///
+/// Entries are plain files named after their remote archive key. The file
+/// mtime doubles as the last-used time, so several processes can share the
+/// directory without a separate index.
+#[derive(Clone)]
+pub struct LocalCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl LocalCache {
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_size > 0
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, archive_key: &str) -> PathBuf {
+        self.dir.join(utils::sanitize_path_component(archive_key))
+    }
+
+    /// Return the cached archive for `archive_key` if its size matches.
+    ///
+    /// Entries of another size are evicted. The contents are not hashed here;
+    /// the restore verifies them as they are unpacked and calls `evict` on a
+    /// mismatch.
+    pub async fn lookup(&self, archive_key: &str, expected_size: u64) -> Option<PathBuf> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let path = self.entry_path(archive_key);
+        let metadata = fs::metadata(&path).await.ok().filter(|m| m.is_file())?;
+        if metadata.len() != expected_size {
+            debug!("Evicting stale local cache entry: {}", path.display());
+            let _ = fs::remove_file(&path).await;
+            return None;
+        }
+
+        // Mark as most recently used
+        let _ = filetime::set_file_mtime(&path, FileTime::now());
+        Some(path)
+    }
+
+    /// Remove the cached archive for `archive_key`, if any
+    pub async fn evict(&self, archive_key: &str) {
+        let path = self.entry_path(archive_key);
+        if fs::remove_file(&path).await.is_ok() {
+            debug!("Evicted {} from local cache", path.display());
+        }
+    }
+
+    /// Copy a downloaded archive into the cache and evict old entries
+    pub async fn insert(&self, archive_key: &str, archive_path: &Path) -> Result<()> {
+        let Some(spool_path) = self.spool_path()? else {
+            return Ok(());
+        };
+        if fs::metadata(archive_path).await?.len() > self.max_size {
+            debug!(
+                "Archive {} exceeds local cache limit, not caching",
+                archive_path.display()
+            );
+            return Ok(());
+        }
+
+        fs::copy(archive_path, &spool_path)
+            .await
+            .with_context(|| format!("Failed to copy {}", archive_path.display()))?;
+        self.adopt(archive_key, &spool_path).await?;
+        Ok(())
+    }
+
+    /// Reserve a temporary file in the cache dir that an archive can be
+    /// written to while it downloads. `None` when the cache is disabled.
+    pub fn spool_path(&self) -> Result<Option<PathBuf>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create local cache dir: {}", self.dir.display()))?;
+        Ok(Some(self.dir.join(format!(".{}.tmp", Uuid::new_v4()))))
+    }
+
+    /// Move a verified archive from `spool_path` into the cache and evict old
+    /// entries. Returns the cached path, or `None` if it was too big to keep.
+    pub async fn adopt(&self, archive_key: &str, spool_path: &Path) -> Result<Option<PathBuf>> {
+        let cache = self.clone();
+        let path = self.entry_path(archive_key);
+        let spool_path = spool_path.to_path_buf();
+
+        tokio::task::spawn_blocking(move || -> Result<Option<PathBuf>> {
+            let size = std::fs::metadata(&spool_path)?.len();
+            if size > cache.max_size {
+                debug!(
+                    "Archive for {} exceeds local cache limit, not caching",
+                    path.display()
+                );
+                std::fs::remove_file(&spool_path)?;
+                return Ok(None);
+            }
+
+            std::fs::rename(&spool_path, &path)
+                .with_context(|| format!("Failed to move {} into place", path.display()))?;
+            filetime::set_file_mtime(&path, FileTime::now())?;
+
+            cache.evict_to(cache.max_size)?;
+            Ok(path.is_file().then_some(path))
+        })
+        .await?
+    }
+
+    /// Total size in bytes of all cached archives
+    pub fn size(&self) -> u64 {
+        self.entries().iter().map(|(_, size, _)| size).sum()
+    }
+
+    /// Delete least recently used entries until the cache fits in `limit` bytes
+    pub fn evict_to(&self, limit: u64) -> Result<()> {
+        let mut entries = self.entries();
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+
+        // Oldest first
+        entries.sort_by_key(|(_, _, mtime)| *mtime);
+
+        for (path, size, _) in entries {
+            if total <= limit {
+                break;
+            }
+
+            match std::fs::remove_file(&path) {
+                Ok(_) => {
+                    debug!("Evicted {} from local cache", path.display());
+                    total = total.saturating_sub(size);
+                }
+                Err(e) => warn!("Failed to evict {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn entries(&self) -> Vec<(PathBuf, u64, FileTime)> {
+        let Ok(read_dir) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        read_dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                if !metadata.is_file() {
+                    return None;
+                }
+                Some((
+                    entry.path(),
+                    metadata.len(),
+                    FileTime::from_last_modification_time(&metadata),
+                ))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,342 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use filetime::FileTime;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::storage::{self, ObjectInfo, StorageBackend};
+use crate::streaming::{ChunkReceiver, ChunkSender};
+
+/// Age after which a temporary file is taken to be abandoned; far longer
+/// than any single write takes
+pub const STALE_TEMP_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Cache store kept in a local directory or network mount (e.g. NFS).
+///
+/// Objects are plain files laid out exactly like their keys, so the tree
+/// under the root mirrors the S3 bucket layout.
+///
+/// Temporary files older than `STALE_TEMP_AGE`, left by writers that
+/// crashed, are removed whenever `list` comes across them.
+#[derive(Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn object_path(&self, key: &str) -> Result<PathBuf> {
+        if key.split('/').any(|part| part == "..") || key.starts_with('/') {
+            return Err(anyhow::anyhow!("Invalid object key: {}", key));
+        }
+        Ok(self.root.join(key))
+    }
+
+    /// Write `data` to a temporary sibling first and rename it into place, so
+    /// readers on other machines never see a partially written object
+    async fn write_atomic(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let path = self.object_path(key)?;
+        let temp_path = Self::temp_path_for(&path);
+
+        let written = async {
+            let mut file = Self::create_temp(&temp_path).await?;
+            file.write_all(&data)
+                .await
+                .with_context(|| format!("Failed to write file: {}", temp_path.display()))?;
+            file.sync_all().await?;
+            Ok(())
+        }
+        .await;
+        Self::persist(&temp_path, &path, written).await
+    }
+
+    /// Create `temp_path` along with its parent directories. Tried twice,
+    /// since `delete` may prune the parent once it is empty in between.
+    async fn create_temp(temp_path: &Path) -> Result<fs::File> {
+        let parent = temp_path.parent().unwrap_or(Path::new(""));
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            match fs::File::create(temp_path).await {
+                Ok(file) => return Ok(file),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && attempts < 2 => continue,
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to create file: {}", temp_path.display()))
+                }
+            }
+        }
+    }
+
+    /// Move `temp_path` into place at `path` once it was `written`, and
+    /// remove it otherwise so failed writes leave nothing behind
+    async fn persist<T>(temp_path: &Path, path: &Path, written: Result<T>) -> Result<T> {
+        let result = match written {
+            Ok(value) => fs::rename(temp_path, path)
+                .await
+                .with_context(|| format!("Failed to move file into place: {}", path.display()))
+                .map(|_| value),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            let _ = fs::remove_file(temp_path).await;
+        }
+        result
+    }
+
+    /// Remove the directories above `path` that are left empty, up to the
+    /// root
+    async fn prune_empty_parents(&self, path: &Path) {
+        let mut dir = path.parent();
+        while let Some(current) = dir {
+            if current == self.root || !current.starts_with(&self.root) {
+                break;
+            }
+            // Fails, and stops the pruning, as soon as a directory has entries
+            if fs::remove_dir(current).await.is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+
+    fn temp_path_for(path: &Path) -> PathBuf {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        path.with_file_name(format!(".{}.{}.tmp", file_name, Uuid::new_v4()))
+    }
+
+    fn collect_objects(
+        &self,
+        dir: &Path,
+        prefix: &str,
+        objects: &mut Vec<ObjectInfo>,
+    ) -> Result<()> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read directory: {}", dir.display()))
+            }
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+
+            if metadata.is_dir() {
+                self.collect_objects(&path, prefix, objects)?;
+                continue;
+            }
+
+            // Skip in-flight writes from `write_atomic`, and remove those
+            // abandoned long enough ago that no writer can still own them
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with('.') && file_name.ends_with(".tmp") {
+                let stale = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.elapsed().ok())
+                    .is_some_and(|age| age > STALE_TEMP_AGE);
+                if stale {
+                    debug!("Removing abandoned temporary file {}", path.display());
+                    let _ = std::fs::remove_file(&path);
+                }
+                continue;
+            }
+
+            let relative = path.strip_prefix(&self.root)?;
+            let key = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            if !key.starts_with(prefix) {
+                continue;
+            }
+
+            let last_modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+
+            objects.push(ObjectInfo {
+                key,
+                size: metadata.len(),
+                last_modified,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    fn location(&self) -> String {
+        format!("file://{}", self.root.display())
+    }
+
+    async fn test_connectivity(&self) -> Result<()> {
+        fs::create_dir_all(&self.root).await.with_context(|| {
+            format!("Failed to create cache directory: {}", self.root.display())
+        })?;
+
+        let test_key = format!("test-{}", Uuid::new_v4());
+        self.write_atomic(&test_key, b"test".to_vec())
+            .await
+            .with_context(|| "Failed to write test file to cache directory")?;
+        let _ = fs::remove_file(self.object_path(&test_key)?).await;
+
+        info!("✅ Local cache directory test passed");
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(fs::try_exists(self.object_path(key)?).await?)
+    }
+
+    async fn refresh(&self, key: &str) -> Result<bool> {
+        let path = self.object_path(key)?;
+        match filetime::set_file_mtime(&path, FileTime::now()) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e)
+                .with_context(|| format!("Failed to refresh cache object: {}", path.display())),
+        }
+    }
+
+    async fn get(&self, key: &str, local_path: &Path) -> Result<()> {
+        let path = self.object_path(key)?;
+        debug!("Copying {} to {}", path.display(), local_path.display());
+
+        if let Some(parent) = local_path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        fs::copy(&path, local_path)
+            .await
+            .with_context(|| format!("Failed to read cache object: {}", path.display()))?;
+        Ok(())
+    }
+
+    async fn get_string(&self, key: &str) -> Result<String> {
+        let path = self.object_path(key)?;
+        fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read cache object: {}", path.display()))
+    }
+
+    async fn get_stream(&self, key: &str, chunks: ChunkSender) -> Result<u64> {
+        let path = self.object_path(key)?;
+        storage::send_file(&path, &chunks)
+            .await
+            .with_context(|| format!("Failed to read cache object: {}", path.display()))
+    }
+
+    async fn put(&self, local_path: &Path, key: &str) -> Result<()> {
+        let path = self.object_path(key)?;
+        debug!("Copying {} to {}", local_path.display(), path.display());
+
+        let temp_path = Self::temp_path_for(&path);
+        let written = async {
+            let mut source = fs::File::open(local_path)
+                .await
+                .with_context(|| format!("Failed to read file: {}", local_path.display()))?;
+            let mut file = Self::create_temp(&temp_path).await?;
+            tokio::io::copy(&mut source, &mut file)
+                .await
+                .with_context(|| format!("Failed to copy file: {}", local_path.display()))?;
+            file.sync_all().await?;
+            Ok(())
+        }
+        .await;
+        Self::persist(&temp_path, &path, written).await
+    }
+
+    async fn put_string(&self, content: &str, key: &str) -> Result<()> {
+        self.write_atomic(key, content.as_bytes().to_vec()).await
+    }
+
+    async fn put_stream(&self, key: &str, mut chunks: ChunkReceiver) -> Result<u64> {
+        let path = self.object_path(key)?;
+        debug!("Streaming to {}", path.display());
+
+        let temp_path = Self::temp_path_for(&path);
+        let written = async {
+            let mut file = Self::create_temp(&temp_path).await?;
+
+            let mut size = 0u64;
+            while let Some(chunk) = chunks.recv().await {
+                let chunk = chunk?;
+                size += chunk.len() as u64;
+                file.write_all(&chunk)
+                    .await
+                    .with_context(|| format!("Failed to write file: {}", temp_path.display()))?;
+            }
+            file.sync_all().await?;
+            Ok::<_, anyhow::Error>(size)
+        }
+        .await;
+        Self::persist(&temp_path, &path, written).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        // Only walk the deepest directory named by the prefix
+        let dir_prefix = match prefix.rfind('/') {
+            Some(idx) => &prefix[..idx],
+            None => "",
+        };
+        let start_dir = self.object_path(dir_prefix)?;
+
+        let storage = self.clone();
+        let prefix = prefix.to_string();
+        tokio::task::spawn_blocking(move || -> Result<Vec<ObjectInfo>> {
+            let mut objects = Vec::new();
+            storage.collect_objects(&start_dir, &prefix, &mut objects)?;
+            Ok(objects)
+        })
+        .await?
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.object_path(key)?;
+        debug!("Deleting {}", path.display());
+
+        match fs::remove_file(&path).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to delete: {}", path.display()))
+            }
+        }
+        self.prune_empty_parents(&path).await;
+        Ok(())
+    }
+}
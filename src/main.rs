@@ -3,19 +3,36 @@
 #![allow(clippy::redundant_pattern_matching)]
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use tracing::{error, info};
 use tracing_subscriber;
 
+mod archive;
+mod bandwidth;
 mod cache;
+mod chunks;
+mod compression;
 mod config;
+mod encryption;
+mod http_storage;
+mod local_cache;
+mod local_storage;
+mod manifest;
+mod partial_download;
+mod relocate;
+mod retry;
 mod s3_operations;
+mod storage;
+mod streaming;
 mod tool_detection;
 mod utils;
 
+use bandwidth::RateLimits;
 use cache::CacheManager;
 use config::Config;
-use s3_operations::S3Client;
+use encryption::EncryptionKey;
+use std::sync::Arc;
+use storage::StorageBackend;
 
 #[derive(Parser)]
 #[command(name = "s3-cache")]
@@ -34,6 +51,32 @@ struct Cli {
     config: Option<String>,
 }
 
+/// Bandwidth caps for a single run, overriding the configured ones
+#[derive(Args, Clone, Default)]
+struct TransferLimits {
+    /// Cap combined upload bandwidth per second, e.g. 2MB (0 for unlimited)
+    #[arg(long, value_parser = parse_rate)]
+    upload_limit: Option<u64>,
+    /// Cap combined download bandwidth per second, e.g. 5MB (0 for unlimited)
+    #[arg(long, value_parser = parse_rate)]
+    download_limit: Option<u64>,
+}
+
+impl TransferLimits {
+    fn apply(&self, config: &mut Config) {
+        if let Some(rate) = self.upload_limit {
+            config.upload_limit = rate;
+        }
+        if let Some(rate) = self.download_limit {
+            config.download_limit = rate;
+        }
+    }
+}
+
+fn parse_rate(value: &str) -> std::result::Result<u64, String> {
+    utils::parse_human_rate(value).ok_or_else(|| format!("invalid rate: {}", value))
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Check if a tool version exists in S3 cache
@@ -67,6 +110,8 @@ enum Commands {
         /// Hook mode - suppress errors and run non-interactively
         #[arg(long)]
         hook_mode: bool,
+        #[command(flatten)]
+        limits: TransferLimits,
     },
     /// Store a tool installation in S3 cache
     Store {
@@ -83,6 +128,8 @@ enum Commands {
         /// Hook mode - suppress errors and run non-interactively
         #[arg(long)]
         hook_mode: bool,
+        #[command(flatten)]
+        limits: TransferLimits,
     },
     /// Show cache statistics
     Stats,
@@ -108,6 +155,8 @@ enum Commands {
         /// CI mode - prioritize cache hits over speed, fail on errors
         #[arg(long)]
         ci_mode: bool,
+        #[command(flatten)]
+        limits: TransferLimits,
     },
     /// Clean old cache entries
     Cleanup {
@@ -118,8 +167,44 @@ enum Commands {
         #[arg(long)]
         temp_only: bool,
     },
+    /// Copy cache entries missing from any configured replica
+    Sync {
+        #[command(flatten)]
+        limits: TransferLimits,
+    },
     /// Test S3 connectivity and permissions
     Test,
+    /// Archive and restore a directory locally and report anything that does
+    /// not survive the round trip
+    SelfTest {
+        /// Directory to check, e.g. an installed tool (default: a generated
+        /// sample with symlinks, hard links and special permissions)
+        path: Option<String>,
+    },
+    /// Compare an installed tool with the manifest of its cache entry and
+    /// report modified, missing and extra files
+    VerifyInstall {
+        /// Tool name
+        tool: String,
+        /// Tool version
+        version: String,
+        /// Installation path (default: where mise installs the tool)
+        #[arg(short, long)]
+        path: Option<String>,
+    },
+}
+
+impl Commands {
+    /// Bandwidth flags of commands that transfer archives
+    fn transfer_limits(&self) -> Option<&TransferLimits> {
+        match self {
+            Commands::Restore { limits, .. }
+            | Commands::Store { limits, .. }
+            | Commands::Warm { limits, .. }
+            | Commands::Sync { limits } => Some(limits),
+            _ => None,
+        }
+    }
 }
 
 #[tokio::main]
@@ -148,7 +233,7 @@ async fn main() -> Result<()> {
         .init();
 
     // Load configuration - in hook mode, exit silently on config errors
-    let config = match Config::load(cli.config.as_deref()) {
+    let mut config = match Config::load(cli.config.as_deref()) {
         Ok(config) => config,
         Err(e) => {
             if hook_mode {
@@ -159,6 +244,16 @@ async fn main() -> Result<()> {
         }
     };
 
+    if let Some(limits) = cli.command.transfer_limits() {
+        limits.apply(&mut config);
+    }
+
+    // The self-test only round-trips archives on this machine, so it must
+    // not depend on reaching the configured storage
+    if let Commands::SelfTest { path } = &cli.command {
+        return handle_self_test(&config, path.as_deref()).await;
+    }
+
     if !config.enabled {
         if !hook_mode {
             info!("S3 cache is disabled");
@@ -166,14 +261,26 @@ async fn main() -> Result<()> {
         std::process::exit(0);
     }
 
-    // Initialize S3 client and cache manager - handle errors gracefully in hook mode
-    let (s3_client, cache_manager) = match (S3Client::new(&config).await, hook_mode) {
-        (Ok(s3_client), _) => {
-            let cache_manager = CacheManager::new(config.clone(), s3_client.clone());
-            (s3_client, cache_manager)
+    // Initialize storage backend and cache manager - handle errors gracefully in hook mode
+    let backends = async {
+        // One pair of limiters for every backend, so the caps hold for the run
+        let limits = RateLimits::from_config(&config);
+        let backend = storage::create_backend(&config, &limits).await?;
+        let fallbacks = storage::create_tiers(&config, &config.fallbacks, &limits).await?;
+        let replicas = storage::create_tiers(&config, &config.replicas, &limits).await?;
+        let encryption_key = EncryptionKey::from_config(&config)?;
+        anyhow::Ok((backend, fallbacks, replicas, encryption_key))
+    };
+    let (backend, cache_manager) = match (backends.await, hook_mode) {
+        (Ok((backend, fallbacks, replicas, encryption_key)), _) => {
+            let cache_manager = CacheManager::new(config.clone(), backend.clone())
+                .with_fallbacks(fallbacks)
+                .with_replicas(replicas)
+                .with_encryption_key(encryption_key);
+            (backend, cache_manager)
         }
         (Err(_e), true) => {
-            // In hook mode, exit silently on S3 connection errors
+            // In hook mode, exit silently on storage connection errors
             std::process::exit(0);
         }
         (Err(e), false) => {
@@ -182,7 +289,7 @@ async fn main() -> Result<()> {
     };
 
     // Execute commands with hook-mode aware error handling
-    let result = execute_command(&cli, &cache_manager, &s3_client).await;
+    let result = execute_command(&cli, &cache_manager, &backend).await;
 
     // Extract hook_mode from the command
     let hook_mode = match &cli.command {
@@ -207,7 +314,7 @@ async fn main() -> Result<()> {
 async fn execute_command(
     cli: &Cli,
     cache_manager: &CacheManager,
-    s3_client: &S3Client,
+    backend: &Arc<dyn StorageBackend>,
 ) -> Result<()> {
     match &cli.command {
         Commands::Check {
@@ -236,6 +343,7 @@ async fn execute_command(
             all,
             selective,
             hook_mode,
+            ..
         } => {
             if *all {
                 handle_restore_all(cache_manager, *selective, *hook_mode).await?;
@@ -263,6 +371,7 @@ async fn execute_command(
             path,
             all,
             hook_mode,
+            ..
         } => {
             if *all {
                 handle_store_all(cache_manager, *hook_mode).await?;
@@ -285,7 +394,7 @@ async fn execute_command(
 
         Commands::Status { quiet } => {
             if !quiet {
-                s3_client.show_status().await;
+                cache_manager.show_status().await;
             }
         }
 
@@ -298,6 +407,7 @@ async fn execute_command(
             background,
             hook_mode,
             ci_mode,
+            ..
         } => {
             // CI mode overrides background mode - always run in foreground
             if *background && !ci_mode {
@@ -332,12 +442,49 @@ async fn execute_command(
             }
         }
 
-        Commands::Test => match s3_client.test_connectivity().await {
+        Commands::Sync { .. } => {
+            cache_manager.sync_replicas().await?;
+        }
+
+        Commands::SelfTest { .. } => {
+            unreachable!("self-test runs before storage is set up")
+        }
+
+        Commands::VerifyInstall {
+            tool,
+            version,
+            path,
+        } => {
+            let report = cache_manager
+                .verify_install(tool, version, path.as_deref())
+                .await?;
+            if report.is_clean() {
+                println!("✅ {tool}@{version} matches its cache entry");
+            } else {
+                for file in &report.modified {
+                    println!("   modified: {} ({})", file.path, file.change);
+                }
+                for path in &report.missing {
+                    println!("   missing:  {}", path);
+                }
+                for path in &report.extra {
+                    println!("   extra:    {}", path);
+                }
+                return Err(anyhow::anyhow!(
+                    "{tool}@{version} differs from its cache entry: {} modified, {} missing, {} extra",
+                    report.modified.len(),
+                    report.missing.len(),
+                    report.extra.len()
+                ));
+            }
+        }
+
+        Commands::Test => match backend.test_connectivity().await {
             Ok(_) => {
-                println!("✅ S3 connectivity test passed");
+                println!("✅ Storage connectivity test passed");
             }
             Err(e) => {
-                error!("❌ S3 connectivity test failed: {}", e);
+                error!("❌ Storage connectivity test failed: {}", e);
                 return Err(e);
             }
         },
@@ -346,6 +493,22 @@ async fn execute_command(
     Ok(())
 }
 
+async fn handle_self_test(config: &Config, path: Option<&str>) -> Result<()> {
+    let differences = CacheManager::self_test(config, path.map(std::path::Path::new)).await?;
+    if differences.is_empty() {
+        println!("✅ Restored tree matches the original");
+        Ok(())
+    } else {
+        for difference in &differences {
+            println!("   {}", difference);
+        }
+        Err(anyhow::anyhow!(
+            "{} differences after the round trip",
+            differences.len()
+        ))
+    }
+}
+
 async fn handle_check_single(
     cache_manager: &CacheManager,
     tool: &str,
@@ -0,0 +1,307 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+use crate::archive::{self, ArchiveOptions, TreeVisitor};
+use crate::relocate::{self, PrefixKind, PrefixReference};
+use crate::utils;
+
+/// One file or symlink of an archived tree. Directories are not listed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the archived directory
+    pub path: String,
+    /// Size in bytes; for a symlink, the length of its target
+    pub size: u64,
+    /// Permission bits of a file, where the platform has them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// SHA-256 of the contents of a file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Target of a symlink
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// How an install was relocated on restore, from the `old` path it was
+/// stored from to the `new` path it was restored at
+#[derive(Debug, Clone, Copy)]
+pub struct Relocation<'a> {
+    pub old: &'a str,
+    pub new: &'a str,
+    pub references: &'a [PrefixReference],
+}
+
+/// A file whose contents, permissions or kind no longer match the manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModifiedFile {
+    pub path: String,
+    /// What differs, e.g. `contents differ`
+    pub change: String,
+}
+
+/// How a tree differs from its manifest
+#[derive(Debug, Default)]
+pub struct ManifestReport {
+    pub modified: Vec<ModifiedFile>,
+    /// Listed in the manifest but not found
+    pub missing: Vec<String>,
+    /// Found but not listed in the manifest
+    pub extra: Vec<String>,
+}
+
+impl ManifestReport {
+    pub fn is_clean(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// List every file and symlink below `root` that an archive made with
+/// `options` holds, in archive order. Each name of a hard-linked file is
+/// listed.
+pub fn build(root: &Path, options: &ArchiveOptions) -> Result<Vec<ManifestEntry>> {
+    let mut builder = ManifestBuilder::default();
+    archive::visit_tree(root, options, &mut builder)?;
+    Ok(builder.finish())
+}
+
+/// Builds a manifest from the entries of an archive as they are stored,
+/// hashing each file as the archive reads it
+#[derive(Default)]
+pub struct ManifestBuilder {
+    entries: Vec<ManifestEntry>,
+    /// Hash of each file stored under more than one name, by its first name
+    hashes: HashMap<String, String>,
+    /// The file being stored, whether it has other names, and its hash so far
+    current: Option<(ManifestEntry, bool, Sha256)>,
+}
+
+impl ManifestBuilder {
+    pub fn finish(self) -> Vec<ManifestEntry> {
+        self.entries
+    }
+}
+
+impl TreeVisitor for ManifestBuilder {
+    fn symlink(&mut self, relative: &Path, target: &Path) -> Result<()> {
+        let target = target.to_string_lossy().to_string();
+        self.entries.push(ManifestEntry {
+            path: relative.to_string_lossy().to_string(),
+            size: target.len() as u64,
+            mode: None,
+            hash: None,
+            target: Some(target),
+        });
+        Ok(())
+    }
+
+    fn hard_link(&mut self, relative: &Path, meta: &Metadata, first: &Path) -> Result<()> {
+        self.entries.push(ManifestEntry {
+            path: relative.to_string_lossy().to_string(),
+            size: meta.len(),
+            mode: archive::permission_bits(meta),
+            hash: self.hashes.get(first.to_string_lossy().as_ref()).cloned(),
+            target: None,
+        });
+        Ok(())
+    }
+
+    fn file_start(&mut self, relative: &Path, meta: &Metadata) {
+        let entry = ManifestEntry {
+            path: relative.to_string_lossy().to_string(),
+            size: 0,
+            mode: archive::permission_bits(meta),
+            hash: None,
+            target: None,
+        };
+        let shared = archive::shared_inode(meta).is_some();
+        self.current = Some((entry, shared, Sha256::new()));
+    }
+
+    fn file_data(&mut self, data: &[u8]) {
+        if let Some((entry, _, hasher)) = &mut self.current {
+            entry.size += data.len() as u64;
+            hasher.update(data);
+        }
+    }
+
+    fn file_end(&mut self) -> Result<()> {
+        if let Some((mut entry, shared, hasher)) = self.current.take() {
+            let hash = format!("{:x}", hasher.finalize());
+            if shared {
+                self.hashes.insert(entry.path.clone(), hash.clone());
+            }
+            entry.hash = Some(hash);
+            self.entries.push(entry);
+        }
+        Ok(())
+    }
+}
+
+/// Compare the tree at `root` with `manifest`, ignoring paths matching the
+/// exclude patterns of `options`. With a `relocation`, entries that refer to
+/// the install path are compared as they were before restoring rewrote them.
+pub fn verify(
+    root: &Path,
+    manifest: &[ManifestEntry],
+    options: &ArchiveOptions,
+    relocation: Option<Relocation>,
+) -> Result<ManifestReport> {
+    let excludes = archive::compile_excludes(&options.exclude)?;
+    let relocated = match relocation {
+        Some(relocation) => relocated_entries(root, relocation.references)?,
+        None => RelocatedEntries::default(),
+    };
+
+    let mut found = BTreeMap::new();
+    for relative in archive::walk(root, &excludes)? {
+        let meta = fs::symlink_metadata(root.join(&relative))?;
+        if meta.is_file() || meta.is_symlink() {
+            found.insert(relative.to_string_lossy().to_string(), (relative, meta));
+        }
+    }
+
+    let mut report = ManifestReport::default();
+    for expected in manifest {
+        let Some((relative, meta)) = found.remove(&expected.path) else {
+            report.missing.push(expected.path.clone());
+            continue;
+        };
+
+        let undo = relocation.zip(relocated.kind(&expected.path, &meta));
+        let actual = describe(&root.join(&relative), &relative, &meta, undo)?;
+        if let Some(change) = describe_change(expected, &actual) {
+            report.modified.push(ModifiedFile {
+                path: expected.path.clone(),
+                change,
+            });
+        }
+    }
+    report.extra = found.into_keys().collect();
+
+    Ok(report)
+}
+
+/// The manifest entry of `path`, found at `relative` below the archived
+/// directory. With `undo`, the contents are first put back the way they
+/// were before a relocation.
+fn describe(
+    path: &Path,
+    relative: &Path,
+    meta: &Metadata,
+    undo: Option<(Relocation, PrefixKind)>,
+) -> Result<ManifestEntry> {
+    let mut entry = ManifestEntry {
+        path: relative.to_string_lossy().to_string(),
+        size: meta.len(),
+        mode: None,
+        hash: None,
+        target: None,
+    };
+
+    if meta.is_symlink() {
+        let target = fs::read_link(path)
+            .with_context(|| format!("Failed to read link {}", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        let target = match undo {
+            Some((relocation, kind)) => String::from_utf8_lossy(&relocate::original(
+                target.as_bytes(),
+                kind,
+                relocation.old,
+                relocation.new,
+            ))
+            .to_string(),
+            None => target,
+        };
+        entry.size = target.len() as u64;
+        entry.target = Some(target);
+        return Ok(entry);
+    }
+
+    entry.mode = archive::permission_bits(meta);
+    entry.hash = match undo {
+        Some((relocation, kind)) => {
+            let data =
+                fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let data = relocate::original(&data, kind, relocation.old, relocation.new);
+            entry.size = data.len() as u64;
+            Some(format!("{:x}", Sha256::digest(&data)))
+        }
+        None => Some(
+            utils::calculate_file_hash(path)
+                .with_context(|| format!("Failed to hash {}", path.display()))?,
+        ),
+    };
+    Ok(entry)
+}
+
+/// How `actual` differs from the `expected` entry, if it does
+fn describe_change(expected: &ManifestEntry, actual: &ManifestEntry) -> Option<String> {
+    match (&expected.target, &actual.target) {
+        (Some(_), None) => return Some("no longer a symlink".to_string()),
+        (None, Some(_)) => return Some("now a symlink".to_string()),
+        (Some(want), Some(got)) if want != got => {
+            return Some(format!("points to {} instead of {}", got, want));
+        }
+        _ => {}
+    }
+
+    let mut changes = Vec::new();
+    if expected.hash != actual.hash {
+        changes.push("contents differ".to_string());
+    }
+    if expected.mode != actual.mode {
+        changes.push(format!(
+            "mode {:o} instead of {:o}",
+            actual.mode.unwrap_or_default(),
+            expected.mode.unwrap_or_default()
+        ));
+    }
+    (!changes.is_empty()).then(|| changes.join(", "))
+}
+
+/// The entries a relocation rewrote, by path and, since rewriting a file
+/// rewrites every name of it, by inode
+#[derive(Default)]
+struct RelocatedEntries {
+    paths: HashMap<String, PrefixKind>,
+    inodes: HashMap<(u64, u64), PrefixKind>,
+}
+
+impl RelocatedEntries {
+    fn kind(&self, path: &str, meta: &Metadata) -> Option<PrefixKind> {
+        self.paths.get(path).copied().or_else(|| {
+            archive::shared_inode(meta)
+                .filter(|_| meta.is_file())
+                .and_then(|inode| self.inodes.get(&inode).copied())
+        })
+    }
+}
+
+fn relocated_entries(root: &Path, references: &[PrefixReference]) -> Result<RelocatedEntries> {
+    let mut relocated = RelocatedEntries::default();
+    for reference in references {
+        relocated
+            .paths
+            .insert(reference.path.clone(), reference.kind);
+
+        // References come from metadata, so are only followed within `root`
+        let Ok(path) = archive::safe_path(root, &PathBuf::from(&reference.path)) else {
+            continue;
+        };
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if let Some(inode) = archive::shared_inode(&meta).filter(|_| meta.is_file()) {
+            relocated.inodes.insert(inode, reference.kind);
+        }
+    }
+    Ok(relocated)
+}
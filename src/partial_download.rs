@@ -0,0 +1,321 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs;
+use tracing::debug;
+
+use crate::utils;
+
+/// Partial downloads nothing has written to for this long are taken to be
+/// abandoned
+pub const ABANDONED_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Which chunks of an object have been downloaded so far
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ProgressRecord {
+    etag: String,
+    size: u64,
+    chunk_size: u64,
+    completed: BTreeSet<u64>,
+}
+
+/// An object download split into fixed-size chunks that survives restarts.
+///
+/// Chunk data is written in place into `<key>.part` and every finished chunk
+/// is recorded in `<key>.progress.json`. The record is tied to the object's
+/// ETag, so progress for an object that has since been replaced is thrown
+/// away rather than mixed with the new bytes.
+pub struct PartialDownload {
+    data_path: PathBuf,
+    record_path: PathBuf,
+    record: ProgressRecord,
+    file: PartFile,
+}
+
+/// Shared handle to the partial data file for positional reads and writes,
+/// so concurrent chunk downloads never reopen or seek the file
+#[derive(Clone)]
+pub struct PartFile {
+    file: Arc<std::fs::File>,
+}
+
+impl PartFile {
+    /// Write `data` at `offset`
+    pub async fn write_at(&self, offset: u64, data: Bytes) -> Result<()> {
+        let file = self.file.clone();
+        tokio::task::spawn_blocking(move || write_all_at(&file, &data, offset))
+            .await?
+            .with_context(|| format!("Failed to write partial download at offset {}", offset))
+    }
+
+    /// Read `length` bytes from `offset`
+    pub async fn read_at(&self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        let file = self.file.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut data = vec![0u8; length as usize];
+            read_exact_at(&file, &mut data, offset).map(|_| data)
+        })
+        .await?
+        .with_context(|| format!("Failed to read partial download at offset {}", offset))
+    }
+
+    async fn sync(&self) -> Result<()> {
+        let file = self.file.clone();
+        tokio::task::spawn_blocking(move || file.sync_data()).await??;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn write_all_at(file: &std::fs::File, data: &[u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, data, offset)
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &std::fs::File, data: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, data, offset)
+}
+
+#[cfg(windows)]
+fn write_all_at(file: &std::fs::File, mut data: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !data.is_empty() {
+        let n = file.seek_write(data, offset)?;
+        data = &data[n..];
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &std::fs::File, mut data: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !data.is_empty() {
+        let n = file.seek_read(data, offset)?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        data = &mut data[n..];
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+impl PartialDownload {
+    /// Resume the download of `key` from `dir`, or start a new one
+    pub async fn open(
+        dir: &Path,
+        key: &str,
+        etag: &str,
+        size: u64,
+        chunk_size: u64,
+    ) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+
+        let name = utils::sanitize_path_component(key);
+        let data_path = dir.join(format!("{}.part", name));
+        let record_path = dir.join(format!("{}.progress.json", name));
+
+        let fresh = ProgressRecord {
+            etag: etag.to_string(),
+            size,
+            chunk_size,
+            completed: BTreeSet::new(),
+        };
+
+        let saved = fs::read_to_string(&record_path)
+            .await
+            .ok()
+            .and_then(|json| serde_json::from_str::<ProgressRecord>(&json).ok());
+
+        let record = match saved {
+            Some(saved)
+                if saved.etag == fresh.etag
+                    && saved.size == fresh.size
+                    && saved.chunk_size == fresh.chunk_size
+                    && fs::try_exists(&data_path).await.unwrap_or(false) =>
+            {
+                saved
+            }
+            saved => {
+                if saved.is_some() {
+                    debug!("Discarding stale partial download of {}", key);
+                }
+                fs::File::create(&data_path)
+                    .await
+                    .with_context(|| format!("Failed to create file: {}", data_path.display()))?
+                    .set_len(size)
+                    .await
+                    .with_context(|| "Failed to set file size")?;
+                fresh
+            }
+        };
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&data_path)
+            .with_context(|| format!("Failed to open {}", data_path.display()))?;
+
+        let partial = Self {
+            data_path,
+            record_path,
+            record,
+            file: PartFile {
+                file: Arc::new(file),
+            },
+        };
+        partial.save().await?;
+        Ok(partial)
+    }
+
+    pub fn part_file(&self) -> PartFile {
+        self.file.clone()
+    }
+
+    pub fn etag(&self) -> &str {
+        &self.record.etag
+    }
+
+    pub fn chunk_count(&self) -> u64 {
+        self.record.size.div_ceil(self.record.chunk_size)
+    }
+
+    /// Byte offset and length of chunk `index`
+    pub fn chunk_range(&self, index: u64) -> (u64, u64) {
+        let start = index * self.record.chunk_size;
+        let length = self.record.chunk_size.min(self.record.size - start);
+        (start, length)
+    }
+
+    pub fn is_complete(&self, index: u64) -> bool {
+        self.record.completed.contains(&index)
+    }
+
+    pub fn missing_chunks(&self) -> Vec<u64> {
+        (0..self.chunk_count())
+            .filter(|index| !self.is_complete(*index))
+            .collect()
+    }
+
+    pub fn completed_bytes(&self) -> u64 {
+        self.record
+            .completed
+            .iter()
+            .map(|index| self.chunk_range(*index).1)
+            .sum()
+    }
+
+    pub async fn read_chunk(&self, index: u64) -> Result<Vec<u8>> {
+        let (start, length) = self.chunk_range(index);
+        self.file.read_at(start, length).await
+    }
+
+    /// Write chunk `index` in place and record it as done
+    pub async fn write_chunk(&mut self, index: u64, data: Bytes) -> Result<()> {
+        let (start, length) = self.chunk_range(index);
+        if data.len() as u64 != length {
+            return Err(anyhow::anyhow!(
+                "Chunk {} has {} bytes, expected {}",
+                index,
+                data.len(),
+                length
+            ));
+        }
+
+        self.file.write_at(start, data).await?;
+        self.mark_complete(index).await
+    }
+
+    /// Record chunk `index` as done once its bytes went through `part_file`
+    pub async fn mark_complete(&mut self, index: u64) -> Result<()> {
+        // The record must never claim bytes that are not on disk yet
+        self.file.sync().await?;
+
+        self.record.completed.insert(index);
+        self.save().await
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.record.completed.len() as u64 == self.chunk_count()
+    }
+
+    /// Move the completed data to `local_path` and drop the progress record
+    pub async fn finish_into(self, local_path: &Path) -> Result<()> {
+        if let Some(parent) = local_path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        if fs::rename(&self.data_path, local_path).await.is_err() {
+            // Different filesystem
+            fs::copy(&self.data_path, local_path)
+                .await
+                .with_context(|| format!("Failed to write {}", local_path.display()))?;
+        }
+        self.remove().await
+    }
+
+    /// Delete the partial data and its progress record
+    pub async fn remove(self) -> Result<()> {
+        for path in [&self.data_path, &self.record_path] {
+            match fs::remove_file(path).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn save(&self) -> Result<()> {
+        let temp_path = self.record_path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec(&self.record)?)
+            .await
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        fs::rename(&temp_path, &self.record_path)
+            .await
+            .with_context(|| format!("Failed to write {}", self.record_path.display()))
+    }
+}
+
+/// Delete the partial downloads in `dir` untouched for `ABANDONED_AGE`,
+/// leaving ones that may still be in progress. Returns the files removed.
+pub async fn remove_abandoned(dir: &Path) -> Result<usize> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+    };
+
+    let mut removed = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        let abandoned = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > ABANDONED_AGE);
+        if metadata.is_file() && abandoned {
+            let path = entry.path();
+            fs::remove_file(&path)
+                .await
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            debug!("Removed abandoned partial download {}", path.display());
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
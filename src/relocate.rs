@@ -0,0 +1,424 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use filetime::FileTime;
+use memchr::memmem;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, Metadata};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::archive::{self, ArchiveOptions, TreeVisitor};
+
+/// Bytes read at a time when searching a file for the install path
+pub const SEARCH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How an entry refers to the install path it was stored from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrefixKind {
+    /// A file without NUL bytes; every occurrence is rewritten
+    Text,
+    /// A file whose occurrences all start NUL-terminated strings, which are
+    /// rewritten in place and padded with NULs. Only possible when the new
+    /// path is no longer than the old one.
+    Binary,
+    /// A symlink whose target contains the path
+    Symlink,
+}
+
+/// One entry below an install directory that refers to that directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixReference {
+    /// Path relative to the install directory
+    pub path: String,
+    pub kind: PrefixKind,
+}
+
+#[derive(Debug, Default)]
+pub struct PrefixScan {
+    pub references: Vec<PrefixReference>,
+    /// Binary files that contain the path somewhere other than at the start
+    /// of a NUL-terminated string, so cannot safely be rewritten
+    pub unrelocatable: Vec<String>,
+}
+
+/// Find every file and symlink below `root` that contains `prefix`, usually
+/// `root` itself, among those an archive made with `options` holds. Of
+/// several hard links to one file only the first is listed, since rewriting
+/// it rewrites them all.
+pub fn scan(root: &Path, prefix: &str, options: &ArchiveOptions) -> Result<PrefixScan> {
+    let mut scanner = PrefixScanner::new(prefix);
+    archive::visit_tree(root, options, &mut scanner)?;
+    Ok(scanner.finish())
+}
+
+/// Builds a `PrefixScan` from the entries of an archive as they are stored,
+/// reading each file once
+pub struct PrefixScanner {
+    prefix: String,
+    /// `None` for prefixes too short to search for
+    finder: Option<memmem::Finder<'static>>,
+    scan: PrefixScan,
+    current: Option<(String, ContentSearch)>,
+}
+
+impl PrefixScanner {
+    pub fn new(prefix: &str) -> Self {
+        let finder =
+            (prefix.len() >= 2).then(|| memmem::Finder::new(prefix.as_bytes()).into_owned());
+        Self {
+            prefix: prefix.to_string(),
+            finder,
+            scan: PrefixScan::default(),
+            current: None,
+        }
+    }
+
+    pub fn finish(self) -> PrefixScan {
+        self.scan
+    }
+}
+
+impl TreeVisitor for PrefixScanner {
+    fn symlink(&mut self, relative: &Path, target: &Path) -> Result<()> {
+        if self.finder.is_some() && target.to_string_lossy().contains(&self.prefix) {
+            self.scan.references.push(PrefixReference {
+                path: relative.to_string_lossy().to_string(),
+                kind: PrefixKind::Symlink,
+            });
+        }
+        Ok(())
+    }
+
+    fn file_start(&mut self, relative: &Path, _meta: &Metadata) {
+        if self.finder.is_some() {
+            let name = relative.to_string_lossy().to_string();
+            self.current = Some((name, ContentSearch::default()));
+        }
+    }
+
+    fn file_data(&mut self, data: &[u8]) {
+        if let (Some(finder), Some((_, search))) = (&self.finder, &mut self.current) {
+            search.update(data, finder);
+        }
+    }
+
+    fn file_end(&mut self) -> Result<()> {
+        let Some((name, search)) = self.current.take() else {
+            return Ok(());
+        };
+        if !search.found {
+            return Ok(());
+        }
+
+        if !search.nul {
+            self.scan.references.push(PrefixReference {
+                path: name,
+                kind: PrefixKind::Text,
+            });
+        } else if search.c_strings && !search.in_string {
+            self.scan.references.push(PrefixReference {
+                path: name,
+                kind: PrefixKind::Binary,
+            });
+        } else {
+            self.scan.unrelocatable.push(name);
+        }
+        Ok(())
+    }
+}
+
+/// What `c_string_ranges` and a search for NULs would find in a file, worked
+/// out a buffer at a time as the file streams past
+struct ContentSearch {
+    /// The last bytes seen, enough to find an occurrence split between two
+    /// buffers and to see the byte before it
+    tail: Vec<u8>,
+    /// Offset of the first byte of `tail` in the file
+    tail_offset: u64,
+    /// Bytes before this offset have been checked
+    checked: u64,
+    found: bool,
+    nul: bool,
+    /// Every occurrence so far starts a string of printable bytes after a
+    /// NUL or at the start of the file
+    c_strings: bool,
+    /// Within such a string, whose terminating NUL has not been seen yet
+    in_string: bool,
+}
+
+impl Default for ContentSearch {
+    fn default() -> Self {
+        Self {
+            tail: Vec::new(),
+            tail_offset: 0,
+            checked: 0,
+            found: false,
+            nul: false,
+            c_strings: true,
+            in_string: false,
+        }
+    }
+}
+
+impl ContentSearch {
+    fn update(&mut self, data: &[u8], finder: &memmem::Finder) {
+        if data.is_empty() {
+            return;
+        }
+        self.nul |= memchr::memchr(0, data).is_some();
+        // Only NULs can change how the file is classified now
+        if self.found && !self.c_strings {
+            return;
+        }
+
+        let mut window = std::mem::take(&mut self.tail);
+        window.extend_from_slice(data);
+        let base = self.tail_offset;
+        let end = base + window.len() as u64;
+        let at = |offset: u64| (offset - base) as usize;
+
+        while self.checked < end {
+            if self.in_string {
+                let rest = &window[at(self.checked)..];
+                let length = memchr::memchr(0, rest).unwrap_or(rest.len());
+                if rest[..length].iter().any(|&b| b < 0x20 || b == 0x7f) {
+                    self.c_strings = false;
+                }
+                self.checked += length as u64;
+                if length < rest.len() {
+                    self.in_string = false;
+                    self.checked += 1;
+                }
+                continue;
+            }
+
+            let Some(found) = finder.find(&window[at(self.checked)..]) else {
+                // An occurrence may yet start in the bytes that could still
+                // begin one
+                let overlap = finder.needle().len() as u64 - 1;
+                self.checked = self.checked.max(end.saturating_sub(overlap));
+                break;
+            };
+            let start = self.checked + found as u64;
+            self.found = true;
+            if start > 0 && window[at(start) - 1] != 0 {
+                self.c_strings = false;
+            }
+            self.in_string = true;
+            self.checked = start;
+        }
+
+        // Keep the bytes from the one before the first unchecked byte on
+        let kept = at(self.checked.saturating_sub(1).max(base));
+        self.tail = window.split_off(kept);
+        self.tail_offset = base + kept as u64;
+    }
+}
+
+/// Rewrite `old` to `new` in the `references` found by `scan` below `root`,
+/// keeping permissions and modification times.
+///
+/// Returns the binary files left unchanged because `new` is longer than
+/// `old` or they no longer hold the path where `scan` found it.
+pub fn relocate(
+    root: &Path,
+    references: &[PrefixReference],
+    old: &str,
+    new: &str,
+) -> Result<Vec<String>> {
+    let finder = memmem::Finder::new(old.as_bytes());
+    let mut skipped = Vec::new();
+
+    for reference in references {
+        // References come from metadata, so are only followed within `root`
+        let path = archive::safe_path(root, Path::new(&reference.path))
+            .with_context(|| format!("Refusing to relocate {}", reference.path))?;
+        let meta = fs::symlink_metadata(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let expected = match reference.kind {
+            PrefixKind::Symlink => meta.is_symlink(),
+            PrefixKind::Text | PrefixKind::Binary => meta.is_file(),
+        };
+        if !expected {
+            return Err(anyhow::anyhow!(
+                "{} is not the kind of entry recorded for it",
+                path.display()
+            ));
+        }
+
+        match reference.kind {
+            PrefixKind::Symlink => {
+                let target = fs::read_link(&path)
+                    .with_context(|| format!("Failed to read link {}", path.display()))?;
+                let target = PathBuf::from(target.to_string_lossy().replace(old, new));
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to replace {}", path.display()))?;
+                symlink(&target, &path)?;
+            }
+            // Files that no longer hold the path are not read in whole
+            PrefixKind::Text if !contains(&path, &finder)? => {}
+            PrefixKind::Text => {
+                let data = fs::read(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                rewrite(&path, &replace_all(&data, &finder, new.as_bytes()))?;
+            }
+            PrefixKind::Binary => {
+                if new.len() > old.len() || !contains(&path, &finder)? {
+                    skipped.push(reference.path.clone());
+                    continue;
+                }
+                let mut data = fs::read(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let Some(ranges) = c_string_ranges(&data, &finder) else {
+                    skipped.push(reference.path.clone());
+                    continue;
+                };
+                for (start, end) in ranges {
+                    let mut string = replace_all(&data[start..end], &finder, new.as_bytes());
+                    string.resize(end - start, 0);
+                    data[start..end].copy_from_slice(&string);
+                }
+                rewrite(&path, &data)?;
+            }
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// The contents, or symlink target, of an entry of `kind` as they were
+/// before `relocate` rewrote `old` to `new` in them
+pub fn original(data: &[u8], kind: PrefixKind, old: &str, new: &str) -> Vec<u8> {
+    let finder = memmem::Finder::new(new.as_bytes());
+    if kind != PrefixKind::Binary {
+        return replace_all(data, &finder, old.as_bytes());
+    }
+
+    let mut data = data.to_vec();
+    // Binaries are left alone rather than rewritten with a longer path
+    if new.len() > old.len() {
+        return data;
+    }
+    let Some(ranges) = c_string_ranges(&data, &finder) else {
+        return data;
+    };
+    for (start, end) in ranges {
+        // The longer original string takes back the NULs it was padded with
+        let string = replace_all(&data[start..end], &finder, old.as_bytes());
+        if let Some(region) = data.get_mut(start..start + string.len()) {
+            region.copy_from_slice(&string);
+        }
+    }
+    data
+}
+
+/// Whether the file at `path` contains the needle of `finder`. It is read a
+/// buffer at a time, each starting with the end of the last one, so that an
+/// occurrence split between two reads is still found.
+fn contains(path: &Path, finder: &memmem::Finder) -> Result<bool> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let overlap = finder.needle().len().saturating_sub(1);
+    let mut buffer = vec![0u8; SEARCH_BUFFER_SIZE + overlap];
+    let mut filled = 0;
+
+    loop {
+        let n = file
+            .read(&mut buffer[filled..])
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            return Ok(false);
+        }
+        filled += n;
+        if finder.find(&buffer[..filled]).is_some() {
+            return Ok(true);
+        }
+
+        let kept = overlap.min(filled);
+        buffer.copy_within(filled - kept..filled, 0);
+        filled = kept;
+    }
+}
+
+/// Byte ranges of the strings in `data` that contain the prefix, provided
+/// every occurrence starts a NUL-terminated string of printable bytes
+fn c_string_ranges(data: &[u8], finder: &memmem::Finder) -> Option<Vec<(usize, usize)>> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for start in finder.find_iter(data) {
+        // Further occurrences within one string, e.g. in a search path
+        if ranges.last().is_some_and(|(_, end)| start < *end) {
+            continue;
+        }
+        if start > 0 && data[start - 1] != 0 {
+            return None;
+        }
+        let end = start + memchr::memchr(0, &data[start..])?;
+        if data[start..end].iter().any(|&b| b < 0x20 || b == 0x7f) {
+            return None;
+        }
+        ranges.push((start, end));
+    }
+
+    Some(ranges)
+}
+
+fn replace_all(data: &[u8], finder: &memmem::Finder, new: &[u8]) -> Vec<u8> {
+    let mut replaced = Vec::with_capacity(data.len());
+    let mut last = 0;
+    for start in finder.find_iter(data) {
+        replaced.extend_from_slice(&data[last..start]);
+        replaced.extend_from_slice(new);
+        last = start + finder.needle().len();
+    }
+    replaced.extend_from_slice(&data[last..]);
+    replaced
+}
+
+/// Overwrite `path` in place, so hard links share the change, even when it
+/// is read-only, and put back its modification time
+fn rewrite(path: &Path, data: &[u8]) -> Result<()> {
+    let meta = fs::metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    make_writable(path, &meta)?;
+    let written =
+        fs::write(path, data).with_context(|| format!("Failed to rewrite {}", path.display()));
+    fs::set_permissions(path, meta.permissions())
+        .with_context(|| format!("Failed to set permissions of {}", path.display()))?;
+    written?;
+
+    filetime::set_file_mtime(path, FileTime::from_last_modification_time(&meta))
+        .with_context(|| format!("Failed to set times of {}", path.display()))
+}
+
+#[cfg(unix)]
+fn make_writable(path: &Path, meta: &Metadata) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = meta.permissions().mode();
+    if mode & 0o200 == 0 {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode | 0o200))
+            .with_context(|| format!("Failed to set permissions of {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_writable(_path: &Path, _meta: &Metadata) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, path)
+        .with_context(|| format!("Failed to create link {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, path: &Path) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "Cannot relink {} on this platform",
+        path.display()
+    ))
+}
@@ -0,0 +1,335 @@
+#![allow(dead_code)]
+
+use anyhow::Result;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::{debug, warn};
+
+use crate::config::Config;
+
+/// Upper bound for a single backoff delay
+const MAX_DELAY: Duration = Duration::from_secs(20);
+
+/// Successful requests needed before a throttled limit grows again by one
+const RECOVER_AFTER: usize = 8;
+
+/// How an S3 failure should be handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Transient: server errors, timeouts, dropped connections
+    Retryable,
+    /// The service asked us to slow down
+    Throttled,
+    /// Retrying cannot help: access denied, missing bucket, bad request
+    Fatal,
+}
+
+/// An error tagged with its `ErrorClass`
+#[derive(Debug)]
+pub struct Classified {
+    pub class: ErrorClass,
+    pub error: anyhow::Error,
+}
+
+impl Classified {
+    pub fn retryable(error: impl Into<anyhow::Error>) -> Self {
+        Self {
+            class: ErrorClass::Retryable,
+            error: error.into(),
+        }
+    }
+
+    pub fn fatal(error: impl Into<anyhow::Error>) -> Self {
+        Self {
+            class: ErrorClass::Fatal,
+            error: error.into(),
+        }
+    }
+}
+
+/// Classify an S3 SDK error by its error code and HTTP status
+pub fn classify<E>(err: &SdkError<E, HttpResponse>) -> ErrorClass
+where
+    E: ProvideErrorMetadata,
+{
+    match err {
+        SdkError::ConstructionFailure(_) => ErrorClass::Fatal,
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            ErrorClass::Retryable
+        }
+        SdkError::ServiceError(service_err) => {
+            let status = service_err.raw().status().as_u16();
+            classify_response(err.code(), status)
+        }
+        _ => ErrorClass::Retryable,
+    }
+}
+
+/// Classify an error response from its S3 error code and HTTP status
+pub fn classify_response(code: Option<&str>, status: u16) -> ErrorClass {
+    match code {
+        Some(
+            "SlowDown"
+            | "Throttling"
+            | "ThrottlingException"
+            | "RequestLimitExceeded"
+            | "RequestThrottled"
+            | "TooManyRequests"
+            | "TooManyRequestsException",
+        ) => return ErrorClass::Throttled,
+        Some("RequestTimeout" | "RequestTimeoutException" | "InternalError") => {
+            return ErrorClass::Retryable
+        }
+        _ => {}
+    }
+
+    match status {
+        429 | 503 => ErrorClass::Throttled,
+        408 | 500 | 502 | 504 => ErrorClass::Retryable,
+        _ => ErrorClass::Fatal,
+    }
+}
+
+/// Convert SDK results into classified ones inside retried operations
+pub trait ClassifyExt<T> {
+    fn classify(self) -> std::result::Result<T, Classified>;
+}
+
+impl<T, E> ClassifyExt<T> for std::result::Result<T, SdkError<E, HttpResponse>>
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+{
+    fn classify(self) -> std::result::Result<T, Classified> {
+        self.map_err(|err| Classified {
+            class: classify(&err),
+            error: err.into(),
+        })
+    }
+}
+
+/// Concurrency limit that halves when S3 throttles and creeps back up to
+/// its maximum as requests succeed again
+pub struct AdaptiveConcurrency {
+    max: usize,
+    limit: AtomicUsize,
+    semaphore: Semaphore,
+    /// Permits still to be retired after a cut while they were in use
+    excess: AtomicUsize,
+    successes: AtomicUsize,
+    resize: Mutex<()>,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            limit: AtomicUsize::new(max),
+            semaphore: Semaphore::new(max),
+            excess: AtomicUsize::new(0),
+            successes: AtomicUsize::new(0),
+            resize: Mutex::new(()),
+        }
+    }
+
+    /// Current number of requests allowed at once
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::SeqCst)
+    }
+
+    pub async fn acquire(&self) -> AdaptivePermit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("concurrency semaphore is never closed");
+        AdaptivePermit {
+            permit: Some(permit),
+            limiter: self,
+        }
+    }
+
+    pub fn on_throttle(&self) {
+        let _guard = self.resize.lock().unwrap();
+        self.successes.store(0, Ordering::SeqCst);
+
+        let limit = self.limit();
+        let reduced = (limit / 2).max(1);
+        if reduced == limit {
+            return;
+        }
+
+        self.limit.store(reduced, Ordering::SeqCst);
+        let retire = limit - reduced;
+        let forgotten = self.semaphore.forget_permits(retire);
+        self.excess.fetch_add(retire - forgotten, Ordering::SeqCst);
+        warn!(
+            "S3 is throttling requests, reducing concurrency to {}",
+            reduced
+        );
+    }
+
+    pub fn on_success(&self) {
+        if self.limit() >= self.max
+            || self.successes.fetch_add(1, Ordering::SeqCst) + 1 < RECOVER_AFTER
+        {
+            return;
+        }
+
+        let _guard = self.resize.lock().unwrap();
+        self.successes.store(0, Ordering::SeqCst);
+        let limit = self.limit();
+        if limit >= self.max {
+            return;
+        }
+
+        self.limit.store(limit + 1, Ordering::SeqCst);
+        if self
+            .excess
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_err()
+        {
+            self.semaphore.add_permits(1);
+        }
+        debug!("Raised S3 concurrency to {}", limit + 1);
+    }
+}
+
+/// A slot held for one request; retired on release if the limit was cut
+pub struct AdaptivePermit<'a> {
+    permit: Option<SemaphorePermit<'a>>,
+    limiter: &'a AdaptiveConcurrency,
+}
+
+impl Drop for AdaptivePermit<'_> {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+
+        let retired = self
+            .limiter
+            .excess
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if retired {
+            permit.forget();
+        }
+    }
+}
+
+/// Retry policy for S3 requests, configured from `Config`
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_attempts: config.retry_max_attempts.max(1),
+            base_delay: Duration::from_millis(config.retry_base_delay_ms),
+            jitter: config.retry_jitter,
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 1): exponential
+    /// backoff, capped, with full jitter when enabled
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_DELAY);
+
+        if self.jitter {
+            exponential.mul_f64(fastrand::f64())
+        } else {
+            exponential
+        }
+    }
+
+    /// Run `operation` until it succeeds, fails fatally or runs out of attempts
+    pub async fn run<T, F, Fut>(&self, what: &str, operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, Classified>>,
+    {
+        self.run_inner(what, None, operation).await
+    }
+
+    /// Like `run`, but each attempt holds a slot of `limiter`, which is cut
+    /// back whenever S3 throttles
+    pub async fn run_limited<T, F, Fut>(
+        &self,
+        what: &str,
+        limiter: &AdaptiveConcurrency,
+        operation: F,
+    ) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, Classified>>,
+    {
+        self.run_inner(what, Some(limiter), operation).await
+    }
+
+    async fn run_inner<T, F, Fut>(
+        &self,
+        what: &str,
+        limiter: Option<&AdaptiveConcurrency>,
+        mut operation: F,
+    ) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, Classified>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = match limiter {
+                Some(limiter) => {
+                    let _permit = limiter.acquire().await;
+                    operation().await
+                }
+                None => operation().await,
+            };
+
+            let failure = match result {
+                Ok(value) => {
+                    if let Some(limiter) = limiter {
+                        limiter.on_success();
+                    }
+                    return Ok(value);
+                }
+                Err(failure) => failure,
+            };
+
+            if failure.class == ErrorClass::Throttled {
+                if let Some(limiter) = limiter {
+                    limiter.on_throttle();
+                }
+            }
+
+            if failure.class == ErrorClass::Fatal || attempt >= self.max_attempts {
+                return Err(failure.error);
+            }
+
+            let delay = self.delay(attempt);
+            warn!(
+                "{} failed (attempt {}/{}): {:#}. Retrying in {}ms",
+                what,
+                attempt,
+                self.max_attempts,
+                failure.error,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
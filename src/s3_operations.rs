@@ -1,101 +1,324 @@
 #![allow(dead_code)]
 
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use aws_config::meta::region::RegionProviderChain;
-use aws_sdk_s3::{primitives::ByteStream, Client};
-use std::path::Path;
+use aws_config::retry::RetryConfig;
+use aws_config::sts::AssumeRoleProvider;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::copy_object::builders::CopyObjectFluentBuilder;
+use aws_sdk_s3::operation::create_multipart_upload::builders::CreateMultipartUploadFluentBuilder;
+use aws_sdk_s3::operation::head_object::HeadObjectError;
+use aws_sdk_s3::operation::put_object::builders::PutObjectFluentBuilder;
+use aws_sdk_s3::primitives::{ByteStream, Length};
+use aws_sdk_s3::types::{
+    CompletedMultipartUpload, CompletedPart, MetadataDirective, ServerSideEncryption, StorageClass,
+};
+use aws_sdk_s3::Client;
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, FuturesUnordered, StreamExt, TryStreamExt};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
-use tracing::{debug, error, info};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
 use uuid::Uuid;
-use futures::future::try_join_all;
-use futures::TryStreamExt;
-use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 
+use crate::bandwidth::{self, RateLimiter, RateLimits};
 use crate::config::Config;
-// use crate::utils;
+use crate::partial_download::{PartFile, PartialDownload};
+use crate::retry::{AdaptiveConcurrency, Classified, ClassifyExt, RetryPolicy};
+use crate::storage::{ObjectInfo, StorageBackend};
+use crate::streaming::{ChunkReceiver, ChunkSender, OrderedTasks, CHUNK_SIZE};
+use crate::utils;
+
+/// Bytes escaped in the key of a copy source: everything but the unreserved
+/// characters of RFC 3986 and the `/` separating segments
+const COPY_SOURCE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
 
 #[derive(Clone)]
 pub struct S3Client {
     client: Client,
     config: Config,
+    retry: RetryPolicy,
+    /// Shared by all part uploads of this client
+    uploads: Arc<AdaptiveConcurrency>,
+    /// Shared by all ranged downloads of this client
+    downloads: Arc<AdaptiveConcurrency>,
+    /// Caps the combined rate of all uploads, shared with the other backends
+    upload_limit: Option<Arc<RateLimiter>>,
+    /// Caps the combined rate of all downloads, shared with the other backends
+    download_limit: Option<Arc<RateLimiter>>,
+    /// Applied to every object this client creates
+    object_settings: ObjectSettings,
+}
+
+/// Encryption, storage class and tags of uploaded objects
+#[derive(Clone, Default)]
+struct ObjectSettings {
+    sse: Option<ServerSideEncryption>,
+    kms_key_id: Option<String>,
+    storage_class: Option<StorageClass>,
+    /// URL-encoded `key=value&...` form expected by the tagging header
+    tagging: Option<String>,
+}
+
+impl ObjectSettings {
+    fn from_config(config: &Config) -> Self {
+        let tagging = (!config.object_tags.is_empty()).then(|| {
+            form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(&config.object_tags)
+                .finish()
+        });
+
+        Self {
+            sse: config
+                .server_side_encryption
+                .as_deref()
+                .map(ServerSideEncryption::from),
+            kms_key_id: config.sse_kms_key_id.clone(),
+            storage_class: config.storage_class.as_deref().map(StorageClass::from),
+            tagging,
+        }
+    }
+
+    fn put_object(&self, request: PutObjectFluentBuilder) -> PutObjectFluentBuilder {
+        request
+            .set_server_side_encryption(self.sse.clone())
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .set_storage_class(self.storage_class.clone())
+            .set_tagging(self.tagging.clone())
+    }
+
+    /// Tags are copied along with the object, so only the rest is set again
+    fn copy_object(&self, request: CopyObjectFluentBuilder) -> CopyObjectFluentBuilder {
+        request
+            .set_server_side_encryption(self.sse.clone())
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .set_storage_class(self.storage_class.clone())
+    }
+
+    fn create_multipart_upload(
+        &self,
+        request: CreateMultipartUploadFluentBuilder,
+    ) -> CreateMultipartUploadFluentBuilder {
+        request
+            .set_server_side_encryption(self.sse.clone())
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .set_storage_class(self.storage_class.clone())
+            .set_tagging(self.tagging.clone())
+    }
+}
+
+/// Where the bytes of one multipart upload part come from
+enum PartBody {
+    Bytes(Bytes),
+    File {
+        path: PathBuf,
+        offset: u64,
+        length: u64,
+    },
+}
+
+impl PartBody {
+    fn len(&self) -> u64 {
+        match self {
+            PartBody::Bytes(bytes) => bytes.len() as u64,
+            PartBody::File { length, .. } => *length,
+        }
+    }
+
+    /// A fresh request body; every retry needs its own
+    async fn to_stream(&self, limit: Option<&Arc<RateLimiter>>) -> Result<ByteStream> {
+        if let Some(limit) = limit {
+            return Ok(match self {
+                PartBody::Bytes(bytes) => bandwidth::throttled_bytes(bytes.clone(), limit.clone()),
+                PartBody::File {
+                    path,
+                    offset,
+                    length,
+                } => bandwidth::throttled_file(path.clone(), *offset, *length, limit.clone()),
+            });
+        }
+
+        match self {
+            PartBody::Bytes(bytes) => Ok(ByteStream::from(bytes.clone())),
+            PartBody::File {
+                path,
+                offset,
+                length,
+            } => ByteStream::read_from()
+                .path(path)
+                .offset(*offset)
+                .length(Length::Exact(*length))
+                .build()
+                .await
+                .with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
 }
 
 impl S3Client {
-    pub async fn new(config: &Config) -> Result<Self> {
+    pub async fn new(config: &Config, limits: &RateLimits) -> Result<Self> {
         let region = aws_config::Region::new(config.region.clone());
         let region_provider = RegionProviderChain::default_provider().or_else(region);
+        Self::connect(config, region_provider, limits).await
+    }
 
-        let mut aws_config_builder =
-            aws_config::defaults(aws_config::BehaviorVersion::latest()).region(region_provider);
+    /// Create a client pinned to `region`, ignoring the AWS environment's region
+    pub async fn new_in_region(config: &Config, region: &str, limits: &RateLimits) -> Result<Self> {
+        let region = aws_config::Region::new(region.to_string());
+        Self::connect(config, RegionProviderChain::first_try(region), limits).await
+    }
 
-        // Check for custom endpoint (for MinIO or other S3-compatible services)
-        if let Ok(endpoint_url) = std::env::var("AWS_ENDPOINT_URL") {
+    async fn connect(
+        config: &Config,
+        region_provider: RegionProviderChain,
+        limits: &RateLimits,
+    ) -> Result<Self> {
+        // Retries are handled by `RetryPolicy` so they follow the config and
+        // can tell throttling apart; the SDK's own would multiply with them
+        let mut aws_config_builder = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(region_provider)
+            .retry_config(RetryConfig::disabled());
+
+        if let Some(profile) = &config.profile {
+            debug!("Using AWS profile: {}", profile);
+            aws_config_builder = aws_config_builder.profile_name(profile);
+        }
+
+        // Custom endpoint for MinIO or other S3-compatible services; the
+        // AWS_ENDPOINT_URL env var is still honoured when none is configured
+        let endpoint_url = config
+            .endpoint_url
+            .clone()
+            .or_else(|| std::env::var("AWS_ENDPOINT_URL").ok());
+        if let Some(endpoint_url) = endpoint_url {
             debug!("Using custom S3 endpoint: {}", endpoint_url);
             aws_config_builder = aws_config_builder.endpoint_url(endpoint_url);
         }
 
         let aws_config = aws_config_builder.load().await;
-        let client = Client::new(&aws_config);
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&aws_config)
+            .force_path_style(config.force_path_style);
+
+        if let Some(role_arn) = &config.role_arn {
+            debug!("Assuming role: {}", role_arn);
+            let provider = AssumeRoleProvider::builder(role_arn)
+                .session_name("mise-s3-cache")
+                .configure(&aws_config)
+                .build()
+                .await;
+            s3_config = s3_config.credentials_provider(provider);
+        }
+
+        let client = Client::from_conf(s3_config.build());
 
         Ok(Self {
             client,
             config: config.clone(),
+            retry: RetryPolicy::from_config(config),
+            uploads: Arc::new(AdaptiveConcurrency::new(config.parallel_uploads)),
+            downloads: Arc::new(AdaptiveConcurrency::new(config.parallel_downloads)),
+            upload_limit: limits.upload.clone(),
+            download_limit: limits.download.clone(),
+            object_settings: ObjectSettings::from_config(config),
         })
     }
 
     pub async fn test_connectivity(&self) -> Result<()> {
         // Test bucket access by attempting to list objects
-        self.client
-            .list_objects_v2()
-            .bucket(&self.config.bucket)
-            .prefix(&self.config.prefix)
-            .max_keys(1)
-            .send()
+        self.retry
+            .run("List bucket", || async {
+                self.client
+                    .list_objects_v2()
+                    .bucket(&self.config.bucket)
+                    .prefix(&self.config.prefix)
+                    .max_keys(1)
+                    .send()
+                    .await
+                    .classify()
+            })
             .await
             .with_context(|| format!("Failed to access S3 bucket: {}", self.config.bucket))?;
 
         // Test write permissions with a small test file
         let test_key = format!("{}/test-{}", self.config.prefix, Uuid::new_v4());
-        let test_content = "test";
-
-        self.client
-            .put_object()
-            .bucket(&self.config.bucket)
-            .key(&test_key)
-            .body(ByteStream::from_static(test_content.as_bytes()))
-            .send()
+        self.upload_string("test", &test_key)
             .await
             .with_context(|| "Failed to write test object to S3")?;
 
         // Clean up test file
-        let _ = self
-            .client
-            .delete_object()
-            .bucket(&self.config.bucket)
-            .key(&test_key)
-            .send()
-            .await;
+        let _ = self.delete_object(&test_key).await;
 
         info!("✅ S3 connectivity test passed");
         Ok(())
     }
 
     pub async fn object_exists(&self, key: &str) -> Result<bool> {
-        match self
-            .client
-            .head_object()
-            .bucket(&self.config.bucket)
-            .key(key)
-            .send()
+        self.retry
+            .run(&format!("Checking {}", key), || async {
+                match self
+                    .client
+                    .head_object()
+                    .bucket(&self.config.bucket)
+                    .key(key)
+                    .send()
+                    .await
+                {
+                    Ok(_) => Ok(true),
+                    Err(SdkError::ServiceError(err))
+                        if matches!(err.err(), HeadObjectError::NotFound(_)) =>
+                    {
+                        Ok(false)
+                    }
+                    Err(e) => Err(e).classify(),
+                }
+            })
             .await
-        {
-            Ok(_) => Ok(true),
-            Err(aws_sdk_s3::error::SdkError::ServiceError(err)) => match err.err() {
-                aws_sdk_s3::operation::head_object::HeadObjectError::NotFound(_) => Ok(false),
-                _ => Err(anyhow::anyhow!("S3 error: {:?}", err)),
-            },
-            Err(e) => Err(anyhow::anyhow!("S3 error: {}", e)),
+            .with_context(|| format!("Failed to check S3 object: {}", key))
+    }
+
+    /// Copy an object onto itself so its last-modified time becomes now.
+    /// Single copies are limited to 5 GB, far above any chunk.
+    pub async fn refresh_object(&self, s3_key: &str) -> Result<bool> {
+        if !self.object_exists(s3_key).await? {
+            return Ok(false);
         }
+
+        let source = format!(
+            "{}/{}",
+            self.config.bucket,
+            utf8_percent_encode(s3_key, COPY_SOURCE)
+        );
+        self.retry
+            .run(&format!("Refreshing {}", s3_key), || async {
+                // S3 refuses to copy an object onto itself unchanged, so its
+                // metadata is replaced, with the none it was uploaded with
+                let request = self
+                    .client
+                    .copy_object()
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .copy_source(&source)
+                    .metadata_directive(MetadataDirective::Replace);
+                self.object_settings
+                    .copy_object(request)
+                    .send()
+                    .await
+                    .classify()
+            })
+            .await
+            .with_context(|| format!("Failed to refresh S3 object: {}", s3_key))?;
+
+        Ok(true)
     }
 
     pub async fn upload_file(&self, local_path: &Path, s3_key: &str) -> Result<()> {
@@ -107,17 +330,34 @@ impl S3Client {
         );
 
         let file_size = fs::metadata(local_path).await?.len();
-        let body = ByteStream::from_path(local_path)
-            .await
-            .with_context(|| format!("Failed to read file: {}", local_path.display()))?;
-
-        self.client
-            .put_object()
-            .bucket(&self.config.bucket)
-            .key(s3_key)
-            .body(body)
-            .content_length(file_size as i64)
-            .send()
+        if file_size > self.config.multipart_part_size {
+            return self
+                .upload_file_multipart(local_path, s3_key, file_size)
+                .await;
+        }
+
+        let body = PartBody::File {
+            path: local_path.to_path_buf(),
+            offset: 0,
+            length: file_size,
+        };
+        self.retry
+            .run(&format!("Uploading {}", s3_key), || async {
+                let body = body
+                    .to_stream(self.upload_limit.as_ref())
+                    .await
+                    .map_err(Classified::fatal)?;
+
+                self.object_settings
+                    .put_object(self.client.put_object())
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .body(body)
+                    .content_length(file_size as i64)
+                    .send()
+                    .await
+                    .classify()
+            })
             .await
             .with_context(|| format!("Failed to upload {} to S3", s3_key))?;
 
@@ -125,6 +365,262 @@ impl S3Client {
         Ok(())
     }
 
+    async fn upload_file_multipart(
+        &self,
+        local_path: &Path,
+        s3_key: &str,
+        file_size: u64,
+    ) -> Result<()> {
+        let parts = plan_parts(file_size, self.config.multipart_part_size);
+        debug!(
+            "Large file detected ({} bytes), uploading {} parts ({} concurrent)",
+            file_size,
+            parts.len(),
+            self.config.parallel_uploads
+        );
+
+        let upload_id = self.start_multipart_upload(s3_key).await?;
+        let result = self
+            .upload_parts(local_path, s3_key, &upload_id, parts)
+            .await;
+        self.finish_multipart_upload(s3_key, &upload_id, result)
+            .await?;
+
+        debug!("✅ Uploaded {} ({} bytes, multipart)", s3_key, file_size);
+        Ok(())
+    }
+
+    async fn upload_parts(
+        &self,
+        local_path: &Path,
+        s3_key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, u64, u64)>,
+    ) -> Result<Vec<CompletedPart>> {
+        let uploads = parts.into_iter().map(|(part_number, offset, length)| {
+            let body = PartBody::File {
+                path: local_path.to_path_buf(),
+                offset,
+                length,
+            };
+            async move { self.upload_part(s3_key, upload_id, part_number, body).await }
+        });
+
+        let mut completed: Vec<CompletedPart> = stream::iter(uploads)
+            .buffer_unordered(self.config.parallel_uploads.max(1))
+            .try_collect()
+            .await?;
+
+        completed.sort_by_key(|part| part.part_number);
+        Ok(completed)
+    }
+
+    /// Upload an object of unknown length as it is produced.
+    ///
+    /// Chunks are gathered into `multipart_part_size` parts and up to
+    /// `parallel_uploads` parts are in flight at once, so memory use stays
+    /// bounded regardless of the object size. Streams that end before the
+    /// first part fills are sent with a single `PutObject`.
+    pub async fn upload_stream(&self, s3_key: &str, mut chunks: ChunkReceiver) -> Result<u64> {
+        debug!("Streaming upload to s3://{}/{}", self.config.bucket, s3_key);
+
+        let part_size = self.config.multipart_part_size as usize;
+        let mut buffer = BytesMut::with_capacity(part_size);
+        let mut total = 0u64;
+
+        while buffer.len() < part_size {
+            match chunks.recv().await {
+                Some(chunk) => {
+                    let chunk = chunk?;
+                    total += chunk.len() as u64;
+                    buffer.extend_from_slice(&chunk);
+                }
+                None => {
+                    let data = PartBody::Bytes(buffer.freeze());
+                    self.retry
+                        .run(&format!("Uploading {}", s3_key), || async {
+                            let body = data
+                                .to_stream(self.upload_limit.as_ref())
+                                .await
+                                .map_err(Classified::fatal)?;
+                            self.object_settings
+                                .put_object(self.client.put_object())
+                                .bucket(&self.config.bucket)
+                                .key(s3_key)
+                                .content_length(data.len() as i64)
+                                .body(body)
+                                .send()
+                                .await
+                                .classify()
+                        })
+                        .await
+                        .with_context(|| format!("Failed to upload {} to S3", s3_key))?;
+
+                    debug!("✅ Uploaded {} ({} bytes)", s3_key, total);
+                    return Ok(total);
+                }
+            }
+        }
+
+        let upload_id = self.start_multipart_upload(s3_key).await?;
+        let result = self
+            .upload_stream_parts(s3_key, &upload_id, buffer, &mut chunks, &mut total)
+            .await;
+        self.finish_multipart_upload(s3_key, &upload_id, result)
+            .await?;
+
+        debug!("✅ Uploaded {} ({} bytes, streamed)", s3_key, total);
+        Ok(total)
+    }
+
+    async fn upload_stream_parts(
+        &self,
+        s3_key: &str,
+        upload_id: &str,
+        buffer: BytesMut,
+        chunks: &mut ChunkReceiver,
+        total: &mut u64,
+    ) -> Result<Vec<CompletedPart>> {
+        let part_size = self.config.multipart_part_size as usize;
+        let concurrency = self.config.parallel_uploads.max(1);
+
+        upload_parts(
+            chunks,
+            buffer,
+            part_size,
+            concurrency,
+            total,
+            |part_number, part| async move {
+                if part_number as u64 > MAX_MULTIPART_PARTS {
+                    return Err(anyhow::anyhow!(
+                        "{} exceeds {} parts; increase multipart_part_size",
+                        s3_key,
+                        MAX_MULTIPART_PARTS
+                    ));
+                }
+                self.upload_part(s3_key, upload_id, part_number, PartBody::Bytes(part))
+                    .await
+            },
+        )
+        .await
+    }
+
+    async fn start_multipart_upload(&self, s3_key: &str) -> Result<String> {
+        self.retry
+            .run(
+                &format!("Starting multipart upload of {}", s3_key),
+                || async {
+                    self.object_settings
+                        .create_multipart_upload(self.client.create_multipart_upload())
+                        .bucket(&self.config.bucket)
+                        .key(s3_key)
+                        .send()
+                        .await
+                        .classify()
+                },
+            )
+            .await
+            .with_context(|| format!("Failed to start multipart upload for {}", s3_key))?
+            .upload_id
+            .ok_or_else(|| anyhow::anyhow!("S3 returned no upload ID for {}", s3_key))
+    }
+
+    async fn upload_part(
+        &self,
+        s3_key: &str,
+        upload_id: &str,
+        part_number: i32,
+        body: PartBody,
+    ) -> Result<CompletedPart> {
+        let length = body.len();
+        let what = format!("Uploading part {} of {}", part_number, s3_key);
+
+        let response = self
+            .retry
+            .run_limited(&what, &self.uploads, || async {
+                let stream = body
+                    .to_stream(self.upload_limit.as_ref())
+                    .await
+                    .map_err(Classified::fatal)?;
+                self.client
+                    .upload_part()
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .content_length(length as i64)
+                    .body(stream)
+                    .send()
+                    .await
+                    .classify()
+            })
+            .await
+            .with_context(|| format!("Failed to upload part {} of {}", part_number, s3_key))?;
+
+        debug!("✅ Uploaded part {} ({} bytes)", part_number, length);
+        Ok(CompletedPart::builder()
+            .part_number(part_number)
+            .set_e_tag(response.e_tag)
+            .build())
+    }
+
+    /// Complete the upload if every part made it, otherwise abort it
+    async fn finish_multipart_upload(
+        &self,
+        s3_key: &str,
+        upload_id: &str,
+        parts: Result<Vec<CompletedPart>>,
+    ) -> Result<()> {
+        match parts {
+            Ok(completed_parts) => {
+                let completed = CompletedMultipartUpload::builder()
+                    .set_parts(Some(completed_parts))
+                    .build();
+
+                self.retry
+                    .run(&format!("Completing upload of {}", s3_key), || async {
+                        self.client
+                            .complete_multipart_upload()
+                            .bucket(&self.config.bucket)
+                            .key(s3_key)
+                            .upload_id(upload_id)
+                            .multipart_upload(completed.clone())
+                            .send()
+                            .await
+                            .classify()
+                    })
+                    .await
+                    .with_context(|| {
+                        format!("Failed to complete multipart upload for {}", s3_key)
+                    })?;
+                Ok(())
+            }
+            Err(e) => {
+                // Abort so no orphaned parts keep accruing storage charges
+                if let Err(abort_err) = self
+                    .retry
+                    .run(&format!("Aborting upload of {}", s3_key), || async {
+                        self.client
+                            .abort_multipart_upload()
+                            .bucket(&self.config.bucket)
+                            .key(s3_key)
+                            .upload_id(upload_id)
+                            .send()
+                            .await
+                            .classify()
+                    })
+                    .await
+                {
+                    warn!(
+                        "Failed to abort multipart upload {} for {}: {:#}",
+                        upload_id, s3_key, abort_err
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+
     pub async fn download_file(&self, s3_key: &str, local_path: &Path) -> Result<()> {
         debug!(
             "Downloading s3://{}/{} to {}",
@@ -133,87 +629,82 @@ impl S3Client {
             local_path.display()
         );
 
-        // Get file size first
-        let file_size = self.get_object_size(s3_key).await?;
-        
-        // For small files (< 16MB), use simple download
-        const CHUNK_SIZE: u64 = 16 * 1024 * 1024; // 16MB chunks
-        const MIN_SIZE_FOR_CONCURRENT: u64 = CHUNK_SIZE;
-        
-        if file_size < MIN_SIZE_FOR_CONCURRENT {
+        let (file_size, etag) = self.get_object_version(s3_key).await?;
+
+        // Small files (or objects without an ETag to resume against) are
+        // fetched in one request
+        let Some(etag) = etag.filter(|_| file_size >= self.config.download_chunk_size) else {
             return self.download_file_simple(s3_key, local_path).await;
-        }
+        };
 
         debug!(
             "Large file detected ({} bytes), using concurrent chunk download",
             file_size
         );
 
-        // Ensure parent directory exists
-        if let Some(parent) = local_path.parent() {
-            fs::create_dir_all(parent)
-                .await
-                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
-        }
+        let mut partial = self.open_partial_download(s3_key, &etag, file_size).await?;
+        let part = partial.part_file();
 
-        // Create file with correct size
-        let file = fs::File::create(local_path)
-            .await
-            .with_context(|| format!("Failed to create file: {}", local_path.display()))?;
-        
-        file.set_len(file_size)
-            .await
-            .with_context(|| "Failed to set file size")?;
-
-        // Calculate chunks
-        let num_chunks = (file_size + CHUNK_SIZE - 1) / CHUNK_SIZE;
-        let max_concurrent = std::cmp::min(8, num_chunks); // Max 8 concurrent downloads
-        
-        debug!("Downloading {} chunks concurrently (max {})", num_chunks, max_concurrent);
-
-        // Create download tasks
-        let mut tasks = Vec::new();
-        for chunk_idx in 0..num_chunks {
-            let start = chunk_idx * CHUNK_SIZE;
-            let end = std::cmp::min(start + CHUNK_SIZE - 1, file_size - 1);
-            
-            let client = self.client.clone();
-            let bucket = self.config.bucket.clone();
-            let key = s3_key.to_string();
-            let path = local_path.to_path_buf();
-            
-            tasks.push(tokio::spawn(async move {
-                Self::download_chunk(client, &bucket, &key, &path, start, end, chunk_idx).await
-            }));
-            
-            // Limit concurrent downloads
-            if tasks.len() >= max_concurrent as usize {
-                let results: Result<Vec<_>, _> = try_join_all(tasks.drain(..)).await;
-                results.with_context(|| "Failed to join download tasks")?;
+        let ranges: Vec<(u64, u64, u64)> = partial
+            .missing_chunks()
+            .into_iter()
+            .map(|index| {
+                let (start, length) = partial.chunk_range(index);
+                (index, start, length)
+            })
+            .collect();
+        let total_chunks = partial.chunk_count();
+
+        debug!(
+            "Downloading {} of {} chunks concurrently (max {})",
+            ranges.len(),
+            total_chunks,
+            self.config.parallel_downloads
+        );
+
+        // Sliding window: the next range starts as soon as any one finishes,
+        // rather than waiting for the slowest of a batch
+        let etag = etag.as_str();
+        let mut downloads = stream::iter(ranges.into_iter().map(|(index, start, length)| {
+            let part = part.clone();
+            async move {
+                let result = self
+                    .download_range(s3_key, etag, start, length, &part)
+                    .await;
+                (index, result)
+            }
+        }))
+        .buffer_unordered(self.config.parallel_downloads);
+
+        // Keep going past failed chunks so as much as possible is saved for
+        // the next attempt
+        let mut failed = 0;
+        let mut last_error = None;
+        while let Some((index, result)) = downloads.next().await {
+            match result {
+                Ok(()) => partial.mark_complete(index).await?,
+                Err(e) => {
+                    warn!("Failed to download chunk {} of {}: {:#}", index, s3_key, e);
+                    failed += 1;
+                    last_error = Some(e);
+                }
             }
         }
 
-        // Wait for remaining tasks
-        if !tasks.is_empty() {
-            let results: Result<Vec<_>, _> = try_join_all(tasks).await;
-            results.with_context(|| "Failed to join remaining download tasks")?;
+        if let Some(e) = last_error {
+            return Err(e.context(format!(
+                "{} of {} chunks of {} failed; completed chunks are kept for the next attempt",
+                failed, total_chunks, s3_key
+            )));
         }
 
+        partial.finish_into(local_path).await?;
+
         debug!("✅ Downloaded {} using concurrent chunks", local_path.display());
         Ok(())
     }
 
-    // Fallback method for small files or when concurrent download fails
     async fn download_file_simple(&self, s3_key: &str, local_path: &Path) -> Result<()> {
-        let response = self
-            .client
-            .get_object()
-            .bucket(&self.config.bucket)
-            .key(s3_key)
-            .send()
-            .await
-            .with_context(|| format!("Failed to download {} from S3", s3_key))?;
-
         // Ensure parent directory exists
         if let Some(parent) = local_path.parent() {
             fs::create_dir_all(parent)
@@ -221,81 +712,287 @@ impl S3Client {
                 .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
         }
 
-        // Stream the response body to file
-        let mut body = response.body;
-        let mut file = fs::File::create(local_path)
+        self.retry
+            .run(&format!("Downloading {}", s3_key), || async {
+                let response = self
+                    .client
+                    .get_object()
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .send()
+                    .await
+                    .classify()?;
+
+                // Stream the response body to file, starting over on retries
+                let mut body = response.body;
+                let mut file = fs::File::create(local_path)
+                    .await
+                    .map_err(Classified::fatal)?;
+
+                while let Some(chunk) = body.try_next().await.map_err(Classified::retryable)? {
+                    bandwidth::throttle(self.download_limit.as_deref(), chunk.len() as u64).await;
+                    file.write_all(&chunk).await.map_err(Classified::fatal)?;
+                }
+
+                file.sync_all().await.map_err(Classified::fatal)
+            })
+            .await
+            .with_context(|| format!("Failed to download {} from S3", s3_key))
+    }
+
+    /// Send an object's body into `chunks` as it arrives.
+    ///
+    /// Large objects are fetched range by range into memory, up to
+    /// `parallel_downloads` ranges ahead of the one being sent, and handed
+    /// on without touching the disk. Only when the stream fails are ranges
+    /// that finished ahead of the failure written to a resumable partial
+    /// download, and the next attempt replays them from there.
+    pub async fn download_stream(&self, s3_key: &str, chunks: ChunkSender) -> Result<u64> {
+        debug!(
+            "Streaming download of s3://{}/{}",
+            self.config.bucket, s3_key
+        );
+
+        let (file_size, etag) = self.get_object_version(s3_key).await?;
+        let Some(etag) = etag.filter(|_| file_size >= self.config.download_chunk_size) else {
+            return self.download_stream_simple(s3_key, &chunks).await;
+        };
+
+        let mut partial = self.open_partial_download(s3_key, &etag, file_size).await?;
+        let part = partial.part_file();
+
+        let plan: Vec<(u64, u64, u64, bool)> = (0..partial.chunk_count())
+            .map(|index| {
+                let (start, length) = partial.chunk_range(index);
+                (index, start, length, partial.is_complete(index))
+            })
+            .collect();
+
+        // Ranges download concurrently as tasks, which keep going while a
+        // send waits on the consumer, but come out in order
+        let fetches = plan.into_iter().map(|(index, start, length, complete)| {
+            let (client, key, etag, part) =
+                (self.clone(), s3_key.to_string(), etag.clone(), part.clone());
+            async move {
+                let data = if complete {
+                    Bytes::from(part.read_at(start, length).await?)
+                } else {
+                    client.fetch_range(&key, &etag, start, length).await?
+                };
+                Ok::<_, anyhow::Error>((index, start, complete, data))
+            }
+        });
+        let mut fetched = OrderedTasks::new(fetches, self.config.parallel_downloads);
+
+        while let Some(result) = fetched.next().await {
+            let data = match result {
+                Ok((_, _, _, data)) => data,
+                Err(e) => {
+                    // Nothing more can be sent, but the ranges that finished
+                    // ahead of the failed one are kept for the next attempt
+                    fetched.stop();
+                    while let Some(result) = fetched.next().await {
+                        if let Ok((index, start, false, data)) = result {
+                            part.write_at(start, data).await?;
+                            partial.mark_complete(index).await?;
+                        }
+                    }
+                    return Err(e);
+                }
+            };
+
+            // Hand the range on in small pieces so the channel never holds
+            // more than a few MiB
+            let mut offset = 0;
+            while offset < data.len() {
+                let piece = data.slice(offset..(offset + CHUNK_SIZE).min(data.len()));
+                offset += piece.len();
+                if chunks.send(Ok(piece)).await.is_err() {
+                    // The consumer rejected the data itself, so do not replay it
+                    drop(fetched);
+                    partial.remove().await?;
+                    return Err(anyhow::anyhow!("Download of {} was abandoned", s3_key));
+                }
+            }
+        }
+
+        drop(fetched);
+        partial.remove().await?;
+        Ok(file_size)
+    }
+
+    async fn download_stream_simple(&self, s3_key: &str, chunks: &ChunkSender) -> Result<u64> {
+        // Only the request itself can be retried; once bytes have been sent
+        // on, a broken body has to fail the stream
+        let response = self
+            .retry
+            .run(&format!("Downloading {}", s3_key), || async {
+                self.client
+                    .get_object()
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .send()
+                    .await
+                    .classify()
+            })
             .await
-            .with_context(|| format!("Failed to create file: {}", local_path.display()))?;
+            .with_context(|| format!("Failed to download {} from S3", s3_key))?;
 
+        let mut body = response.body;
+        let mut size = 0u64;
         while let Some(chunk) = body
             .try_next()
             .await
             .with_context(|| "Failed to read S3 response body")?
         {
-            file.write_all(&chunk)
+            bandwidth::throttle(self.download_limit.as_deref(), chunk.len() as u64).await;
+            size += chunk.len() as u64;
+            chunks
+                .send(Ok(chunk))
                 .await
-                .with_context(|| format!("Failed to write to file: {}", local_path.display()))?;
+                .map_err(|_| anyhow::anyhow!("Download of {} was abandoned", s3_key))?;
         }
 
-        file.sync_all()
-            .await
-            .with_context(|| "Failed to sync file to disk")?;
+        Ok(size)
+    }
 
-        Ok(())
+    async fn open_partial_download(
+        &self,
+        s3_key: &str,
+        etag: &str,
+        file_size: u64,
+    ) -> Result<PartialDownload> {
+        let partial = PartialDownload::open(
+            &self.config.get_cache_dir().join("partial"),
+            &format!("{}/{}", self.config.bucket, s3_key),
+            etag,
+            file_size,
+            self.config.download_chunk_size,
+        )
+        .await?;
+
+        let resumed = partial.completed_bytes();
+        if resumed > 0 {
+            info!(
+                "⏩ Resuming download of {} ({} of {} already downloaded)",
+                s3_key,
+                utils::human_readable_size(resumed),
+                utils::human_readable_size(file_size)
+            );
+        }
+        Ok(partial)
     }
 
-    // Download a specific byte range of the file
-    async fn download_chunk(
-        client: Client,
-        bucket: &str,
+    /// Download one byte range of a specific object version straight into
+    /// its place in `part`, writing as the body arrives
+    async fn download_range(
+        &self,
         s3_key: &str,
-        local_path: &Path,
+        etag: &str,
         start: u64,
-        end: u64,
-        chunk_idx: u64,
+        length: u64,
+        part: &PartFile,
     ) -> Result<()> {
-        let range = format!("bytes={}-{}", start, end);
-        
-        debug!("Downloading chunk {} ({})", chunk_idx, range);
-        
-        let response = client
-            .get_object()
-            .bucket(bucket)
-            .key(s3_key)
-            .range(&range)
-            .send()
-            .await
-            .with_context(|| format!("Failed to download chunk {} with range {}", chunk_idx, range))?;
+        self.download_range_with(s3_key, etag, start, length, |offset, data| {
+            part.write_at(offset, data)
+        })
+        .await
+    }
 
-        // Read chunk data into memory
-        let mut chunk_data = Vec::new();
-        let mut body = response.body;
-        
-        while let Some(data) = body
-            .try_next()
-            .await
-            .with_context(|| format!("Failed to read chunk {} data", chunk_idx))?
-        {
-            chunk_data.extend_from_slice(&data);
-        }
+    /// Download one byte range of a specific object version into memory
+    async fn fetch_range(
+        &self,
+        s3_key: &str,
+        etag: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<Bytes> {
+        let buffer = std::sync::Mutex::new(BytesMut::with_capacity(length as usize));
+        self.download_range_with(s3_key, etag, start, length, |offset, data| {
+            let mut buffer = buffer.lock().unwrap();
+            // Retries start over from the start of the range
+            buffer.truncate((offset - start) as usize);
+            buffer.extend_from_slice(&data);
+            std::future::ready(Ok(()))
+        })
+        .await?;
+        Ok(buffer.into_inner().unwrap().freeze())
+    }
 
-        // Write chunk to correct position in file
-        let mut file = fs::OpenOptions::new()
-            .write(true)
-            .open(local_path)
-            .await
-            .with_context(|| format!("Failed to open file for chunk {}", chunk_idx))?;
+    /// Download one byte range of a specific object version, handing the
+    /// body to `write` piece by piece with the offset of each piece
+    async fn download_range_with<W, F>(
+        &self,
+        s3_key: &str,
+        etag: &str,
+        start: u64,
+        length: u64,
+        write: W,
+    ) -> Result<()>
+    where
+        W: Fn(u64, Bytes) -> F,
+        F: Future<Output = Result<()>>,
+    {
+        let end = start + length;
+        let range = format!("bytes={}-{}", start, end - 1);
+        let what = format!("Downloading range {} of {}", range, s3_key);
+
+        self.retry
+            .run_limited(&what, &self.downloads, || async {
+                debug!("{}", what);
+
+                // If-Match makes S3 refuse the range if the object changed
+                let response = self
+                    .client
+                    .get_object()
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .range(&range)
+                    .if_match(etag)
+                    .send()
+                    .await
+                    .classify()?;
+
+                // Retries overwrite the range from its start
+                let mut offset = start;
+                let mut buffer = BytesMut::new();
+                let mut body = response.body;
+                while let Some(chunk) = body.try_next().await.map_err(Classified::retryable)? {
+                    if offset + (buffer.len() + chunk.len()) as u64 > end {
+                        return Err(Classified::fatal(anyhow::anyhow!(
+                            "S3 returned more than the {} bytes requested",
+                            length
+                        )));
+                    }
 
-        file.seek(std::io::SeekFrom::Start(start))
-            .await
-            .with_context(|| format!("Failed to seek to position {} for chunk {}", start, chunk_idx))?;
+                    bandwidth::throttle(self.download_limit.as_deref(), chunk.len() as u64).await;
+                    buffer.extend_from_slice(&chunk);
+                    if buffer.len() >= CHUNK_SIZE {
+                        let data = buffer.split().freeze();
+                        let written = data.len() as u64;
+                        write(offset, data).await.map_err(Classified::fatal)?;
+                        offset += written;
+                    }
+                }
 
-        file.write_all(&chunk_data)
-            .await
-            .with_context(|| format!("Failed to write chunk {} data", chunk_idx))?;
+                if !buffer.is_empty() {
+                    let data = buffer.split().freeze();
+                    let written = data.len() as u64;
+                    write(offset, data).await.map_err(Classified::fatal)?;
+                    offset += written;
+                }
 
-        debug!("✅ Completed chunk {} ({} bytes)", chunk_idx, chunk_data.len());
-        Ok(())
+                if offset != end {
+                    return Err(Classified::retryable(anyhow::anyhow!(
+                        "Response ended after {} of {} bytes",
+                        offset - start,
+                        length
+                    )));
+                }
+                Ok(())
+            })
+            .await
+            .with_context(|| format!("Failed to download range {} of {}", range, s3_key))
     }
 
     pub async fn upload_string(&self, content: &str, s3_key: &str) -> Result<()> {
@@ -304,13 +1001,18 @@ impl S3Client {
             self.config.bucket, s3_key
         );
 
-        self.client
-            .put_object()
-            .bucket(&self.config.bucket)
-            .key(s3_key)
-            .body(ByteStream::from(content.as_bytes().to_vec()))
-            .content_length(content.len() as i64)
-            .send()
+        self.retry
+            .run(&format!("Uploading {}", s3_key), || async {
+                self.object_settings
+                    .put_object(self.client.put_object())
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .body(ByteStream::from(content.as_bytes().to_vec()))
+                    .content_length(content.len() as i64)
+                    .send()
+                    .await
+                    .classify()
+            })
             .await
             .with_context(|| format!("Failed to upload string to S3: {}", s3_key))?;
 
@@ -323,48 +1025,57 @@ impl S3Client {
             self.config.bucket, s3_key
         );
 
-        let response = self
-            .client
-            .get_object()
-            .bucket(&self.config.bucket)
-            .key(s3_key)
-            .send()
+        let bytes = self
+            .retry
+            .run(&format!("Downloading {}", s3_key), || async {
+                let response = self
+                    .client
+                    .get_object()
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .send()
+                    .await
+                    .classify()?;
+
+                response.body.collect().await.map_err(Classified::retryable)
+            })
             .await
             .with_context(|| format!("Failed to download string from S3: {}", s3_key))?;
 
-        let bytes = response
-            .body
-            .collect()
-            .await
-            .with_context(|| "Failed to collect response body")?;
-
         String::from_utf8(bytes.to_vec()).with_context(|| "Invalid UTF-8 in S3 object")
     }
 
-    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<String>> {
-        let mut keys = Vec::new();
-        let mut continuation_token = None;
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let mut objects = Vec::new();
+        let mut continuation_token: Option<String> = None;
 
         loop {
-            let mut request = self
-                .client
-                .list_objects_v2()
-                .bucket(&self.config.bucket)
-                .prefix(prefix);
-
-            if let Some(token) = continuation_token {
-                request = request.continuation_token(token);
-            }
-
-            let response = request
-                .send()
+            let response = self
+                .retry
+                .run(&format!("Listing {}", prefix), || async {
+                    self.client
+                        .list_objects_v2()
+                        .bucket(&self.config.bucket)
+                        .prefix(prefix)
+                        .set_continuation_token(continuation_token.clone())
+                        .send()
+                        .await
+                        .classify()
+                })
                 .await
                 .with_context(|| format!("Failed to list S3 objects with prefix: {}", prefix))?;
 
             if let Some(contents) = response.contents {
                 for object in contents {
                     if let Some(key) = object.key {
-                        keys.push(key);
+                        objects.push(ObjectInfo {
+                            key,
+                            size: object.size.unwrap_or(0) as u64,
+                            last_modified: object
+                                .last_modified
+                                .map(|t| t.secs() as u64)
+                                .unwrap_or(0),
+                        });
                     }
                 }
             }
@@ -376,158 +1087,171 @@ impl S3Client {
             }
         }
 
-        Ok(keys)
+        Ok(objects)
     }
 
     pub async fn delete_object(&self, s3_key: &str) -> Result<()> {
         debug!("Deleting s3://{}/{}", self.config.bucket, s3_key);
 
-        self.client
-            .delete_object()
-            .bucket(&self.config.bucket)
-            .key(s3_key)
-            .send()
+        self.retry
+            .run(&format!("Deleting {}", s3_key), || async {
+                self.client
+                    .delete_object()
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .send()
+                    .await
+                    .classify()
+            })
             .await
             .with_context(|| format!("Failed to delete S3 object: {}", s3_key))?;
 
         Ok(())
     }
 
-    pub async fn get_object_size(&self, s3_key: &str) -> Result<u64> {
+    /// Size and ETag of an object
+    pub async fn get_object_version(&self, s3_key: &str) -> Result<(u64, Option<String>)> {
         let response = self
-            .client
-            .head_object()
-            .bucket(&self.config.bucket)
-            .key(s3_key)
-            .send()
+            .retry
+            .run(&format!("Checking {}", s3_key), || async {
+                self.client
+                    .head_object()
+                    .bucket(&self.config.bucket)
+                    .key(s3_key)
+                    .send()
+                    .await
+                    .classify()
+            })
             .await
             .with_context(|| format!("Failed to get object metadata: {}", s3_key))?;
 
-        Ok(response.content_length.unwrap_or(0) as u64)
+        Ok((response.content_length.unwrap_or(0) as u64, response.e_tag))
     }
 
-    pub async fn get_cache_size(&self, prefix: &str) -> Result<u64> {
-        let mut total_size = 0u64;
-        let mut continuation_token = None;
-
-        loop {
-            let mut request = self
-                .client
-                .list_objects_v2()
-                .bucket(&self.config.bucket)
-                .prefix(prefix);
-
-            if let Some(token) = continuation_token {
-                request = request.continuation_token(token);
-            }
+    pub async fn get_object_size(&self, s3_key: &str) -> Result<u64> {
+        Ok(self.get_object_version(s3_key).await?.0)
+    }
+}
 
-            let response = request.send().await.with_context(|| {
-                format!("Failed to list objects for size calculation: {}", prefix)
-            })?;
+/// S3 allows at most this many parts in one multipart upload
+const MAX_MULTIPART_PARTS: u64 = 10_000;
+
+/// Cut the stream from `chunks`, following what is already in `buffer`, into
+/// parts of `part_size` bytes and upload up to `concurrency` of them at once
+/// with `upload`, numbered from 1. Reading goes on while parts are in
+/// flight, until a whole part is buffered and every slot is taken; the
+/// bounded channel then holds back the producer. Adds the bytes read to
+/// `total` and returns the upload results in part order.
+pub async fn upload_parts<T, F, Fut>(
+    chunks: &mut ChunkReceiver,
+    mut buffer: BytesMut,
+    part_size: usize,
+    concurrency: usize,
+    total: &mut u64,
+    mut upload: F,
+) -> Result<Vec<T>>
+where
+    F: FnMut(i32, Bytes) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut in_flight = FuturesUnordered::new();
+    let mut completed = Vec::new();
+    let mut part_number = 0i32;
+    let mut finished = false;
+
+    loop {
+        let ready = buffer.len() >= part_size || (finished && !buffer.is_empty());
+        if ready && in_flight.len() < concurrency {
+            part_number += 1;
+            let part = buffer.split_to(part_size.min(buffer.len())).freeze();
+            let upload = upload(part_number, part);
+            in_flight.push(async move { upload.await.map(|result| (part_number, result)) });
+            continue;
+        }
 
-            if let Some(contents) = response.contents {
-                for object in contents {
-                    total_size += object.size.unwrap_or(0) as u64;
+        tokio::select! {
+            chunk = chunks.recv(), if !finished && buffer.len() < part_size => match chunk {
+                Some(chunk) => {
+                    let chunk = chunk?;
+                    *total += chunk.len() as u64;
+                    buffer.extend_from_slice(&chunk);
                 }
-            }
-
-            if response.is_truncated == Some(true) {
-                continuation_token = response.next_continuation_token;
-            } else {
-                break;
-            }
+                None => finished = true,
+            },
+            Some(part) = in_flight.next(), if !in_flight.is_empty() => completed.push(part?),
+            else => break,
         }
+    }
+
+    completed.sort_by_key(|(part_number, _)| *part_number);
+    Ok(completed.into_iter().map(|(_, result)| result).collect())
+}
 
-        Ok(total_size)
+/// Split a file into `(part_number, offset, length)` multipart ranges.
+///
+/// The part size grows beyond `part_size` when needed to stay within the
+/// S3 limit of 10,000 parts.
+pub fn plan_parts(file_size: u64, part_size: u64) -> Vec<(i32, u64, u64)> {
+    let part_size = part_size
+        .max(file_size.div_ceil(MAX_MULTIPART_PARTS))
+        .max(1);
+
+    (0..file_size.div_ceil(part_size))
+        .map(|idx| {
+            let offset = idx * part_size;
+            let length = part_size.min(file_size - offset);
+            (idx as i32 + 1, offset, length)
+        })
+        .collect()
+}
+
+#[async_trait]
+impl StorageBackend for S3Client {
+    fn location(&self) -> String {
+        format!("s3://{} ({})", self.config.bucket, self.config.region)
     }
 
-    pub async fn cleanup_old_objects(
-        &self,
-        prefix: &str,
-        max_age_seconds: u64,
-    ) -> Result<Vec<String>> {
-        let cutoff_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)?
-            .as_secs()
-            - max_age_seconds;
+    async fn test_connectivity(&self) -> Result<()> {
+        S3Client::test_connectivity(self).await
+    }
 
-        let mut deleted_keys = Vec::new();
-        let mut continuation_token = None;
+    async fn exists(&self, key: &str) -> Result<bool> {
+        self.object_exists(key).await
+    }
 
-        loop {
-            let mut request = self
-                .client
-                .list_objects_v2()
-                .bucket(&self.config.bucket)
-                .prefix(prefix);
-
-            if let Some(token) = continuation_token {
-                request = request.continuation_token(token);
-            }
+    async fn refresh(&self, key: &str) -> Result<bool> {
+        self.refresh_object(key).await
+    }
 
-            let response = request
-                .send()
-                .await
-                .with_context(|| format!("Failed to list objects for cleanup: {}", prefix))?;
+    async fn get(&self, key: &str, local_path: &Path) -> Result<()> {
+        self.download_file(key, local_path).await
+    }
 
-            if let Some(contents) = response.contents {
-                for object in contents {
-                    if let (Some(key), Some(last_modified)) = (object.key, object.last_modified) {
-                        let modified_time = last_modified.secs() as u64;
-
-                        if modified_time < cutoff_time {
-                            info!("Deleting old cache entry: {}", key);
-                            if let Err(e) = self.delete_object(&key).await {
-                                error!("Failed to delete {}: {}", key, e);
-                            } else {
-                                deleted_keys.push(key);
-                            }
-                        }
-                    }
-                }
-            }
+    async fn get_string(&self, key: &str) -> Result<String> {
+        self.download_string(key).await
+    }
 
-            if response.is_truncated == Some(true) {
-                continuation_token = response.next_continuation_token;
-            } else {
-                break;
-            }
-        }
+    async fn put(&self, local_path: &Path, key: &str) -> Result<()> {
+        self.upload_file(local_path, key).await
+    }
 
-        Ok(deleted_keys)
+    async fn put_string(&self, content: &str, key: &str) -> Result<()> {
+        self.upload_string(content, key).await
     }
 
-    pub async fn show_status(&self) {
-        println!("📋 S3 Cache Configuration:");
-        println!("   Region: {}", self.config.region);
-        println!("   Bucket: {}", self.config.bucket);
-        println!("   Prefix: {}", self.config.prefix);
+    async fn put_stream(&self, key: &str, chunks: ChunkReceiver) -> Result<u64> {
+        self.upload_stream(key, chunks).await
+    }
 
-        // Test connectivity
-        match self.test_connectivity().await {
-            Ok(_) => println!("   Status: ✅ Connected"),
-            Err(e) => println!("   Status: ❌ Connection failed: {}", e),
-        }
+    async fn get_stream(&self, key: &str, chunks: ChunkSender) -> Result<u64> {
+        self.download_stream(key, chunks).await
+    }
 
-        // Get cache size
-        let prefix = format!("{}/tools", self.config.prefix);
-        match self.get_cache_size(&prefix).await {
-            Ok(size) => {
-                println!("   Cache size: {}", crate::utils::human_readable_size(size));
-            }
-            Err(e) => {
-                println!("   Cache size: ❌ Failed to calculate: {}", e);
-            }
-        }
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        self.list_objects(prefix).await
+    }
 
-        // Get object count
-        match self.list_objects(&prefix).await {
-            Ok(objects) => {
-                println!("   Cached tools: {}", objects.len());
-            }
-            Err(e) => {
-                println!("   Cached tools: ❌ Failed to list: {}", e);
-            }
-        }
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.delete_object(key).await
     }
 }
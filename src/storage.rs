@@ -0,0 +1,194 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::bandwidth::RateLimits;
+use crate::config::{Config, RemoteCache};
+use crate::http_storage::HttpStorage;
+use crate::local_storage::LocalStorage;
+use crate::s3_operations::S3Client;
+use crate::streaming::{self, ChunkReceiver, ChunkSender};
+
+/// A single object held by a storage backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    /// Last modification time in seconds since Unix epoch
+    pub last_modified: u64,
+}
+
+/// Object store that holds cache entries.
+///
+/// Keys are the `/`-separated paths produced by `Config::get_cache_key`,
+/// e.g. `mise-cache/tools/node/18.17.0/linux-x86_64/metadata.json`.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Human-readable location of the store, used in status output
+    fn location(&self) -> String;
+
+    /// Check that the store is reachable and writable
+    async fn test_connectivity(&self) -> Result<()>;
+
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// Reset the modification time of `key` to now without changing it, so
+    /// cleanup treats a reused object as new. Returns false if there is no
+    /// such object.
+    ///
+    /// The default downloads the object and uploads it again.
+    async fn refresh(&self, key: &str) -> Result<bool> {
+        if !self.exists(key).await? {
+            return Ok(false);
+        }
+        let spool =
+            tempfile::NamedTempFile::new().with_context(|| "Failed to create spool file")?;
+        self.get(key, spool.path()).await?;
+        self.put(spool.path(), key).await?;
+        Ok(true)
+    }
+
+    /// Download an object to a local file
+    async fn get(&self, key: &str, local_path: &Path) -> Result<()>;
+
+    async fn get_string(&self, key: &str) -> Result<String>;
+
+    /// Send an object's bytes into `chunks` in order and return its size.
+    ///
+    /// Stops with an error if the receiver goes away. The default downloads
+    /// to a temporary file with `get` and streams that.
+    async fn get_stream(&self, key: &str, chunks: ChunkSender) -> Result<u64> {
+        let spool =
+            tempfile::NamedTempFile::new().with_context(|| "Failed to create spool file")?;
+        self.get(key, spool.path()).await?;
+        send_file(spool.path(), &chunks).await
+    }
+
+    /// Upload a local file as an object
+    async fn put(&self, local_path: &Path, key: &str) -> Result<()>;
+
+    async fn put_string(&self, content: &str, key: &str) -> Result<()>;
+
+    /// Upload an object of unknown length from a chunk stream and return its
+    /// size in bytes.
+    ///
+    /// Nothing may be committed under `key` if the stream yields an error.
+    /// The default spools to a temporary file and calls `put`.
+    async fn put_stream(&self, key: &str, mut chunks: ChunkReceiver) -> Result<u64> {
+        let spool =
+            tempfile::NamedTempFile::new().with_context(|| "Failed to create spool file")?;
+        let mut file = tokio::fs::File::create(spool.path())
+            .await
+            .with_context(|| format!("Failed to open {}", spool.path().display()))?;
+
+        let mut size = 0u64;
+        while let Some(chunk) = chunks.recv().await {
+            let chunk = chunk?;
+            size += chunk.len() as u64;
+            file.write_all(&chunk)
+                .await
+                .with_context(|| format!("Failed to write {}", spool.path().display()))?;
+        }
+        file.flush().await?;
+        drop(file);
+
+        self.put(spool.path(), key).await?;
+        Ok(size)
+    }
+
+    /// List all objects whose key starts with `prefix`
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>>;
+
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Total size in bytes of all objects under `prefix`
+    async fn size(&self, prefix: &str) -> Result<u64> {
+        let objects = self.list(prefix).await?;
+        Ok(objects.iter().map(|o| o.size).sum())
+    }
+}
+
+/// Stream a local file into `chunks`, returning the number of bytes sent
+pub async fn send_file(path: &Path, chunks: &ChunkSender) -> Result<u64> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    let mut size = 0u64;
+    loop {
+        let mut buffer = Vec::with_capacity(streaming::CHUNK_SIZE);
+        let n = (&mut file)
+            .take(streaming::CHUNK_SIZE as u64)
+            .read_to_end(&mut buffer)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            return Ok(size);
+        }
+
+        size += n as u64;
+        chunks
+            .send(Ok(buffer.into()))
+            .await
+            .map_err(|_| anyhow::anyhow!("Download of {} was abandoned", path.display()))?;
+    }
+}
+
+/// An additional remote cache with the config its keys are built from
+#[derive(Clone)]
+pub struct RemoteTier {
+    pub config: Config,
+    pub backend: Arc<dyn StorageBackend>,
+}
+
+/// Create the storage backend selected by the configured bucket.
+///
+/// A plain bucket name selects S3; `file:///path/to/dir` selects a local
+/// directory or network mount; `https://host/path` selects a read-only
+/// HTTP mirror. Transfers are throttled by the shared `limits`.
+pub async fn create_backend(
+    config: &Config,
+    limits: &RateLimits,
+) -> Result<Arc<dyn StorageBackend>> {
+    match config.bucket.split_once("://") {
+        None => Ok(Arc::new(S3Client::new(config, limits).await?)),
+        Some(("file", path)) => Ok(Arc::new(LocalStorage::new(path))),
+        Some(("http" | "https", _)) => Ok(Arc::new(
+            HttpStorage::new(&config.bucket)?.with_download_limit(limits.download.clone()),
+        )),
+        Some((scheme, _)) => Err(anyhow::anyhow!(
+            "Unsupported storage backend: {}://",
+            scheme
+        )),
+    }
+}
+
+/// Create backends for additional remote caches, in the given order
+pub async fn create_tiers(
+    config: &Config,
+    remotes: &[RemoteCache],
+    limits: &RateLimits,
+) -> Result<Vec<RemoteTier>> {
+    let mut tiers = Vec::new();
+
+    for remote in remotes {
+        let tier_config = config.for_remote(remote);
+        let backend: Arc<dyn StorageBackend> = match &remote.region {
+            // An explicit region must win over AWS_REGION for cross-region buckets
+            Some(region) if !tier_config.bucket.contains("://") => {
+                Arc::new(S3Client::new_in_region(&tier_config, region, limits).await?)
+            }
+            _ => create_backend(&tier_config, limits).await?,
+        };
+        tiers.push(RemoteTier {
+            config: tier_config,
+            backend,
+        });
+    }
+
+    Ok(tiers)
+}
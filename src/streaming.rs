@@ -0,0 +1,285 @@
+#![allow(dead_code)]
+
+use anyhow::Result;
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, Read, Write};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Size of the chunks handed from the archive writer to uploads
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Chunks in flight between the archive writer and each upload
+pub const CHANNEL_DEPTH: usize = 8;
+
+/// Sending half of a chunk stream.
+///
+/// An `Err` item means the producer failed and the upload must be abandoned;
+/// a closed channel without an error marks the end of the data.
+pub type ChunkSender = mpsc::Sender<Result<Bytes>>;
+pub type ChunkReceiver = mpsc::Receiver<Result<Bytes>>;
+
+pub fn chunk_channel() -> (ChunkSender, ChunkReceiver) {
+    mpsc::channel(CHANNEL_DEPTH)
+}
+
+/// Futures run as tasks up to `limit` ahead of the consumer and handed out
+/// in the order they were queued. Running tasks go on while the consumer is
+/// busy, e.g. blocked sending on a full channel, and are aborted when this
+/// is dropped.
+pub struct OrderedTasks<T, I> {
+    queued: I,
+    running: VecDeque<JoinHandle<Result<T>>>,
+    stopped: bool,
+}
+
+impl<T, I, F> OrderedTasks<T, I>
+where
+    T: Send + 'static,
+    I: Iterator<Item = F>,
+    F: Future<Output = Result<T>> + Send + 'static,
+{
+    pub fn new(mut queued: I, limit: usize) -> Self {
+        let running = queued
+            .by_ref()
+            .take(limit.max(1))
+            .map(tokio::spawn)
+            .collect();
+        Self {
+            queued,
+            running,
+            stopped: false,
+        }
+    }
+
+    /// Start no more queued tasks; `next` still hands out the ones already
+    /// running
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+
+    /// The result of the next task in queue order, starting another task in
+    /// its place
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        let task = self.running.pop_front()?;
+        let result = match task.await {
+            Ok(result) => result,
+            Err(e) => Err(e.into()),
+        };
+        if let Some(next) = self.queued.next().filter(|_| !self.stopped) {
+            self.running.push_back(tokio::spawn(next));
+        }
+        Some(result)
+    }
+}
+
+impl<T, I> Drop for OrderedTasks<T, I> {
+    fn drop(&mut self) {
+        for task in &self.running {
+            task.abort();
+        }
+    }
+}
+
+/// Blocking writer that forwards everything written to a chunk stream while
+/// keeping a running SHA-256 and byte count.
+///
+/// Intended to sit at the bottom of a `tar::Builder<GzEncoder<_>>` inside
+/// `spawn_blocking`, so archives never touch the local disk.
+pub struct ChunkWriter {
+    tx: ChunkSender,
+    buffer: Vec<u8>,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl ChunkWriter {
+    pub fn new(tx: ChunkSender) -> Self {
+        Self {
+            tx,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// Send any buffered bytes and return the hex SHA-256 and total size
+    pub fn finish(mut self) -> io::Result<(String, u64)> {
+        self.send_buffer()?;
+        Ok((format!("{:x}", self.hasher.finalize()), self.size))
+    }
+
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = Bytes::from(std::mem::replace(
+            &mut self.buffer,
+            Vec::with_capacity(CHUNK_SIZE),
+        ));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "all uploads were abandoned"))
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.hasher.update(data);
+        self.size += data.len() as u64;
+        self.buffer.extend_from_slice(data);
+
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+/// Copy every chunk from `source` to all `sinks`.
+///
+/// A sink whose receiver went away (its upload failed) is dropped and the
+/// others carry on. Returns once the source is exhausted or no sink is left,
+/// which in turn makes the producer's next send fail.
+pub async fn fan_out(mut source: ChunkReceiver, sinks: Vec<ChunkSender>) {
+    let mut sinks: Vec<Option<ChunkSender>> = sinks.into_iter().map(Some).collect();
+
+    while let Some(item) = source.recv().await {
+        for slot in sinks.iter_mut() {
+            let Some(sink) = slot else {
+                continue;
+            };
+
+            let message = match &item {
+                Ok(chunk) => Ok(chunk.clone()),
+                Err(e) => Err(anyhow::anyhow!("{:#}", e)),
+            };
+            if sink.send(message).await.is_err() {
+                *slot = None;
+            }
+        }
+
+        if sinks.iter().all(Option::is_none) {
+            break;
+        }
+    }
+}
+
+/// Blocking reader over a chunk stream, the receiving counterpart of
+/// `ChunkWriter`. An `Err` item surfaces as an I/O error.
+pub struct ChunkReader {
+    rx: ChunkReceiver,
+    current: Bytes,
+}
+
+impl ChunkReader {
+    pub fn new(rx: ChunkReceiver) -> Self {
+        Self {
+            rx,
+            current: Bytes::new(),
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(Ok(chunk)) => self.current = chunk,
+                Some(Err(e)) => return Err(io::Error::other(format!("{:#}", e))),
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
+    }
+}
+
+/// Reader adapter that keeps a running SHA-256 and byte count of everything
+/// read through it, optionally copying the bytes to a spool file as well.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+    spool: Option<File>,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+            spool: None,
+        }
+    }
+
+    pub fn with_spool(mut self, spool: File) -> Self {
+        self.spool = Some(spool);
+        self
+    }
+
+    /// Return the hex SHA-256 and size of the bytes read so far
+    pub fn finish(self) -> io::Result<(String, u64)> {
+        if let Some(spool) = self.spool {
+            spool.sync_all()?;
+        }
+        Ok((format!("{:x}", self.hasher.finalize()), self.size))
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        if let Some(spool) = &mut self.spool {
+            spool.write_all(&buf[..n])?;
+        }
+        Ok(n)
+    }
+}
+
+/// Writer adapter that keeps a running SHA-256 of everything written
+/// through it
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Return the inner writer and the hex SHA-256 of the bytes written
+    pub fn finish(self) -> (W, String) {
+        (self.inner, format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -3,6 +3,7 @@
 use anyhow::Result;
 use regex::Regex;
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -101,9 +102,9 @@ pub fn sanitize_path_component(input: &str) -> String {
 
 /// Calculate SHA256 hash of a file
 pub fn calculate_file_hash(path: &Path) -> Result<String> {
-    let content = std::fs::read(path)?;
+    let mut file = std::fs::File::open(path)?;
     let mut hasher = Sha256::new();
-    hasher.update(&content);
+    std::io::copy(&mut file, &mut hasher)?;
     Ok(format!("{:x}", hasher.finalize()))
 }
 
@@ -138,6 +139,63 @@ pub fn human_readable_size(bytes: u64) -> String {
     }
 }
 
+/// Format a bandwidth limit in bytes per second, where 0 means no limit
+pub fn format_rate(bytes_per_second: u64) -> String {
+    if bytes_per_second == 0 {
+        "unlimited".to_string()
+    } else {
+        format!("{}/s", human_readable_size(bytes_per_second))
+    }
+}
+
+/// Parse a human-readable size such as `512`, `64KB`, `16M` or `5 GiB` into bytes
+pub fn parse_human_size(input: &str) -> Option<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        "T" | "TB" | "TIB" => 1024 * 1024 * 1024 * 1024,
+        _ => return None,
+    };
+
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Parse a transfer rate such as `2MB`, `512KB/s` or `0` into bytes per second
+pub fn parse_human_rate(input: &str) -> Option<u64> {
+    let input = input.trim();
+    parse_human_size(input.strip_suffix("/s").unwrap_or(input))
+}
+
+/// Parse a comma-separated `key=value` list such as `team=infra,env=ci`
+pub fn parse_key_value_list(input: &str) -> BTreeMap<String, String> {
+    input
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+/// Parse a comma-separated list such as `**/__pycache__,share/doc/**`,
+/// dropping empty items
+pub fn parse_list(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 /// Check if running in a CI environment
 pub fn is_ci_environment() -> bool {
     std::env::var("CI").is_ok()
@@ -208,37 +266,6 @@ pub fn create_temp_file_with_extension(extension: &str) -> Result<tempfile::Name
         .map_err(anyhow::Error::from)
 }
 
-/// Retry a function with exponential backoff
-pub async fn retry_with_backoff<F, Fut, T, E>(
-    mut operation: F,
-    max_attempts: usize,
-    initial_delay_ms: u64,
-) -> Result<T, E>
-where
-    F: FnMut() -> Fut,
-    Fut: std::future::Future<Output = Result<T, E>>,
-    E: std::fmt::Display,
-{
-    let mut delay = initial_delay_ms;
-
-    for attempt in 1..=max_attempts {
-        match operation().await {
-            Ok(result) => return Ok(result),
-            Err(e) => {
-                if attempt == max_attempts {
-                    return Err(e);
-                }
-
-                tracing::warn!("Attempt {} failed: {}. Retrying in {}ms", attempt, e, delay);
-                tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-                delay *= 2; // Exponential backoff
-            }
-        }
-    }
-
-    unreachable!("Should have returned from the loop")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,234 @@
+use mise_s3_cache::archive::{self, ArchiveOptions};
+use std::path::Path;
+use tempfile::TempDir;
+
+fn round_trip(source: &Path, target: &Path, options: &ArchiveOptions) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    archive::append_tree(&mut builder, source, options).unwrap();
+    let stored = builder.into_inner().unwrap();
+
+    archive::unpack_tree(&mut tar::Archive::new(stored.as_slice()), target).unwrap();
+    stored
+}
+
+#[test]
+fn test_sample_tree_survives_round_trip() {
+    let options = ArchiveOptions {
+        xattrs: true,
+        ..Default::default()
+    };
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    archive::create_sample_tree(source.path(), &options).unwrap();
+
+    let stored = round_trip(source.path(), target.path(), &options);
+
+    let differences = archive::compare_trees(source.path(), target.path(), &options).unwrap();
+    assert!(differences.is_empty(), "{:#?}", differences);
+
+    // Links are stored as links, not as copies of what they point to
+    let mut archive = tar::Archive::new(stored.as_slice());
+    let kinds: Vec<_> = archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            (
+                entry.path().unwrap().to_string_lossy().to_string(),
+                entry.header().entry_type(),
+            )
+        })
+        .collect();
+    assert!(kinds.contains(&("bin/env".to_string(), tar::EntryType::Symlink)));
+    assert!(kinds.contains(&("bin/lib".to_string(), tar::EntryType::Symlink)));
+    assert!(kinds.contains(&("bin/tool-alias".to_string(), tar::EntryType::Link)));
+    assert!(!kinds.iter().any(|(path, _)| path.starts_with("bin/lib/")));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_compare_trees_reports_differences() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let options = ArchiveOptions::default();
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    archive::create_sample_tree(source.path(), &options).unwrap();
+    round_trip(source.path(), target.path(), &options);
+
+    let tool = target.path().join("bin/tool");
+    std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o644)).unwrap();
+    std::fs::remove_file(target.path().join("bin/tool-alias")).unwrap();
+    std::fs::copy(&tool, target.path().join("bin/tool-alias")).unwrap();
+    std::fs::write(target.path().join("extra"), "").unwrap();
+
+    let differences = archive::compare_trees(source.path(), target.path(), &options).unwrap();
+    let report = differences.join("\n");
+    assert!(
+        report.contains("bin/tool: mode 644 instead of 755"),
+        "{}",
+        report
+    );
+    assert!(report.contains("bin/tool-alias: hard linked"), "{}", report);
+    assert!(report.contains("extra: unexpected"), "{}", report);
+}
+
+#[test]
+fn test_exclude_patterns_leave_out_matches() {
+    let source = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    for path in [
+        "bin/python3",
+        "lib/python3.11/__pycache__/os.cpython-311.pyc",
+        "lib/python3.11/os.py",
+        "lib/libpython3.11.a",
+        "share/doc/python/README",
+        "share/man/python.1",
+    ] {
+        let path = source.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+
+    let options = ArchiveOptions {
+        exclude: vec![
+            "**/__pycache__".to_string(),
+            "share/doc/**".to_string(),
+            "**/*.a".to_string(),
+        ],
+        ..Default::default()
+    };
+    round_trip(source.path(), target.path(), &options);
+
+    assert!(target.path().join("bin/python3").exists());
+    assert!(target.path().join("lib/python3.11/os.py").exists());
+    assert!(target.path().join("share/man/python.1").exists());
+    assert!(!target.path().join("lib/python3.11/__pycache__").exists());
+    assert!(!target.path().join("lib/libpython3.11.a").exists());
+    assert!(!target.path().join("share/doc").exists());
+
+    // The comparison skips excluded paths on both sides
+    let differences = archive::compare_trees(source.path(), target.path(), &options).unwrap();
+    assert!(differences.is_empty(), "{:#?}", differences);
+
+    assert!(archive::compile_excludes(&["lib/[".to_string()]).is_err());
+}
+
+/// Append an entry with `name` written straight into the header, bypassing
+/// the checks `tar::Builder` applies to paths
+fn append_raw(
+    builder: &mut tar::Builder<Vec<u8>>,
+    entry_type: tar::EntryType,
+    name: &str,
+    link: Option<&str>,
+    data: &[u8],
+) {
+    let mut header = tar::Header::new_gnu();
+    header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+    if let Some(link) = link {
+        header.as_gnu_mut().unwrap().linkname[..link.len()].copy_from_slice(link.as_bytes());
+    }
+    header.set_entry_type(entry_type);
+    header.set_mode(0o644);
+    header.set_size(data.len() as u64);
+    header.set_cksum();
+    builder.append(&header, data).unwrap();
+}
+
+#[test]
+fn test_unpack_refuses_escaping_entries() {
+    use tar::EntryType;
+
+    type Entry<'a> = (EntryType, &'a str, Option<&'a str>);
+    let outside = TempDir::new().unwrap();
+    let outside_dir = outside.path().to_str().unwrap();
+    let cases: Vec<(&str, Vec<Entry>)> = vec![
+        (
+            "absolute path",
+            vec![(EntryType::Regular, "/tmp/mise-s3-cache-escape", None)],
+        ),
+        (
+            "parent directory",
+            vec![(EntryType::Regular, "bin/../../escape", None)],
+        ),
+        (
+            "write through symlink",
+            vec![
+                (EntryType::Symlink, "lib", Some(outside_dir)),
+                (EntryType::Regular, "lib/escape", None),
+            ],
+        ),
+        (
+            "replace earlier entry",
+            vec![
+                (EntryType::Symlink, "bin/tool", Some("/etc/passwd")),
+                (EntryType::Regular, "bin/tool", None),
+            ],
+        ),
+        (
+            "hard link outside",
+            vec![(EntryType::Link, "bin/passwd", Some("../../etc/passwd"))],
+        ),
+        ("device file", vec![(EntryType::Char, "dev/null", None)]),
+    ];
+
+    for (case, entries) in cases {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (entry_type, name, link) in entries {
+            let data: &[u8] = if entry_type.is_file() {
+                b"escaped"
+            } else {
+                b""
+            };
+            append_raw(&mut builder, entry_type, name, link, data);
+        }
+        let stored = builder.into_inner().unwrap();
+
+        let target = TempDir::new().unwrap();
+        let result = archive::unpack_tree(&mut tar::Archive::new(stored.as_slice()), target.path());
+        assert!(result.is_err(), "{} was unpacked", case);
+    }
+
+    assert!(!Path::new("/tmp/mise-s3-cache-escape").exists());
+    assert!(!outside.path().join("escape").exists());
+}
+
+#[test]
+fn test_deterministic_archives_ignore_owners_and_times() {
+    let options = ArchiveOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    archive::create_sample_tree(first.path(), &options).unwrap();
+    archive::create_sample_tree(second.path(), &options).unwrap();
+    filetime::set_file_mtime(
+        second.path().join("bin/tool"),
+        filetime::FileTime::from_unix_time(1_700_000_000, 0),
+    )
+    .unwrap();
+
+    assert_eq!(
+        archive::tree_digest(first.path(), &options).unwrap(),
+        archive::tree_digest(second.path(), &options).unwrap()
+    );
+    assert_ne!(
+        archive::tree_digest(first.path(), &ArchiveOptions::default()).unwrap(),
+        archive::tree_digest(second.path(), &ArchiveOptions::default()).unwrap()
+    );
+
+    // Restores get the fixed time, which the comparison expects
+    let target = TempDir::new().unwrap();
+    let stored = round_trip(first.path(), target.path(), &options);
+    let differences = archive::compare_trees(first.path(), target.path(), &options).unwrap();
+    assert!(differences.is_empty(), "{:#?}", differences);
+
+    let mut archive = tar::Archive::new(stored.as_slice());
+    for entry in archive.entries().unwrap() {
+        let entry = entry.unwrap();
+        let header = entry.header();
+        assert_eq!(header.mtime().unwrap(), archive::DETERMINISTIC_MTIME);
+        assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0));
+    }
+}
@@ -0,0 +1,51 @@
+use bytes::Bytes;
+use mise_s3_cache::bandwidth::{throttled_bytes, RateLimiter};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_rate_limiter_shared() {
+    assert!(RateLimiter::shared(0).is_none());
+
+    let limiter = RateLimiter::shared(1024).unwrap();
+    assert_eq!(limiter.bytes_per_second(), 1024);
+}
+
+#[test]
+fn test_rate_limiter_reserve() {
+    let limiter = RateLimiter::new(1000);
+
+    // One second of burst is available up front
+    assert_eq!(limiter.reserve(1000), Duration::ZERO);
+
+    // Beyond that, callers wait off the deficit
+    let wait = limiter.reserve(500);
+    assert!(wait > Duration::from_millis(400), "waited {:?}", wait);
+    assert!(wait <= Duration::from_millis(500), "waited {:?}", wait);
+
+    // Later reservations queue behind earlier ones
+    let wait = limiter.reserve(500);
+    assert!(wait > Duration::from_millis(900), "waited {:?}", wait);
+}
+
+#[tokio::test]
+async fn test_rate_limiter_consume_waits() {
+    let limiter = RateLimiter::new(10_000);
+    limiter.consume(10_000).await;
+
+    let start = Instant::now();
+    limiter.consume(2_000).await;
+    assert!(start.elapsed() >= Duration::from_millis(150));
+}
+
+#[tokio::test]
+async fn test_throttled_bytes_preserves_data() {
+    let data = Bytes::from((0..200_000u32).map(|i| i as u8).collect::<Vec<_>>());
+    let limiter = Arc::new(RateLimiter::new(100 * 1024 * 1024));
+
+    let body = throttled_bytes(data.clone(), limiter)
+        .collect()
+        .await
+        .unwrap();
+    assert_eq!(body.into_bytes(), data);
+}
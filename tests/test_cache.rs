@@ -0,0 +1,905 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use mise_s3_cache::cache::CacheManager;
+use mise_s3_cache::compression::Compression as ArchiveCompression;
+use mise_s3_cache::config::Config;
+use mise_s3_cache::encryption::{EncryptingWriter, EncryptionKey};
+use mise_s3_cache::http_storage::HttpStorage;
+use mise_s3_cache::storage::{ObjectInfo, RemoteTier, StorageBackend};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+
+/// In-memory backend so the cache flow can be exercised without AWS
+#[derive(Default)]
+struct MemoryBackend {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    fn insert(&self, key: &str, data: Vec<u8>) {
+        self.objects.lock().unwrap().insert(key.to_string(), data);
+    }
+}
+
+#[async_trait]
+impl StorageBackend for MemoryBackend {
+    fn location(&self) -> String {
+        "memory://".to_string()
+    }
+
+    async fn test_connectivity(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.objects.lock().unwrap().contains_key(key))
+    }
+
+    async fn get(&self, key: &str, local_path: &Path) -> Result<()> {
+        let data = self
+            .objects
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Not found: {}", key))?;
+        std::fs::write(local_path, data)?;
+        Ok(())
+    }
+
+    async fn get_string(&self, key: &str) -> Result<String> {
+        let data = self
+            .objects
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Not found: {}", key))?;
+        Ok(String::from_utf8(data)?)
+    }
+
+    async fn put(&self, local_path: &Path, key: &str) -> Result<()> {
+        self.insert(key, std::fs::read(local_path)?);
+        Ok(())
+    }
+
+    async fn put_string(&self, content: &str, key: &str) -> Result<()> {
+        self.insert(key, content.as_bytes().to_vec());
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        Ok(self
+            .objects
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, data)| ObjectInfo {
+                key: key.clone(),
+                size: data.len() as u64,
+                last_modified: 0,
+            })
+            .collect())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.objects.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+fn build_archive(files: &[(&str, &str)]) -> Vec<u8> {
+    let encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (path, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, path, content.as_bytes())
+            .unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+/// Held by tests that change the working directory to a throwaway project
+static PROJECT_DIR: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+fn test_config() -> Config {
+    Config {
+        // Keep tests out of the real ~/.cache/mise-s3 archive cache
+        local_cache_max_size: 0,
+        ..Default::default()
+    }
+}
+
+/// Metadata of a gzip archive entry with `checksum`
+fn metadata_json(tool: &str, version: &str, checksum: &str) -> Vec<u8> {
+    serde_json::json!({
+        "tool": tool,
+        "version": version,
+        "platform": "linux",
+        "arch": "x64",
+        "created_at": 0,
+        "size_bytes": 0,
+        "checksum": checksum,
+        "mise_version": "test",
+        "compressed": true,
+    })
+    .to_string()
+    .into()
+}
+
+fn populate_entry(backend: &MemoryBackend, config: &Config, tool: &str, version: &str) {
+    let cache_key = config.get_cache_key(tool, version);
+    let archive = build_archive(&[("bin/tool", "#!/bin/sh\necho hi\n")]);
+    let checksum = mise_s3_cache::utils::calculate_hash(&archive);
+
+    backend.insert(&format!("{}/archive.tar.gz", cache_key), archive);
+    backend.insert(
+        &format!("{}/metadata.json", cache_key),
+        metadata_json(tool, version, &checksum),
+    );
+    backend.insert(&format!("{}/checksum.sha256", cache_key), checksum.into());
+}
+
+#[tokio::test]
+async fn test_check_cache_with_memory_backend() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    let manager = CacheManager::new(config.clone(), backend.clone());
+
+    assert!(!manager.check_cache("node", "18.17.0").await.unwrap());
+
+    populate_entry(&backend, &config, "node", "18.17.0");
+    assert!(manager.check_cache("node", "18.17.0").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_restore_from_memory_backend() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    populate_entry(&backend, &config, "node", "20.0.0");
+
+    let manager = CacheManager::new(config, backend);
+    let install_dir = TempDir::new().unwrap();
+    let install_path = install_dir.path().join("node/20.0.0");
+
+    let restored = manager
+        .restore_from_cache("node", "20.0.0", install_path.to_str().unwrap())
+        .await
+        .unwrap();
+
+    assert!(restored);
+    let content = std::fs::read_to_string(install_path.join("bin/tool")).unwrap();
+    assert_eq!(content, "#!/bin/sh\necho hi\n");
+}
+
+#[tokio::test]
+async fn test_restore_rejects_checksum_mismatch() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    populate_entry(&backend, &config, "terraform", "1.5.0");
+
+    let cache_key = config.get_cache_key("terraform", "1.5.0");
+    backend.insert(&format!("{}/checksum.sha256", cache_key), b"0000".to_vec());
+
+    let manager = CacheManager::new(config, backend);
+    let install_dir = TempDir::new().unwrap();
+    let install_path = install_dir.path().join("terraform/1.5.0");
+    std::fs::create_dir_all(&install_path).unwrap();
+    std::fs::write(install_path.join("previous"), "kept").unwrap();
+
+    let restored = manager
+        .restore_from_cache("terraform", "1.5.0", install_path.to_str().unwrap())
+        .await
+        .unwrap();
+
+    assert!(!restored);
+
+    // The unverified archive never reaches the install path, and its staging
+    // directory is cleaned up
+    assert!(install_path.join("previous").exists());
+    assert!(!install_path.join("bin/tool").exists());
+    let siblings: Vec<_> = std::fs::read_dir(install_dir.path().join("terraform"))
+        .unwrap()
+        .collect();
+    assert_eq!(siblings.len(), 1);
+}
+
+#[tokio::test]
+async fn test_restore_verifies_metadata_checksum_without_checksum_object() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    populate_entry(&backend, &config, "terraform", "1.6.0");
+    let cache_key = config.get_cache_key("terraform", "1.6.0");
+    backend
+        .delete(&format!("{}/checksum.sha256", cache_key))
+        .await
+        .unwrap();
+    let manager = CacheManager::new(config, backend.clone());
+
+    let install_dir = TempDir::new().unwrap();
+    assert!(manager
+        .restore_from_cache("terraform", "1.6.0", install_dir.path().to_str().unwrap())
+        .await
+        .unwrap());
+
+    // A wrong metadata checksum is a mismatch, and a missing one a refusal
+    for checksum in ["0000", ""] {
+        backend.insert(
+            &format!("{}/metadata.json", cache_key),
+            metadata_json("terraform", "1.6.0", checksum),
+        );
+        let install_dir = TempDir::new().unwrap();
+        let install_path = install_dir.path().join("terraform/1.6.0");
+        assert!(!manager
+            .restore_from_cache("terraform", "1.6.0", install_path.to_str().unwrap())
+            .await
+            .unwrap());
+        assert!(!install_path.exists());
+    }
+}
+
+#[tokio::test]
+async fn test_restore_refuses_unreadable_metadata() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    populate_entry(&backend, &config, "terraform", "1.7.0");
+    let cache_key = config.get_cache_key("terraform", "1.7.0");
+    backend.insert(&format!("{}/metadata.json", cache_key), b"{}".to_vec());
+
+    let manager = CacheManager::new(config, backend);
+    let install_dir = TempDir::new().unwrap();
+    let install_path = install_dir.path().join("terraform/1.7.0");
+    assert!(!manager
+        .restore_from_cache("terraform", "1.7.0", install_path.to_str().unwrap())
+        .await
+        .unwrap());
+    assert!(!install_dir.path().join("terraform").exists());
+}
+
+#[tokio::test]
+async fn test_restore_refuses_unsafe_archive() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+
+    // A valid first entry, then one that climbs out of the install path
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for name in ["bin/tool", "bin/../../../escape"] {
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(2);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &b"hi"[..]).unwrap();
+    }
+    let archive = builder.into_inner().unwrap().finish().unwrap();
+
+    let cache_key = config.get_cache_key("node", "21.0.0");
+    let checksum = mise_s3_cache::utils::calculate_hash(&archive);
+    backend.insert(&format!("{}/archive.tar.gz", cache_key), archive);
+    backend.insert(
+        &format!("{}/metadata.json", cache_key),
+        metadata_json("node", "21.0.0", &checksum),
+    );
+    backend.insert(&format!("{}/checksum.sha256", cache_key), checksum.into());
+
+    let manager = CacheManager::new(config, backend);
+    let install_dir = TempDir::new().unwrap();
+    let install_path = install_dir.path().join("node/21.0.0");
+    let restored = manager
+        .restore_from_cache("node", "21.0.0", install_path.to_str().unwrap())
+        .await
+        .unwrap();
+
+    // Nothing half-extracted is left behind where mise would look
+    assert!(!restored);
+    assert!(!install_path.exists());
+    assert!(!install_dir.path().join("escape").exists());
+    assert_eq!(
+        std::fs::read_dir(install_dir.path().join("node"))
+            .unwrap()
+            .count(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_restore_decrypts_encrypted_entry() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    let key = Arc::new(EncryptionKey::from_hex("4f".repeat(32).as_str()).expect("valid key"));
+
+    let mut writer = EncryptingWriter::new(Vec::new(), key.clone()).unwrap();
+    writer
+        .write_all(&build_archive(&[("bin/tool", "proprietary\n")]))
+        .unwrap();
+    let archive = writer.finish().unwrap();
+
+    let cache_key = config.get_cache_key("licensed", "1.0.0");
+    let metadata = serde_json::json!({
+        "tool": "licensed",
+        "version": "1.0.0",
+        "platform": "linux",
+        "arch": "x64",
+        "created_at": 0,
+        "size_bytes": archive.len(),
+        "checksum": mise_s3_cache::utils::calculate_hash(&archive),
+        "mise_version": "test",
+        "compressed": true,
+        "encryption": {"algorithm": "AES-256-GCM-STREAM", "key_id": key.id()},
+    });
+    backend.insert(
+        &format!("{}/checksum.sha256", cache_key),
+        mise_s3_cache::utils::calculate_hash(&archive).into(),
+    );
+    backend.insert(&format!("{}/archive.tar.gz", cache_key), archive);
+    backend.insert(
+        &format!("{}/metadata.json", cache_key),
+        metadata.to_string().into(),
+    );
+
+    // Without the key, or with another one, the entry is treated as a miss
+    let other_key = Arc::new(EncryptionKey::from_hex("a1".repeat(32).as_str()).unwrap());
+    for wrong_key in [None, Some(other_key)] {
+        let manager =
+            CacheManager::new(config.clone(), backend.clone()).with_encryption_key(wrong_key);
+        let install_dir = TempDir::new().unwrap();
+        let restored = manager
+            .restore_from_cache("licensed", "1.0.0", install_dir.path().to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(!restored);
+    }
+
+    let manager = CacheManager::new(config, backend).with_encryption_key(Some(key));
+    let install_dir = TempDir::new().unwrap();
+    let install_path = install_dir.path().join("licensed/1.0.0");
+    let restored = manager
+        .restore_from_cache("licensed", "1.0.0", install_path.to_str().unwrap())
+        .await
+        .unwrap();
+    assert!(restored);
+    let content = std::fs::read_to_string(install_path.join("bin/tool")).unwrap();
+    assert_eq!(content, "proprietary\n");
+}
+
+#[tokio::test]
+async fn test_restore_uses_recorded_compression() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+
+    let mut encoder = ArchiveCompression::Zstd
+        .encoder(Vec::new(), None, 1)
+        .unwrap();
+    let mut builder = tar::Builder::new(&mut encoder);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(3);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "bin/zig", &b"zig"[..])
+        .unwrap();
+    builder.finish().unwrap();
+    drop(builder);
+    let archive = encoder.finish().unwrap();
+
+    let cache_key = config.get_cache_key("zig", "0.13.0");
+    let checksum = mise_s3_cache::utils::calculate_hash(&archive);
+    backend.insert(&format!("{}/archive.tar.zst", cache_key), archive);
+    backend.insert(&format!("{}/checksum.sha256", cache_key), checksum.into());
+    backend.insert(
+        &format!("{}/metadata.json", cache_key),
+        serde_json::json!({
+            "tool": "zig",
+            "version": "0.13.0",
+            "platform": "linux",
+            "arch": "x64",
+            "created_at": 0,
+            "size_bytes": 0,
+            "checksum": "",
+            "mise_version": "test",
+            "compressed": true,
+            "compression": "zstd",
+        })
+        .to_string()
+        .into(),
+    );
+
+    // The local setting does not matter, only what the entry records
+    let manager = CacheManager::new(config, backend);
+    let install_dir = TempDir::new().unwrap();
+    let restored = manager
+        .restore_from_cache("zig", "0.13.0", install_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert!(restored);
+    assert_eq!(
+        std::fs::read_to_string(install_dir.path().join("bin/zig")).unwrap(),
+        "zig"
+    );
+}
+
+#[tokio::test]
+async fn test_restore_relocates_recorded_prefix() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    let old_prefix = "/home/alice/.local/share/mise/installs/ruby/3.3.0";
+    let shebang = format!("#!{}/bin/ruby\n", old_prefix);
+    let archive = build_archive(&[("bin/gem", shebang.as_str())]);
+
+    let cache_key = config.get_cache_key("ruby", "3.3.0");
+    let checksum = mise_s3_cache::utils::calculate_hash(&archive);
+    backend.insert(&format!("{}/archive.tar.gz", cache_key), archive);
+    backend.insert(&format!("{}/checksum.sha256", cache_key), checksum.into());
+    backend.insert(
+        &format!("{}/metadata.json", cache_key),
+        serde_json::json!({
+            "tool": "ruby",
+            "version": "3.3.0",
+            "platform": "linux",
+            "arch": "x64",
+            "created_at": 0,
+            "size_bytes": 0,
+            "checksum": "",
+            "mise_version": "test",
+            "compressed": true,
+            "install_path": old_prefix,
+            "prefix_references": [{ "path": "bin/gem", "kind": "text" }],
+        })
+        .to_string()
+        .into(),
+    );
+
+    let manager = CacheManager::new(config, backend);
+    let install_dir = TempDir::new().unwrap();
+    let install_path = install_dir.path().join("ruby/3.3.0");
+    let restored = manager
+        .restore_from_cache("ruby", "3.3.0", install_path.to_str().unwrap())
+        .await
+        .unwrap();
+
+    assert!(restored);
+    assert_eq!(
+        std::fs::read_to_string(install_path.join("bin/gem")).unwrap(),
+        format!("#!{}/bin/ruby\n", install_path.display())
+    );
+}
+
+#[tokio::test]
+async fn test_cleanup_removes_old_entries() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    populate_entry(&backend, &config, "python", "3.11.0");
+
+    let manager = CacheManager::new(config, backend.clone());
+    manager.cleanup_old_cache(1).await.unwrap();
+
+    assert!(backend.list("").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_cleanup_decides_expiry_per_entry_from_metadata() {
+    let config = test_config();
+    let backend = Arc::new(MemoryBackend::default());
+    populate_entry(&backend, &config, "python", "3.11.0");
+    populate_entry(&backend, &config, "node", "20.0.0");
+
+    // Every object reports an old write time, but node was stored just now
+    let node_key = config.get_cache_key("node", "20.0.0");
+    let mut metadata: serde_json::Value =
+        serde_json::from_slice(&metadata_json("node", "20.0.0", "abc")).unwrap();
+    metadata["created_at"] = mise_s3_cache::utils::current_timestamp().into();
+    backend.insert(
+        &format!("{}/metadata.json", node_key),
+        metadata.to_string().into(),
+    );
+    // An archive whose store never published metadata
+    let orphan_key = config.get_cache_key("ruby", "3.3.0");
+    backend.insert(&format!("{}/archive.tar.gz", orphan_key), vec![1, 2, 3]);
+
+    let manager = CacheManager::new(config.clone(), backend.clone());
+    manager.cleanup_old_cache(1).await.unwrap();
+
+    let mut remaining: Vec<String> = backend
+        .list("")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|object| object.key)
+        .collect();
+    remaining.sort();
+    assert_eq!(
+        remaining,
+        vec![
+            format!("{}/archive.tar.gz", node_key),
+            format!("{}/checksum.sha256", node_key),
+            format!("{}/metadata.json", node_key),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_restore_falls_back_to_secondary_cache() {
+    let config = test_config();
+    let primary = Arc::new(MemoryBackend::default());
+    let fallback = Arc::new(MemoryBackend::default());
+
+    let fallback_config = Config {
+        prefix: "global".to_string(),
+        ..test_config()
+    };
+    populate_entry(&fallback, &fallback_config, "node", "20.0.0");
+
+    let manager =
+        CacheManager::new(config.clone(), primary.clone()).with_fallbacks(vec![RemoteTier {
+            config: fallback_config,
+            backend: fallback.clone(),
+        }]);
+
+    assert!(manager.check_cache("node", "20.0.0").await.unwrap());
+    assert!(!manager.check_cache("node", "21.0.0").await.unwrap());
+
+    let install_dir = TempDir::new().unwrap();
+    let restored = manager
+        .restore_from_cache("node", "20.0.0", install_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert!(restored);
+    assert!(install_dir.path().join("bin/tool").exists());
+
+    // The entry is copied into the primary cache on the way through
+    let cache_key = config.get_cache_key("node", "20.0.0");
+    assert!(primary
+        .exists(&format!("{}/archive.tar.gz", cache_key))
+        .await
+        .unwrap());
+    assert!(primary
+        .exists(&format!("{}/metadata.json", cache_key))
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn test_sync_fills_missing_replica_entries() {
+    let config = test_config();
+    let primary = Arc::new(MemoryBackend::default());
+    let replica = Arc::new(MemoryBackend::default());
+
+    let replica_config = Config {
+        prefix: "eu-west".to_string(),
+        ..test_config()
+    };
+    populate_entry(&primary, &config, "node", "20.0.0");
+    populate_entry(&primary, &config, "python", "3.11.0");
+    populate_entry(&replica, &replica_config, "node", "20.0.0");
+
+    let manager = CacheManager::new(config, primary).with_replicas(vec![RemoteTier {
+        config: replica_config.clone(),
+        backend: replica.clone(),
+    }]);
+
+    manager.sync_replicas().await.unwrap();
+
+    let cache_key = replica_config.get_cache_key("python", "3.11.0");
+    for object in ["archive.tar.gz", "checksum.sha256", "metadata.json"] {
+        assert!(replica
+            .exists(&format!("{}/{}", cache_key, object))
+            .await
+            .unwrap());
+    }
+    assert_eq!(replica.list("eu-west/tools/").await.unwrap().len(), 6);
+}
+
+#[tokio::test]
+async fn test_sync_copies_entries_without_checksum_object() {
+    let config = test_config();
+    let primary = Arc::new(MemoryBackend::default());
+    let replica = Arc::new(MemoryBackend::default());
+    let replica_config = Config {
+        prefix: "eu-west".to_string(),
+        ..test_config()
+    };
+
+    populate_entry(&primary, &config, "node", "20.0.0");
+    let cache_key = config.get_cache_key("node", "20.0.0");
+    let checksum_key = format!("{}/checksum.sha256", cache_key);
+    let checksum = primary
+        .objects
+        .lock()
+        .unwrap()
+        .remove(&checksum_key)
+        .unwrap();
+
+    let manager = CacheManager::new(config, primary).with_replicas(vec![RemoteTier {
+        config: replica_config.clone(),
+        backend: replica.clone(),
+    }]);
+    manager.sync_replicas().await.unwrap();
+
+    // The replica gets the checksum recorded in the metadata
+    let replica_key = replica_config.get_cache_key("node", "20.0.0");
+    assert_eq!(
+        replica
+            .get_string(&format!("{}/checksum.sha256", replica_key))
+            .await
+            .unwrap()
+            .as_bytes(),
+        checksum
+    );
+}
+
+#[tokio::test]
+async fn test_store_streams_to_primary_and_replicas() {
+    // Run from a throwaway project that pins the tool
+    let _cwd = PROJECT_DIR.lock().await;
+    let project = TempDir::new().unwrap();
+    std::fs::create_dir(project.path().join(".git")).unwrap();
+    std::fs::write(project.path().join(".tool-versions"), "node 22.1.0\n").unwrap();
+    std::env::set_current_dir(project.path()).unwrap();
+
+    let install_dir = TempDir::new().unwrap();
+    std::fs::create_dir_all(install_dir.path().join("bin")).unwrap();
+    std::fs::write(install_dir.path().join("bin/node"), "#!/bin/sh\n").unwrap();
+
+    let config = test_config();
+    let primary = Arc::new(MemoryBackend::default());
+    let replica = Arc::new(MemoryBackend::default());
+    let replica_config = Config {
+        prefix: "eu-west".to_string(),
+        ..test_config()
+    };
+
+    let manager = CacheManager::new(config.clone(), primary.clone()).with_replicas(vec![
+        RemoteTier {
+            config: replica_config.clone(),
+            backend: replica.clone(),
+        },
+        // A replica that rejects writes must not fail the store
+        RemoteTier {
+            config: test_config(),
+            backend: Arc::new(HttpStorage::new("http://127.0.0.1:9/mirror").unwrap()),
+        },
+    ]);
+
+    manager
+        .store_in_cache("node", "22.1.0", install_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    let cache_key = config.get_cache_key("node", "22.1.0");
+    let archive = primary.objects.lock().unwrap()[&format!("{}/archive.tar.gz", cache_key)].clone();
+    let checksum = primary
+        .get_string(&format!("{}/checksum.sha256", cache_key))
+        .await
+        .unwrap();
+    assert_eq!(checksum, mise_s3_cache::utils::calculate_hash(&archive));
+
+    let replica_key = replica_config.get_cache_key("node", "22.1.0");
+    assert!(replica
+        .exists(&format!("{}/metadata.json", replica_key))
+        .await
+        .unwrap());
+
+    let restore_dir = TempDir::new().unwrap();
+    let restored = manager
+        .restore_from_cache("node", "22.1.0", restore_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert!(restored);
+    assert!(restore_dir.path().join("bin/node").exists());
+}
+
+#[tokio::test]
+async fn test_deterministic_store_is_reproducible_and_skips_unchanged() {
+    let _cwd = PROJECT_DIR.lock().await;
+    let project = TempDir::new().unwrap();
+    std::fs::create_dir(project.path().join(".git")).unwrap();
+    std::fs::write(project.path().join(".tool-versions"), "deno 2.0.0\n").unwrap();
+    std::env::set_current_dir(project.path()).unwrap();
+
+    let config = Config {
+        deterministic_archives: true,
+        ..test_config()
+    };
+    let cache_key = config.get_cache_key("deno", "2.0.0");
+    let archive_key = format!("{}/archive.tar.gz", cache_key);
+
+    // The same install on two runners, written at different times
+    let mut archives = Vec::new();
+    for mtime in [1_700_000_000, 1_800_000_000] {
+        let install_dir = TempDir::new().unwrap();
+        let binary = install_dir.path().join("bin/deno");
+        std::fs::create_dir_all(binary.parent().unwrap()).unwrap();
+        std::fs::write(&binary, "#!/bin/sh\n").unwrap();
+        filetime::set_file_mtime(&binary, filetime::FileTime::from_unix_time(mtime, 0)).unwrap();
+
+        let backend = Arc::new(MemoryBackend::default());
+        CacheManager::new(config.clone(), backend.clone())
+            .store_in_cache("deno", "2.0.0", install_dir.path().to_str().unwrap())
+            .await
+            .unwrap();
+        let archive = backend.objects.lock().unwrap()[&archive_key].clone();
+        archives.push((archive, backend, install_dir));
+    }
+    assert_eq!(archives[0].0, archives[1].0);
+
+    // Storing an unchanged install again uploads nothing, but restarts the
+    // age of the entry so cleanup keeps it
+    let (_, backend, install_dir) = archives.pop().unwrap();
+    backend.insert(&archive_key, b"untouched".to_vec());
+    let metadata_key = format!("{}/metadata.json", cache_key);
+    let mut metadata: serde_json::Value =
+        serde_json::from_slice(&backend.objects.lock().unwrap()[&metadata_key]).unwrap();
+    metadata["created_at"] = 1_000.into();
+    backend.insert(&metadata_key, metadata.to_string().into());
+    let manager = CacheManager::new(config.clone(), backend.clone());
+    let install_path = install_dir.path().to_str().unwrap();
+    manager
+        .store_in_cache("deno", "2.0.0", install_path)
+        .await
+        .unwrap();
+    assert_eq!(backend.objects.lock().unwrap()[&archive_key], b"untouched");
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&backend.objects.lock().unwrap()[&metadata_key]).unwrap();
+    assert!(metadata["created_at"].as_u64().unwrap() > 1_000);
+
+    // A different compression level is not the same entry
+    let releveled = Config {
+        compression_level: Some(1),
+        ..config
+    };
+    CacheManager::new(releveled, backend.clone())
+        .store_in_cache("deno", "2.0.0", install_path)
+        .await
+        .unwrap();
+    assert_ne!(backend.objects.lock().unwrap()[&archive_key], b"untouched");
+    backend.insert(&archive_key, b"untouched".to_vec());
+
+    std::fs::write(install_dir.path().join("bin/deno"), "#!/bin/sh\nexit 0\n").unwrap();
+    manager
+        .store_in_cache("deno", "2.0.0", install_path)
+        .await
+        .unwrap();
+    assert_ne!(backend.objects.lock().unwrap()[&archive_key], b"untouched");
+
+    // An unchanged entry stored before manifests were recorded gets one
+    let mut metadata: serde_json::Value =
+        serde_json::from_slice(&backend.objects.lock().unwrap()[&metadata_key]).unwrap();
+    metadata.as_object_mut().unwrap().remove("manifest");
+    backend.insert(&metadata_key, metadata.to_string().into());
+    backend.insert(&archive_key, b"untouched".to_vec());
+    manager
+        .store_in_cache("deno", "2.0.0", install_path)
+        .await
+        .unwrap();
+    assert_ne!(backend.objects.lock().unwrap()[&archive_key], b"untouched");
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&backend.objects.lock().unwrap()[&metadata_key]).unwrap();
+    assert!(metadata["manifest"].is_array());
+}
+
+#[tokio::test]
+async fn test_verify_install_against_stored_manifest() {
+    let _cwd = PROJECT_DIR.lock().await;
+    let project = TempDir::new().unwrap();
+    std::fs::create_dir(project.path().join(".git")).unwrap();
+    std::fs::write(project.path().join(".tool-versions"), "bun 1.1.0\n").unwrap();
+    std::env::set_current_dir(project.path()).unwrap();
+
+    let install_dir = TempDir::new().unwrap();
+    let prefix = install_dir.path().to_str().unwrap().to_string();
+    std::fs::create_dir_all(install_dir.path().join("bin")).unwrap();
+    std::fs::write(
+        install_dir.path().join("bin/bunx"),
+        format!("#!{}/bin/bun\n", prefix),
+    )
+    .unwrap();
+    std::fs::write(install_dir.path().join("bin/bun"), "binary").unwrap();
+
+    let manager = CacheManager::new(test_config(), Arc::new(MemoryBackend::default()));
+    manager
+        .store_in_cache("bun", "1.1.0", &prefix)
+        .await
+        .unwrap();
+
+    // Restored elsewhere, bin/bunx is relocated but still matches
+    let restore_dir = TempDir::new().unwrap();
+    let restore_path = restore_dir.path().join("bun/1.1.0");
+    let restore_path = restore_path.to_str().unwrap();
+    assert!(manager
+        .restore_from_cache("bun", "1.1.0", restore_path)
+        .await
+        .unwrap());
+    let report = manager
+        .verify_install("bun", "1.1.0", Some(restore_path))
+        .await
+        .unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+
+    std::fs::write(restore_dir.path().join("bun/1.1.0/bin/bun"), "tampered").unwrap();
+    std::fs::remove_file(restore_dir.path().join("bun/1.1.0/bin/bunx")).unwrap();
+    let report = manager
+        .verify_install("bun", "1.1.0", Some(restore_path))
+        .await
+        .unwrap();
+    assert_eq!(report.modified.len(), 1);
+    assert_eq!(report.modified[0].path, "bin/bun");
+    assert_eq!(report.missing, vec!["bin/bunx".to_string()]);
+}
+
+#[tokio::test]
+async fn test_encrypted_entry_metadata_hides_the_files_of_the_install() {
+    let _cwd = PROJECT_DIR.lock().await;
+    let project = TempDir::new().unwrap();
+    std::fs::create_dir(project.path().join(".git")).unwrap();
+    std::fs::write(project.path().join(".tool-versions"), "zig 0.13.0\n").unwrap();
+    std::env::set_current_dir(project.path()).unwrap();
+
+    let install_dir = TempDir::new().unwrap();
+    let prefix = install_dir.path().to_str().unwrap().to_string();
+    std::fs::create_dir_all(install_dir.path().join("bin")).unwrap();
+    std::fs::write(
+        install_dir.path().join("bin/zig-wrapper"),
+        format!("#!{}/bin/zig\n", prefix),
+    )
+    .unwrap();
+    std::fs::write(install_dir.path().join("bin/zig"), "binary").unwrap();
+
+    let config = Config {
+        deterministic_archives: true,
+        ..test_config()
+    };
+    let metadata_key = format!("{}/metadata.json", config.get_cache_key("zig", "0.13.0"));
+    let key = Arc::new(EncryptionKey::from_hex("4f".repeat(32).as_str()).unwrap());
+    let backend = Arc::new(MemoryBackend::default());
+    let manager = CacheManager::new(config, backend.clone()).with_encryption_key(Some(key));
+    manager
+        .store_in_cache("zig", "0.13.0", &prefix)
+        .await
+        .unwrap();
+
+    let json = String::from_utf8(backend.objects.lock().unwrap()[&metadata_key].clone()).unwrap();
+    let binary_hash = mise_s3_cache::utils::calculate_hash(b"binary");
+    assert!(!json.contains(&binary_hash), "{json}");
+    assert!(!json.contains("zig-wrapper"), "{json}");
+    let metadata: serde_json::Value = serde_json::from_str(&json).unwrap();
+    for field in ["manifest", "prefix_references", "content_digest"] {
+        assert!(metadata.get(field).is_none(), "{field} in {json}");
+    }
+    assert!(metadata["sealed"].is_string());
+
+    // With the key, the sealed fields still serve restores and checks
+    let restore_dir = TempDir::new().unwrap();
+    let restore_path = restore_dir.path().join("zig/0.13.0");
+    let restore_path = restore_path.to_str().unwrap();
+    assert!(manager
+        .restore_from_cache("zig", "0.13.0", restore_path)
+        .await
+        .unwrap());
+    let wrapper = std::fs::read_to_string(restore_dir.path().join("zig/0.13.0/bin/zig-wrapper"));
+    assert_eq!(wrapper.unwrap(), format!("#!{}/bin/zig\n", restore_path));
+    let report = manager
+        .verify_install("zig", "0.13.0", Some(restore_path))
+        .await
+        .unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+
+    // The unchanged check reads the sealed content digest, so the entry is
+    // kept as it is rather than sealed again
+    manager
+        .store_in_cache("zig", "0.13.0", &prefix)
+        .await
+        .unwrap();
+    let stored: serde_json::Value =
+        serde_json::from_slice(&backend.objects.lock().unwrap()[&metadata_key]).unwrap();
+    assert_eq!(stored["sealed"], metadata["sealed"]);
+}
@@ -0,0 +1,249 @@
+use mise_s3_cache::cache::CacheManager;
+use mise_s3_cache::chunks::{self, ChunkEncoding, ChunkIndex};
+use mise_s3_cache::compression::Compression;
+use mise_s3_cache::config::Config;
+use mise_s3_cache::encryption::EncryptionKey;
+use mise_s3_cache::local_storage::LocalStorage;
+use mise_s3_cache::storage::RemoteTier;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+/// Deterministic incompressible bytes, so chunk boundaries are realistic
+fn pseudo_random(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as u8
+        })
+        .collect()
+}
+
+async fn split(data: Vec<u8>) -> Vec<String> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+    let splitter = tokio::task::spawn_blocking(move || chunks::split(&data[..], tx));
+
+    let encoding = ChunkEncoding {
+        compression: Compression::None,
+        level: None,
+        key: None,
+    };
+    let mut ids = Vec::new();
+    while let Some(chunk) = rx.recv().await {
+        ids.push(encoding.id(&chunk.unwrap()));
+    }
+    splitter.await.unwrap().unwrap();
+    ids
+}
+
+#[test]
+fn test_chunk_encoding_round_trip() {
+    let key = Arc::new(EncryptionKey::from_hex(&"3c".repeat(32)).unwrap());
+    let data = pseudo_random(1, 300 * 1024);
+
+    for compression in [
+        Compression::Gzip,
+        Compression::Zstd,
+        Compression::Xz,
+        Compression::None,
+    ] {
+        for key in [None, Some(key.clone())] {
+            let encoding = ChunkEncoding {
+                compression,
+                level: None,
+                key,
+            };
+            let stored = encoding.encode(&data).unwrap();
+            assert_eq!(encoding.decode(&stored).unwrap(), data);
+        }
+    }
+
+    // Encrypted entries name their chunks with a keyed hash
+    let plain = ChunkEncoding {
+        compression: Compression::None,
+        level: None,
+        key: None,
+    };
+    let keyed = ChunkEncoding {
+        key: Some(key),
+        ..plain.clone()
+    };
+    assert_eq!(plain.id(&data), mise_s3_cache::utils::calculate_hash(&data));
+    assert_ne!(plain.id(&data), keyed.id(&data));
+}
+
+#[test]
+fn test_chunk_keys() {
+    let id = "ab".repeat(32);
+    let key = chunks::chunk_key("mise-cache", &id, Compression::Zstd);
+
+    assert_eq!(key, format!("mise-cache/chunks/ab/{}.zst", id));
+    assert_eq!(chunks::chunk_id_from_key(&key), Some(id.as_str()));
+    assert_ne!(key, chunks::chunk_key("mise-cache", &id, Compression::Gzip));
+}
+
+#[tokio::test]
+async fn test_split_is_content_defined() {
+    let data = pseudo_random(7, 12 * 1024 * 1024);
+    let mut shifted = b"a few inserted bytes".to_vec();
+    shifted.extend_from_slice(&data);
+
+    let original = split(data).await;
+    let edited = split(shifted).await;
+    assert!(original.len() > 2);
+
+    // Only the chunk around the edit changes
+    let shared = edited.iter().filter(|id| original.contains(id)).count();
+    assert!(shared >= original.len() - 1);
+}
+
+fn write_install(dir: &Path, shared: &[u8], own: &str) {
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::create_dir_all(dir.join("bin")).unwrap();
+    std::fs::write(dir.join("lib/runtime.so"), shared).unwrap();
+    std::fs::write(dir.join("bin/tool"), own).unwrap();
+}
+
+fn backdate(dir: &Path, age: Duration) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            backdate(&path, age);
+        } else {
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::now() - age).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_chunked_store_restore_and_cleanup() {
+    let project = TempDir::new().unwrap();
+    std::fs::create_dir(project.path().join(".git")).unwrap();
+    std::fs::write(
+        project.path().join(".tool-versions"),
+        "node 22.1.0\ndeno 22.1.0\n",
+    )
+    .unwrap();
+    std::env::set_current_dir(project.path()).unwrap();
+
+    let shared = pseudo_random(3, 6 * 1024 * 1024);
+    let installs = TempDir::new().unwrap();
+    write_install(&installs.path().join("node"), &shared, "node\n");
+    write_install(&installs.path().join("deno"), &shared, "deno\n");
+
+    let root = TempDir::new().unwrap();
+    let config = Config {
+        storage_mode: chunks::CHUNKED.to_string(),
+        compression: "zstd".to_string(),
+        local_cache_max_size: 0,
+        ..Default::default()
+    };
+    let manager = CacheManager::new(config.clone(), Arc::new(LocalStorage::new(root.path())));
+    let chunks_dir = root.path().join("mise-cache/chunks");
+    let count_chunks = || {
+        std::fs::read_dir(&chunks_dir)
+            .unwrap()
+            .map(|dir| std::fs::read_dir(dir.unwrap().path()).unwrap().count())
+            .sum::<usize>()
+    };
+
+    for tool in ["node", "deno"] {
+        manager
+            .store_in_cache(tool, "22.1.0", installs.path().join(tool).to_str().unwrap())
+            .await
+            .unwrap();
+    }
+
+    let entry = root.path().join(config.get_cache_key("deno", "22.1.0"));
+    assert!(entry.join("index.json").exists());
+    assert!(!entry.join("archive.tar.zst").exists());
+
+    // The second tool only adds the chunks around what differs
+    let node_index: ChunkIndex = serde_json::from_str(
+        &std::fs::read_to_string(
+            root.path()
+                .join(config.get_cache_key("node", "22.1.0"))
+                .join("index.json"),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let stored = count_chunks();
+    assert!(stored < node_index.chunks.len() + 3);
+
+    let restore_dir = TempDir::new().unwrap();
+    let restore_path = restore_dir.path().join("deno");
+    assert!(manager
+        .restore_from_cache("deno", "22.1.0", restore_path.to_str().unwrap())
+        .await
+        .unwrap());
+    assert_eq!(
+        std::fs::read(restore_path.join("lib/runtime.so")).unwrap(),
+        shared
+    );
+    assert_eq!(
+        std::fs::read_to_string(restore_path.join("bin/tool")).unwrap(),
+        "deno\n"
+    );
+
+    // Sync copies the index and chunks to a replica with its own prefix
+    let replica_root = TempDir::new().unwrap();
+    let replica_config = Config {
+        prefix: "eu-west".to_string(),
+        ..config.clone()
+    };
+    let replica = Arc::new(LocalStorage::new(replica_root.path()));
+    CacheManager::new(config.clone(), Arc::new(LocalStorage::new(root.path())))
+        .with_replicas(vec![RemoteTier {
+            config: replica_config.clone(),
+            backend: replica.clone(),
+        }])
+        .sync_replicas()
+        .await
+        .unwrap();
+
+    let restore_path = restore_dir.path().join("deno-replica");
+    assert!(CacheManager::new(replica_config, replica)
+        .restore_from_cache("deno", "22.1.0", restore_path.to_str().unwrap())
+        .await
+        .unwrap());
+    assert_eq!(
+        std::fs::read(restore_path.join("lib/runtime.so")).unwrap(),
+        shared
+    );
+
+    // A store refreshes the old chunks it reuses, so a cleanup that runs
+    // before its index is written leaves them alone
+    backdate(&chunks_dir, Duration::from_secs(3 * 24 * 60 * 60));
+    manager
+        .store_in_cache(
+            "deno",
+            "22.1.0",
+            installs.path().join("deno").to_str().unwrap(),
+        )
+        .await
+        .unwrap();
+    std::fs::remove_dir_all(&entry).unwrap();
+    manager.cleanup_old_cache(1).await.unwrap();
+    assert_eq!(count_chunks(), stored);
+
+    // Once deno has expired, only the chunks node still uses are kept
+    backdate(&chunks_dir, Duration::from_secs(3 * 24 * 60 * 60));
+    manager.cleanup_old_cache(1).await.unwrap();
+
+    assert!(count_chunks() < stored);
+    let restore_path = restore_dir.path().join("node");
+    assert!(manager
+        .restore_from_cache("node", "22.1.0", restore_path.to_str().unwrap())
+        .await
+        .unwrap());
+    assert_eq!(
+        std::fs::read_to_string(restore_path.join("bin/tool")).unwrap(),
+        "node\n"
+    );
+}
@@ -0,0 +1,128 @@
+use mise_s3_cache::compression::{Compression, GZIP_BLOCK_SIZE};
+use std::io::{Read, Write};
+
+const FORMATS: [Compression; 4] = [
+    Compression::Gzip,
+    Compression::Zstd,
+    Compression::Xz,
+    Compression::None,
+];
+
+#[test]
+fn test_compression_parse() {
+    assert_eq!(Compression::parse("gzip").unwrap(), Compression::Gzip);
+    assert_eq!(Compression::parse("ZSTD").unwrap(), Compression::Zstd);
+    assert_eq!(Compression::parse("xz").unwrap(), Compression::Xz);
+    assert_eq!(Compression::parse("none").unwrap(), Compression::None);
+    assert!(Compression::parse("brotli").is_err());
+
+    for format in FORMATS {
+        assert_eq!(Compression::parse(format.name()).unwrap(), format);
+    }
+}
+
+#[test]
+fn test_compression_archive_names() {
+    assert_eq!(Compression::Gzip.archive_name(), "archive.tar.gz");
+    assert_eq!(Compression::Zstd.archive_name(), "archive.tar.zst");
+    assert_eq!(Compression::Xz.archive_name(), "archive.tar.xz");
+    assert_eq!(Compression::None.archive_name(), "archive.tar");
+}
+
+fn compress(format: Compression, data: &[u8], level: Option<i32>, threads: usize) -> Vec<u8> {
+    let mut encoder = format.encoder(Vec::new(), level, threads).unwrap();
+    // Odd write sizes so blocks do not line up with writes
+    for piece in data.chunks(70_001) {
+        encoder.write_all(piece).unwrap();
+    }
+    encoder.finish().unwrap()
+}
+
+fn decompress(format: Compression, data: &[u8], threads: usize) -> Vec<u8> {
+    let mut decompressed = Vec::new();
+    format
+        .decoder(data, threads)
+        .unwrap()
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    decompressed
+}
+
+#[test]
+fn test_compression_round_trip() {
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 7) as u8).collect();
+
+    for format in FORMATS {
+        for level in [None, format.level_range().map(|(_, max)| max)] {
+            let compressed = compress(format, &data, level, 1);
+            if format != Compression::None {
+                assert!(compressed.len() < data.len() / 10, "{:?}", format);
+            }
+
+            let decompressed = decompress(format, &compressed, 1);
+            assert_eq!(decompressed, data, "{:?} at level {:?}", format, level);
+        }
+    }
+}
+
+#[test]
+fn test_compression_multithreaded_round_trip() {
+    let data: Vec<u8> = (0..5 * GZIP_BLOCK_SIZE as u32 + 777)
+        .map(|i| ((i / 3) % 251) as u8)
+        .collect();
+
+    for format in FORMATS {
+        let compressed = compress(format, &data, Some(1), 4);
+
+        // Archives written on several threads decode with one thread or many
+        assert_eq!(decompress(format, &compressed, 1), data, "{:?}", format);
+        assert_eq!(decompress(format, &compressed, 4), data, "{:?}", format);
+    }
+}
+
+#[test]
+fn test_parallel_gzip_members() {
+    let data: Vec<u8> = (0..3 * GZIP_BLOCK_SIZE as u32 + 1)
+        .map(|i| (i % 13) as u8)
+        .collect();
+
+    let two_threads = compress(Compression::Gzip, &data, None, 2);
+    let eight_threads = compress(Compression::Gzip, &data, None, 8);
+
+    // One gzip member per block, the same however many threads compress them
+    let members = two_threads
+        .windows(3)
+        .filter(|w| w == &[0x1f, 0x8b, 0x08])
+        .count();
+    assert_eq!(members, 4);
+    assert_eq!(two_threads, eight_threads);
+
+    // Empty input is still a valid gzip stream
+    let empty = compress(Compression::Gzip, &[], None, 4);
+    assert!(decompress(Compression::Gzip, &empty, 4).is_empty());
+}
+
+#[test]
+fn test_deterministic_encoder_ignores_thread_count() {
+    // Several gzip members, xz blocks and zstd jobs at level 1
+    let data: Vec<u8> = (0..10 * GZIP_BLOCK_SIZE as u32)
+        .map(|i| ((i / 5) % 241) as u8 ^ (i >> 17) as u8)
+        .collect();
+
+    for format in FORMATS {
+        let outputs: Vec<Vec<u8>> = [1, 2, 6]
+            .into_iter()
+            .map(|threads| {
+                let mut encoder = format
+                    .deterministic_encoder(Vec::new(), Some(1), threads)
+                    .unwrap();
+                encoder.write_all(&data).unwrap();
+                encoder.finish().unwrap()
+            })
+            .collect();
+
+        assert_eq!(outputs[0], outputs[1], "{:?}", format);
+        assert_eq!(outputs[0], outputs[2], "{:?}", format);
+        assert_eq!(decompress(format, &outputs[0], 1), data, "{:?}", format);
+    }
+}
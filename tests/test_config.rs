@@ -1,4 +1,5 @@
-use mise_s3_cache::config::Config;
+use mise_s3_cache::archive::ArchiveOptions;
+use mise_s3_cache::config::{Config, RemoteCache};
 use std::env;
 use tempfile::TempDir;
 use tokio::fs;
@@ -13,7 +14,19 @@ async fn test_config_default() {
     assert_eq!(config.prefix, "mise-cache");
     assert_eq!(config.ttl_seconds, 604800);
     assert_eq!(config.parallel_uploads, 3);
+    assert_eq!(config.multipart_part_size, 64 * 1024 * 1024);
+    assert_eq!(config.parallel_downloads, 8);
+    assert_eq!(config.download_chunk_size, 16 * 1024 * 1024);
+    assert_eq!(config.upload_limit, 0);
+    assert_eq!(config.download_limit, 0);
+    assert_eq!(config.retry_max_attempts, 5);
+    assert_eq!(config.retry_base_delay_ms, 200);
+    assert!(config.retry_jitter);
     assert_eq!(config.compression, "gzip");
+    assert_eq!(config.storage_mode, "archive");
+    assert!(!config.preserve_xattrs);
+    assert!(!config.deterministic_archives);
+    assert_eq!(config.local_cache_max_size, 5 * 1024 * 1024 * 1024);
 }
 
 #[tokio::test]
@@ -98,6 +111,210 @@ S3_CACHE_DEBUG="true"
     assert!(config.debug);
 }
 
+#[tokio::test]
+async fn test_config_connection_settings() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let toml_path = temp_dir.path().join("config.toml");
+    let toml_content = r#"
+bucket = "minio-bucket"
+endpoint_url = "http://minio.internal:9000"
+force_path_style = true
+profile = "ci"
+role_arn = "arn:aws:iam::123456789012:role/mise-cache"
+"#;
+    fs::write(&toml_path, toml_content).await.unwrap();
+
+    let config = Config::load(Some(toml_path.to_str().unwrap())).unwrap();
+    assert_eq!(
+        config.endpoint_url.as_deref(),
+        Some("http://minio.internal:9000")
+    );
+    assert!(config.force_path_style);
+    assert_eq!(config.profile.as_deref(), Some("ci"));
+    assert_eq!(
+        config.role_arn.as_deref(),
+        Some("arn:aws:iam::123456789012:role/mise-cache")
+    );
+
+    let shell_path = temp_dir.path().join("config.conf");
+    let shell_content = r#"
+S3_CACHE_BUCKET="ceph-bucket"
+S3_CACHE_ENDPOINT_URL="https://ceph.internal"
+S3_CACHE_FORCE_PATH_STYLE="true"
+S3_CACHE_PROFILE="dev"
+S3_CACHE_ROLE_ARN=""
+"#;
+    fs::write(&shell_path, shell_content).await.unwrap();
+
+    let config = Config::load(Some(shell_path.to_str().unwrap())).unwrap();
+    assert_eq!(
+        config.endpoint_url.as_deref(),
+        Some("https://ceph.internal")
+    );
+    assert!(config.force_path_style);
+    assert_eq!(config.profile.as_deref(), Some("dev"));
+    assert_eq!(config.role_arn, None);
+
+    let invalid_path = temp_dir.path().join("invalid.conf");
+    let invalid_content = r#"
+S3_CACHE_BUCKET="ceph-bucket"
+S3_CACHE_ENDPOINT_URL="ceph.internal"
+"#;
+    fs::write(&invalid_path, invalid_content).await.unwrap();
+    assert!(Config::load(Some(invalid_path.to_str().unwrap())).is_err());
+}
+
+#[tokio::test]
+async fn test_config_object_settings() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let toml_path = temp_dir.path().join("config.toml");
+    let toml_content = r#"
+bucket = "secure-bucket"
+server_side_encryption = "aws:kms"
+sse_kms_key_id = "alias/mise-cache"
+storage_class = "INTELLIGENT_TIERING"
+
+[object_tags]
+team = "infra"
+data = "binaries"
+"#;
+    fs::write(&toml_path, toml_content).await.unwrap();
+
+    let config = Config::load(Some(toml_path.to_str().unwrap())).unwrap();
+    assert_eq!(config.server_side_encryption.as_deref(), Some("aws:kms"));
+    assert_eq!(config.sse_kms_key_id.as_deref(), Some("alias/mise-cache"));
+    assert_eq!(config.storage_class.as_deref(), Some("INTELLIGENT_TIERING"));
+    assert_eq!(config.object_tags.len(), 2);
+    assert_eq!(config.object_tags["team"], "infra");
+
+    let shell_path = temp_dir.path().join("config.conf");
+    let shell_content = r#"
+S3_CACHE_BUCKET="secure-bucket"
+S3_CACHE_SSE="AES256"
+S3_CACHE_STORAGE_CLASS="STANDARD_IA"
+S3_CACHE_OBJECT_TAGS="team=infra,env=ci"
+"#;
+    fs::write(&shell_path, shell_content).await.unwrap();
+
+    let config = Config::load(Some(shell_path.to_str().unwrap())).unwrap();
+    assert_eq!(config.server_side_encryption.as_deref(), Some("AES256"));
+    assert_eq!(config.storage_class.as_deref(), Some("STANDARD_IA"));
+    assert_eq!(config.object_tags["env"], "ci");
+
+    // A KMS key only makes sense with SSE-KMS
+    let invalid_path = temp_dir.path().join("invalid.conf");
+    let invalid_content = r#"
+S3_CACHE_BUCKET="secure-bucket"
+S3_CACHE_SSE="AES256"
+S3_CACHE_SSE_KMS_KEY_ID="alias/mise-cache"
+"#;
+    fs::write(&invalid_path, invalid_content).await.unwrap();
+    assert!(Config::load(Some(invalid_path.to_str().unwrap())).is_err());
+
+    let invalid_content = r#"
+S3_CACHE_BUCKET="secure-bucket"
+S3_CACHE_STORAGE_CLASS="CHEAP"
+"#;
+    fs::write(&invalid_path, invalid_content).await.unwrap();
+    assert!(Config::load(Some(invalid_path.to_str().unwrap())).is_err());
+}
+
+#[tokio::test]
+async fn test_config_compression() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("config.conf");
+
+    fs::write(
+        &config_path,
+        "S3_CACHE_BUCKET=\"zstd-bucket\"\nS3_CACHE_COMPRESSION=\"zstd\"\nS3_CACHE_COMPRESSION_LEVEL=\"19\"\n",
+    )
+    .await
+    .unwrap();
+    let config = Config::load(Some(config_path.to_str().unwrap())).unwrap();
+    assert_eq!(config.compression, "zstd");
+    assert_eq!(config.compression_level, Some(19));
+
+    // gzip only goes up to 9
+    fs::write(
+        &config_path,
+        "S3_CACHE_BUCKET=\"gzip-bucket\"\nS3_CACHE_COMPRESSION_LEVEL=\"19\"\n",
+    )
+    .await
+    .unwrap();
+    assert!(Config::load(Some(config_path.to_str().unwrap())).is_err());
+
+    fs::write(
+        &config_path,
+        "S3_CACHE_BUCKET=\"lz4-bucket\"\nS3_CACHE_COMPRESSION=\"lz4\"\n",
+    )
+    .await
+    .unwrap();
+    assert!(Config::load(Some(config_path.to_str().unwrap())).is_err());
+}
+
+#[tokio::test]
+async fn test_config_storage_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("config.conf");
+
+    fs::write(
+        &config_path,
+        "S3_CACHE_BUCKET=\"dedup-bucket\"\nS3_CACHE_STORAGE_MODE=\"chunked\"\n",
+    )
+    .await
+    .unwrap();
+    let config = Config::load(Some(config_path.to_str().unwrap())).unwrap();
+    assert_eq!(config.storage_mode, "chunked");
+
+    fs::write(
+        &config_path,
+        "S3_CACHE_BUCKET=\"dedup-bucket\"\nS3_CACHE_STORAGE_MODE=\"blocks\"\n",
+    )
+    .await
+    .unwrap();
+    assert!(Config::load(Some(config_path.to_str().unwrap())).is_err());
+}
+
+#[tokio::test]
+async fn test_config_exclude_patterns() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("s3-cache.toml");
+
+    fs::write(
+        &config_path,
+        r#"
+bucket = "exclude-bucket"
+exclude = ["**/__pycache__", "share/doc/**"]
+
+[tool_excludes]
+python = ["lib/python3.*/test/**", "**/__pycache__"]
+"#,
+    )
+    .await
+    .unwrap();
+    let config = Config::load(Some(config_path.to_str().unwrap())).unwrap();
+
+    let options = ArchiveOptions::for_tool(&config, "python");
+    assert_eq!(
+        options.exclude,
+        vec!["**/__pycache__", "share/doc/**", "lib/python3.*/test/**"]
+    );
+    assert_eq!(
+        ArchiveOptions::for_tool(&config, "node").exclude,
+        vec!["**/__pycache__", "share/doc/**"]
+    );
+
+    fs::write(
+        &config_path,
+        "bucket = \"exclude-bucket\"\nexclude = [\"lib/[\"]\n",
+    )
+    .await
+    .unwrap();
+    assert!(Config::load(Some(config_path.to_str().unwrap())).is_err());
+}
+
 #[tokio::test]
 async fn test_config_validation() {
     // Test valid config
@@ -123,6 +340,57 @@ async fn test_config_validation() {
     assert!(config.is_err());
 }
 
+#[tokio::test]
+async fn test_config_remote_caches_from_toml_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+
+    let toml_content = r#"
+bucket = "regional-bucket"
+prefix = "regional"
+
+[[fallbacks]]
+bucket = "global-bucket"
+region = "us-east-1"
+
+[[fallbacks]]
+bucket = "file:///mnt/shared/mise-cache"
+prefix = "shared"
+
+[[replicas]]
+bucket = "eu-west-bucket"
+region = "eu-west-1"
+"#;
+
+    fs::write(&config_path, toml_content).await.unwrap();
+
+    let config = Config::load(Some(config_path.to_str().unwrap())).unwrap();
+
+    assert_eq!(config.fallbacks.len(), 2);
+    assert_eq!(config.fallbacks[0].bucket, "global-bucket");
+
+    let global = config.for_remote(&config.fallbacks[0]);
+    assert_eq!(global.bucket, "global-bucket");
+    assert_eq!(global.prefix, config.prefix);
+    assert_eq!(global.region, "us-east-1");
+
+    let shared = config.for_remote(&config.fallbacks[1]);
+    assert_eq!(shared.prefix, "shared");
+
+    assert_eq!(config.replicas.len(), 1);
+    assert_eq!(config.for_remote(&config.replicas[0]).region, "eu-west-1");
+}
+
+#[test]
+fn test_remote_cache_parse_list() {
+    let remotes = RemoteCache::parse_list("global-bucket, file:///mnt/cache,,");
+
+    assert_eq!(remotes.len(), 2);
+    assert_eq!(remotes[0].bucket, "global-bucket");
+    assert_eq!(remotes[1].bucket, "file:///mnt/cache");
+    assert!(remotes[0].prefix.is_none());
+}
+
 #[test]
 fn test_get_cache_key() {
     let config = Config {
@@ -0,0 +1,79 @@
+use mise_s3_cache::encryption::{DecryptingReader, EncryptingWriter, EncryptionKey, SEGMENT_SIZE};
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+const OTHER_KEY: &str = "ff0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+fn key(hex: &str) -> Arc<EncryptionKey> {
+    Arc::new(EncryptionKey::from_hex(hex).unwrap())
+}
+
+fn encrypt(data: &[u8], key: Arc<EncryptionKey>) -> Vec<u8> {
+    let mut writer = EncryptingWriter::new(Vec::new(), key).unwrap();
+    // Odd write sizes so segments do not line up with writes
+    for piece in data.chunks(10_007) {
+        writer.write_all(piece).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+fn decrypt(data: &[u8], key: Arc<EncryptionKey>) -> std::io::Result<Vec<u8>> {
+    let mut plaintext = Vec::new();
+    DecryptingReader::new(data, key)?.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+#[test]
+fn test_encryption_key_parsing() {
+    let key = EncryptionKey::from_hex(&format!("{}\n", KEY)).unwrap();
+    assert_eq!(key.id().len(), 16);
+    assert_ne!(key.id(), EncryptionKey::from_hex(OTHER_KEY).unwrap().id());
+
+    assert!(EncryptionKey::from_hex("abcd").is_err());
+    assert!(EncryptionKey::from_hex("not hex at all").is_err());
+}
+
+#[test]
+fn test_encryption_round_trip() {
+    for size in [
+        0,
+        1,
+        SEGMENT_SIZE - 1,
+        SEGMENT_SIZE,
+        SEGMENT_SIZE + 1,
+        3 * SEGMENT_SIZE,
+        3 * SEGMENT_SIZE + 123,
+    ] {
+        let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let sealed = encrypt(&data, key(KEY));
+        // Header, then one tag per segment; empty input still has one segment
+        let segments = size.div_ceil(SEGMENT_SIZE).max(1);
+        assert_eq!(sealed.len(), 12 + size + 16 * segments, "size {}", size);
+        assert_eq!(decrypt(&sealed, key(KEY)).unwrap(), data, "size {}", size);
+    }
+}
+
+#[test]
+fn test_encryption_nonce_is_random() {
+    let data = vec![7u8; 1000];
+    assert_ne!(encrypt(&data, key(KEY)), encrypt(&data, key(KEY)));
+}
+
+#[test]
+fn test_decryption_rejects_wrong_key_and_tampering() {
+    let data = vec![42u8; 2 * SEGMENT_SIZE + 500];
+    let sealed = encrypt(&data, key(KEY));
+
+    assert!(decrypt(&sealed, key(OTHER_KEY)).is_err());
+
+    let mut tampered = sealed.clone();
+    tampered[SEGMENT_SIZE] ^= 1;
+    assert!(decrypt(&tampered, key(KEY)).is_err());
+
+    // Dropping the final segment must not pass for a shorter archive
+    let truncated = &sealed[..sealed.len() - 516];
+    assert!(decrypt(truncated, key(KEY)).is_err());
+
+    assert!(decrypt(b"\x1f\x8b plain gzip", key(KEY)).is_err());
+}
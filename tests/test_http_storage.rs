@@ -0,0 +1,136 @@
+use mise_s3_cache::bandwidth::RateLimiter;
+use mise_s3_cache::http_storage::HttpStorage;
+use mise_s3_cache::storage::StorageBackend;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Serve `files` over HTTP/1.1 on a random local port and return the base URL
+async fn serve(files: HashMap<String, Vec<u8>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let files = Arc::new(files);
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let files = files.clone();
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 8192];
+                let n = socket.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let mut parts = request.split_whitespace();
+                let method = parts.next().unwrap_or("").to_string();
+                let path = parts.next().unwrap_or("").to_string();
+
+                let (status, body) = match files.get(&path) {
+                    Some(body) => ("200 OK", body.clone()),
+                    None => ("404 Not Found", Vec::new()),
+                };
+
+                let mut response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .into_bytes();
+                if method == "GET" {
+                    response.extend_from_slice(&body);
+                }
+                let _ = socket.write_all(&response).await;
+            });
+        }
+    });
+
+    format!("http://{}/mirror", addr)
+}
+
+#[tokio::test]
+async fn test_http_storage_reads_objects() {
+    let mut files = HashMap::new();
+    files.insert(
+        "/mirror/mise-cache/tools/node/18.17.0/linux-x86_64/checksum.sha256".to_string(),
+        b"abc123".to_vec(),
+    );
+    files.insert(
+        "/mirror/mise-cache/tools/node/18.17.0/linux-x86_64/archive.tar.gz".to_string(),
+        b"archive bytes".to_vec(),
+    );
+    let base_url = serve(files).await;
+    let storage = HttpStorage::new(&format!("{}/", base_url)).unwrap();
+
+    let prefix = "mise-cache/tools/node/18.17.0/linux-x86_64";
+    assert!(storage
+        .exists(&format!("{}/checksum.sha256", prefix))
+        .await
+        .unwrap());
+    assert!(!storage
+        .exists(&format!("{}/metadata.json", prefix))
+        .await
+        .unwrap());
+    assert_eq!(
+        storage
+            .get_string(&format!("{}/checksum.sha256", prefix))
+            .await
+            .unwrap(),
+        "abc123"
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    let local_path = temp_dir.path().join("archive.tar.gz");
+    storage
+        .get(&format!("{}/archive.tar.gz", prefix), &local_path)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&local_path).unwrap(), b"archive bytes");
+
+    assert!(storage
+        .get_string(&format!("{}/metadata.json", prefix))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_http_storage_encodes_key_segments() {
+    let mut files = HashMap::new();
+    files.insert(
+        "/mirror/mise-cache/tools/tool/1.0.0%2Bbuild%201/linux-x86_64/checksum.sha256".to_string(),
+        b"abc123".to_vec(),
+    );
+    let storage = HttpStorage::new(&serve(files).await).unwrap();
+
+    let key = "mise-cache/tools/tool/1.0.0+build 1/linux-x86_64/checksum.sha256";
+    assert!(storage.exists(key).await.unwrap());
+    assert_eq!(storage.get_string(key).await.unwrap(), "abc123");
+}
+
+#[tokio::test]
+async fn test_http_storage_throttles_downloads() {
+    let mut files = HashMap::new();
+    files.insert("/mirror/archive.tar.gz".to_string(), vec![7u8; 40_000]);
+    let storage = HttpStorage::new(&serve(files).await)
+        .unwrap()
+        .with_download_limit(RateLimiter::shared(20_000));
+
+    // One second of burst, then the rest trickles in at the limit
+    let temp_dir = TempDir::new().unwrap();
+    let local_path = temp_dir.path().join("archive.tar.gz");
+    let start = Instant::now();
+    storage.get("archive.tar.gz", &local_path).await.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    assert_eq!(std::fs::read(&local_path).unwrap().len(), 40_000);
+}
+
+#[tokio::test]
+async fn test_http_storage_is_read_only() {
+    let base_url = serve(HashMap::new()).await;
+    let storage = HttpStorage::new(&base_url).unwrap();
+
+    storage.test_connectivity().await.unwrap();
+    assert!(storage.put_string("x", "some/key").await.is_err());
+    assert!(storage.delete("some/key").await.is_err());
+    assert!(storage.list("some").await.is_err());
+}
@@ -0,0 +1,93 @@
+use filetime::FileTime;
+use mise_s3_cache::local_cache::LocalCache;
+use tempfile::TempDir;
+
+const ARCHIVE_KEY: &str = "mise-cache/tools/node/20.0.0/linux-x86_64/archive.tar.gz";
+
+#[tokio::test]
+async fn test_local_cache_insert_and_lookup() {
+    let cache_dir = TempDir::new().unwrap();
+    let cache = LocalCache::new(cache_dir.path(), 1024 * 1024);
+
+    let source_dir = TempDir::new().unwrap();
+    let archive = source_dir.path().join("archive.tar.gz");
+    std::fs::write(&archive, b"archive bytes").unwrap();
+
+    assert!(cache.lookup(ARCHIVE_KEY, 13).await.is_none());
+
+    cache.insert(ARCHIVE_KEY, &archive).await.unwrap();
+    let hit = cache.lookup(ARCHIVE_KEY, 13).await.unwrap();
+    assert_eq!(std::fs::read(hit).unwrap(), b"archive bytes");
+    assert_eq!(cache.size(), 13);
+}
+
+#[tokio::test]
+async fn test_local_cache_evicts_stale_size() {
+    let cache_dir = TempDir::new().unwrap();
+    let cache = LocalCache::new(cache_dir.path(), 1024 * 1024);
+
+    let source_dir = TempDir::new().unwrap();
+    let archive = source_dir.path().join("archive.tar.gz");
+    std::fs::write(&archive, b"old bytes").unwrap();
+    cache.insert(ARCHIVE_KEY, &archive).await.unwrap();
+
+    // The remote entry was replaced, so the local copy must not be used
+    assert!(cache.lookup(ARCHIVE_KEY, 13).await.is_none());
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_local_cache_evict() {
+    let cache_dir = TempDir::new().unwrap();
+    let cache = LocalCache::new(cache_dir.path(), 1024 * 1024);
+
+    let source_dir = TempDir::new().unwrap();
+    let archive = source_dir.path().join("archive.tar.gz");
+    std::fs::write(&archive, b"bytes").unwrap();
+    cache.insert(ARCHIVE_KEY, &archive).await.unwrap();
+
+    cache.evict(ARCHIVE_KEY).await;
+    assert!(cache.lookup(ARCHIVE_KEY, 5).await.is_none());
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_local_cache_evicts_least_recently_used() {
+    let cache_dir = TempDir::new().unwrap();
+    let cache = LocalCache::new(cache_dir.path(), 25);
+
+    let source_dir = TempDir::new().unwrap();
+    let archive = source_dir.path().join("archive.tar.gz");
+    std::fs::write(&archive, [0u8; 10]).unwrap();
+
+    cache.insert("old/archive.tar.gz", &archive).await.unwrap();
+    cache.insert("used/archive.tar.gz", &archive).await.unwrap();
+
+    // Age both entries, then touch "used" so "old" is the LRU entry
+    for name in ["old-archive.tar.gz", "used-archive.tar.gz"] {
+        filetime::set_file_mtime(
+            cache_dir.path().join(name),
+            FileTime::from_unix_time(1_000_000, 0),
+        )
+        .unwrap();
+    }
+    assert!(cache.lookup("used/archive.tar.gz", 10).await.is_some());
+
+    cache.insert("new/archive.tar.gz", &archive).await.unwrap();
+
+    assert!(!cache_dir.path().join("old-archive.tar.gz").exists());
+    assert!(cache_dir.path().join("used-archive.tar.gz").exists());
+    assert!(cache_dir.path().join("new-archive.tar.gz").exists());
+}
+
+#[tokio::test]
+async fn test_local_cache_disabled() {
+    let cache_dir = TempDir::new().unwrap();
+    let cache = LocalCache::new(cache_dir.path(), 0);
+
+    let archive = cache_dir.path().join("source.tar.gz");
+    std::fs::write(&archive, b"bytes").unwrap();
+    cache.insert(ARCHIVE_KEY, &archive).await.unwrap();
+
+    assert!(cache.lookup(ARCHIVE_KEY, 5).await.is_none());
+}
@@ -0,0 +1,252 @@
+use bytes::Bytes;
+use mise_s3_cache::bandwidth::RateLimits;
+use mise_s3_cache::config::Config;
+use mise_s3_cache::local_storage::{LocalStorage, STALE_TEMP_AGE};
+use mise_s3_cache::storage::{self, StorageBackend};
+use mise_s3_cache::streaming;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+use tokio::fs;
+
+#[tokio::test]
+async fn test_local_storage_round_trip() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+
+    let key = "mise-cache/tools/node/18.17.0/linux-x86_64/metadata.json";
+    assert!(!storage.exists(key).await.unwrap());
+
+    storage.put_string("{}", key).await.unwrap();
+    assert!(storage.exists(key).await.unwrap());
+    assert_eq!(storage.get_string(key).await.unwrap(), "{}");
+
+    // Objects follow the key layout on disk
+    assert!(root.path().join(key).is_file());
+
+    let source = TempDir::new().unwrap();
+    let local_file = source.path().join("archive.tar.gz");
+    fs::write(&local_file, b"archive bytes").await.unwrap();
+
+    let archive_key = "mise-cache/tools/node/18.17.0/linux-x86_64/archive.tar.gz";
+    storage.put(&local_file, archive_key).await.unwrap();
+
+    let download = source.path().join("download/archive.tar.gz");
+    storage.get(archive_key, &download).await.unwrap();
+    assert_eq!(fs::read(&download).await.unwrap(), b"archive bytes");
+
+    storage.delete(key).await.unwrap();
+    assert!(!storage.exists(key).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_local_storage_list_and_size() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+
+    storage
+        .put_string(
+            "aaaa",
+            "mise-cache/tools/node/18.17.0/linux-x86_64/checksum.sha256",
+        )
+        .await
+        .unwrap();
+    storage
+        .put_string(
+            "bb",
+            "mise-cache/tools/python/3.11.0/linux-x86_64/checksum.sha256",
+        )
+        .await
+        .unwrap();
+    storage
+        .put_string(
+            "c",
+            "other-prefix/tools/node/1.0.0/linux-x86_64/checksum.sha256",
+        )
+        .await
+        .unwrap();
+
+    let objects = storage.list("mise-cache/tools").await.unwrap();
+    assert_eq!(objects.len(), 2);
+    assert!(objects.iter().all(|o| o.last_modified > 0));
+
+    assert_eq!(storage.size("mise-cache/tools/node").await.unwrap(), 4);
+    assert!(storage.list("missing/").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_local_storage_refresh() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+    let key = "mise-cache/chunks/ab/abcd.zst";
+    storage.put_string("chunk", key).await.unwrap();
+
+    let path = root.path().join(key);
+    let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(week_ago)
+        .unwrap();
+
+    assert!(storage.refresh(key).await.unwrap());
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    assert!(modified > week_ago + Duration::from_secs(60));
+    assert_eq!(storage.get_string(key).await.unwrap(), "chunk");
+
+    assert!(!storage
+        .refresh("mise-cache/chunks/cd/cdef.zst")
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn test_local_storage_list_removes_abandoned_temp_files() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+    storage
+        .put_string("{}", "mise-cache/tools/node/metadata.json")
+        .await
+        .unwrap();
+
+    let dir = root.path().join("mise-cache/tools/node");
+    let abandoned = dir.join(".archive.tar.gz.1234.tmp");
+    let in_flight = dir.join(".archive.tar.gz.5678.tmp");
+    std::fs::write(&abandoned, "partial").unwrap();
+    std::fs::write(&in_flight, "partial").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&abandoned)
+        .unwrap()
+        .set_modified(SystemTime::now() - STALE_TEMP_AGE - Duration::from_secs(60))
+        .unwrap();
+
+    let objects = storage.list("mise-cache/").await.unwrap();
+    assert_eq!(objects.len(), 1);
+    assert!(!abandoned.exists());
+    assert!(in_flight.exists());
+}
+
+#[tokio::test]
+async fn test_local_storage_rejects_path_traversal() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+
+    assert!(storage.put_string("x", "../escape").await.is_err());
+    assert!(storage.get_string("/etc/passwd").await.is_err());
+}
+
+#[tokio::test]
+async fn test_create_backend_for_file_url() {
+    let root = TempDir::new().unwrap();
+    let config = Config {
+        bucket: format!("file://{}", root.path().display()),
+        ..Default::default()
+    };
+
+    let backend = storage::create_backend(&config, &RateLimits::default())
+        .await
+        .unwrap();
+    assert_eq!(backend.location(), config.bucket);
+    backend.test_connectivity().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_local_storage_put_stream() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+    let key = "mise-cache/tools/node/18.17.0/linux-x86_64/archive.tar.gz";
+
+    let (tx, rx) = streaming::chunk_channel();
+    tx.send(Ok(Bytes::from_static(b"archive "))).await.unwrap();
+    tx.send(Ok(Bytes::from_static(b"bytes"))).await.unwrap();
+    drop(tx);
+    assert_eq!(storage.put_stream(key, rx).await.unwrap(), 13);
+    assert_eq!(storage.get_string(key).await.unwrap(), "archive bytes");
+
+    // A failed producer leaves the previous object untouched
+    let (tx, rx) = streaming::chunk_channel();
+    tx.send(Ok(Bytes::from_static(b"trunc"))).await.unwrap();
+    tx.send(Err(anyhow::anyhow!("archive failed")))
+        .await
+        .unwrap();
+    drop(tx);
+    assert!(storage.put_stream(key, rx).await.is_err());
+    assert_eq!(storage.get_string(key).await.unwrap(), "archive bytes");
+    assert_eq!(storage.list("mise-cache/").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_local_storage_get_stream() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+    let key = "mise-cache/tools/node/18.17.0/linux-x86_64/archive.tar.gz";
+    let data = vec![7u8; streaming::CHUNK_SIZE + 10];
+    fs::create_dir_all(root.path().join(key).parent().unwrap())
+        .await
+        .unwrap();
+    fs::write(root.path().join(key), &data).await.unwrap();
+
+    let (tx, mut rx) = streaming::chunk_channel();
+    let sent = tokio::spawn(async move { storage.get_stream(key, tx).await.unwrap() });
+
+    let mut received = Vec::new();
+    while let Some(chunk) = rx.recv().await {
+        received.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(sent.await.unwrap(), data.len() as u64);
+    assert_eq!(received, data);
+}
+
+/// Every file below `dir`, including hidden temporary ones
+fn files_below(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(files_below(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
+#[tokio::test]
+async fn test_local_storage_failed_put_leaves_no_temp_file() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+    let key = "mise-cache/tools/node/18.17.0/linux-x86_64/archive.tar.gz";
+
+    // A directory opens but cannot be read as a file
+    let source = TempDir::new().unwrap();
+    assert!(storage.put(source.path(), key).await.is_err());
+    assert!(files_below(root.path()).is_empty());
+}
+
+#[tokio::test]
+async fn test_local_storage_delete_prunes_empty_directories() {
+    let root = TempDir::new().unwrap();
+    let storage = LocalStorage::new(root.path());
+    let metadata_key = "mise-cache/tools/node/18.17.0/linux-x86_64/metadata.json";
+    let archive_key = "mise-cache/tools/node/18.17.0/linux-x86_64/archive.tar.gz";
+    let other_key = "mise-cache/tools/node/20.0.0/linux-x86_64/metadata.json";
+    for key in [metadata_key, archive_key, other_key] {
+        storage.put_string("{}", key).await.unwrap();
+    }
+
+    storage.delete(metadata_key).await.unwrap();
+    assert!(root.path().join("mise-cache/tools/node/18.17.0").is_dir());
+
+    storage.delete(archive_key).await.unwrap();
+    assert!(!root.path().join("mise-cache/tools/node/18.17.0").exists());
+    assert!(root.path().join("mise-cache/tools/node/20.0.0").is_dir());
+
+    storage.delete(other_key).await.unwrap();
+    assert!(root.path().exists());
+    assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 0);
+
+    // Writing again recreates the directories
+    storage.put_string("{}", other_key).await.unwrap();
+    assert_eq!(storage.get_string(other_key).await.unwrap(), "{}");
+}
@@ -0,0 +1,115 @@
+use mise_s3_cache::archive::{self, ArchiveOptions};
+use mise_s3_cache::manifest::{self, Relocation};
+use mise_s3_cache::relocate;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_manifest_matches_unchanged_tree() {
+    let root = TempDir::new().unwrap();
+    let options = ArchiveOptions::default();
+    archive::create_sample_tree(root.path(), &options).unwrap();
+
+    let entries = manifest::build(root.path(), &options).unwrap();
+    let tool = entries
+        .iter()
+        .find(|entry| entry.path == "bin/tool")
+        .unwrap();
+    assert_eq!(tool.size, 22);
+    assert!(tool.hash.is_some());
+    #[cfg(unix)]
+    {
+        assert_eq!(tool.mode, Some(0o755));
+        let alias = entries
+            .iter()
+            .find(|entry| entry.path == "bin/tool-alias")
+            .unwrap();
+        assert_eq!(alias.hash, tool.hash);
+        let link = entries
+            .iter()
+            .find(|entry| entry.path == "lib/libsample.so")
+            .unwrap();
+        assert_eq!(link.target.as_deref(), Some("libsample.so.1.2.3"));
+    }
+    assert!(entries.iter().all(|entry| entry.path != "lib/private"));
+
+    let report = manifest::verify(root.path(), &entries, &options, None).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+}
+
+#[test]
+fn test_verify_reports_modified_missing_and_extra() {
+    let root = TempDir::new().unwrap();
+    let options = ArchiveOptions {
+        exclude: vec!["**/__pycache__/**".to_string()],
+        ..Default::default()
+    };
+    archive::create_sample_tree(root.path(), &options).unwrap();
+    let entries = manifest::build(root.path(), &options).unwrap();
+
+    fs::write(root.path().join("lib/libsample.so.1.2.3"), "patched\n").unwrap();
+    fs::remove_file(root.path().join("share/doc/README")).unwrap();
+    fs::write(root.path().join("bin/injected"), "#!/bin/sh\n").unwrap();
+    // Excluded from the archive, so not extra either
+    fs::create_dir_all(root.path().join("lib/__pycache__")).unwrap();
+    fs::write(root.path().join("lib/__pycache__/os.pyc"), "cache").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(
+            root.path().join("lib/private/data"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+    }
+
+    let report = manifest::verify(root.path(), &entries, &options, None).unwrap();
+
+    let modified: Vec<_> = report
+        .modified
+        .iter()
+        .map(|file| (file.path.as_str(), file.change.as_str()))
+        .collect();
+    assert!(modified.contains(&("lib/libsample.so.1.2.3", "contents differ")));
+    #[cfg(unix)]
+    assert!(modified.contains(&("lib/private/data", "mode 644 instead of 600")));
+    assert_eq!(report.missing, vec!["share/doc/README".to_string()]);
+    assert_eq!(report.extra, vec!["bin/injected".to_string()]);
+}
+
+#[test]
+fn test_verify_undoes_relocation() {
+    let old = "/home/alice/.local/share/mise/installs/python/3.11.0";
+    let new = "/opt/mise/installs/python/3.11.0";
+    let root = TempDir::new().unwrap();
+    fs::create_dir_all(root.path().join("bin")).unwrap();
+    fs::create_dir_all(root.path().join("lib")).unwrap();
+    fs::write(
+        root.path().join("bin/pip"),
+        format!("#!{}/bin/python3\n", old),
+    )
+    .unwrap();
+    let mut binary = b"\x7fELF\0".to_vec();
+    binary.extend_from_slice(format!("{}/lib\0tail\0", old).as_bytes());
+    fs::write(root.path().join("lib/libpython3.so"), binary).unwrap();
+    #[cfg(unix)]
+    fs::hard_link(root.path().join("bin/pip"), root.path().join("bin/pip3.11")).unwrap();
+
+    let options = ArchiveOptions::default();
+    let entries = manifest::build(root.path(), &options).unwrap();
+    let scan = relocate::scan(root.path(), old, &options).unwrap();
+    relocate::relocate(root.path(), &scan.references, old, new).unwrap();
+
+    let relocation = Relocation {
+        old,
+        new,
+        references: &scan.references,
+    };
+    let report = manifest::verify(root.path(), &entries, &options, Some(relocation)).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+
+    // Changes besides the relocation still show
+    fs::write(root.path().join("bin/pip"), format!("#!{}/bin/evil\n", new)).unwrap();
+    let report = manifest::verify(root.path(), &entries, &options, Some(relocation)).unwrap();
+    assert_eq!(report.modified.len(), if cfg!(unix) { 2 } else { 1 });
+}
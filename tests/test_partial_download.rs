@@ -0,0 +1,144 @@
+use bytes::Bytes;
+use filetime::FileTime;
+use mise_s3_cache::partial_download::{remove_abandoned, PartialDownload};
+use tempfile::TempDir;
+
+const KEY: &str = "bucket/mise-cache/tools/node/20.0.0/linux-x86_64/archive.tar.gz";
+
+#[tokio::test]
+async fn test_partial_download_resumes_completed_chunks() {
+    let dir = TempDir::new().unwrap();
+
+    let mut partial = PartialDownload::open(dir.path(), KEY, "\"etag-1\"", 10, 4)
+        .await
+        .unwrap();
+    assert_eq!(partial.chunk_count(), 3);
+    assert_eq!(partial.chunk_range(2), (8, 2));
+
+    partial
+        .write_chunk(0, Bytes::from_static(b"abcd"))
+        .await
+        .unwrap();
+    partial
+        .write_chunk(2, Bytes::from_static(b"ij"))
+        .await
+        .unwrap();
+    assert!(partial
+        .write_chunk(1, Bytes::from_static(b"too long"))
+        .await
+        .is_err());
+    drop(partial);
+
+    // A later attempt picks up where the first one stopped
+    let mut partial = PartialDownload::open(dir.path(), KEY, "\"etag-1\"", 10, 4)
+        .await
+        .unwrap();
+    assert_eq!(partial.missing_chunks(), vec![1]);
+    assert_eq!(partial.completed_bytes(), 6);
+    assert_eq!(partial.read_chunk(2).await.unwrap(), b"ij");
+
+    partial
+        .write_chunk(1, Bytes::from_static(b"efgh"))
+        .await
+        .unwrap();
+    assert!(partial.is_finished());
+
+    let target = dir.path().join("out/archive.tar.gz");
+    partial.finish_into(&target).await.unwrap();
+    assert_eq!(std::fs::read(&target).unwrap(), b"abcdefghij");
+
+    // Only the finished download remains
+    let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(leftovers, vec!["out"]);
+}
+
+#[tokio::test]
+async fn test_partial_download_discards_progress_for_changed_object() {
+    let dir = TempDir::new().unwrap();
+
+    let mut partial = PartialDownload::open(dir.path(), KEY, "\"etag-1\"", 10, 4)
+        .await
+        .unwrap();
+    partial
+        .write_chunk(0, Bytes::from_static(b"abcd"))
+        .await
+        .unwrap();
+    drop(partial);
+
+    let partial = PartialDownload::open(dir.path(), KEY, "\"etag-2\"", 10, 4)
+        .await
+        .unwrap();
+    assert_eq!(partial.missing_chunks(), vec![0, 1, 2]);
+    assert_eq!(partial.etag(), "\"etag-2\"");
+
+    partial.remove().await.unwrap();
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_partial_download_concurrent_positional_writes() {
+    let dir = TempDir::new().unwrap();
+
+    let mut partial = PartialDownload::open(dir.path(), KEY, "\"etag-1\"", 10, 4)
+        .await
+        .unwrap();
+
+    // Ranges arrive out of order and in pieces through a shared handle
+    let part = partial.part_file();
+    let writes = [(8, "ij"), (4, "ef"), (0, "abcd"), (6, "gh")]
+        .into_iter()
+        .map(|(offset, data)| {
+            let part = part.clone();
+            tokio::spawn(async move { part.write_at(offset, Bytes::from(data)).await })
+        });
+    for write in writes {
+        write.await.unwrap().unwrap();
+    }
+
+    // Written bytes only count once their chunk is marked complete
+    assert_eq!(partial.missing_chunks(), vec![0, 1, 2]);
+    for index in 0..3 {
+        partial.mark_complete(index).await.unwrap();
+    }
+    assert!(partial.is_finished());
+    assert_eq!(part.read_at(2, 6).await.unwrap(), b"cdefgh");
+
+    let target = dir.path().join("archive.tar.gz");
+    partial.finish_into(&target).await.unwrap();
+    assert_eq!(std::fs::read(&target).unwrap(), b"abcdefghij");
+}
+
+#[tokio::test]
+async fn test_remove_abandoned_keeps_downloads_in_progress() {
+    let dir = TempDir::new().unwrap();
+
+    let partial = PartialDownload::open(dir.path(), KEY, "\"etag-1\"", 10, 4)
+        .await
+        .unwrap();
+    drop(partial);
+    let abandoned = PartialDownload::open(dir.path(), "bucket/old", "\"etag-1\"", 10, 4)
+        .await
+        .unwrap();
+    drop(abandoned);
+    for name in ["bucket-old.part", "bucket-old.progress.json"] {
+        filetime::set_file_mtime(
+            dir.path().join(name),
+            FileTime::from_unix_time(1_000_000, 0),
+        )
+        .unwrap();
+    }
+
+    assert_eq!(remove_abandoned(dir.path()).await.unwrap(), 2);
+    let partial = PartialDownload::open(dir.path(), KEY, "\"etag-1\"", 10, 4)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    partial.remove().await.unwrap();
+
+    // A directory that was never created holds nothing to remove
+    let missing = dir.path().join("missing");
+    assert_eq!(remove_abandoned(&missing).await.unwrap(), 0);
+}
@@ -0,0 +1,185 @@
+use mise_s3_cache::archive::{ArchiveOptions, TreeVisitor};
+use mise_s3_cache::relocate::{self, PrefixKind, PrefixReference, PrefixScan, PrefixScanner};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+const OLD: &str = "/home/alice/.local/share/mise/installs/python/3.11.0";
+
+fn kind_of(references: &[PrefixReference], path: &str) -> Option<PrefixKind> {
+    references
+        .iter()
+        .find(|reference| reference.path == path)
+        .map(|reference| reference.kind)
+}
+
+/// Scan one file whose contents reach the scanner `chunk` bytes at a time
+fn scan_in_chunks(root: &Path, data: &[u8], chunk: usize) -> PrefixScan {
+    fs::write(root.join("file"), data).unwrap();
+    let meta = fs::metadata(root.join("file")).unwrap();
+
+    let mut scanner = PrefixScanner::new(OLD);
+    scanner.file_start(Path::new("file"), &meta);
+    for piece in data.chunks(chunk) {
+        scanner.file_data(piece);
+    }
+    scanner.file_end().unwrap();
+    scanner.finish()
+}
+
+fn populate(root: &Path) {
+    fs::create_dir_all(root.join("bin")).unwrap();
+    fs::create_dir_all(root.join("lib/pkgconfig")).unwrap();
+    fs::write(root.join("bin/pip"), format!("#!{}/bin/python3\n", OLD)).unwrap();
+    fs::write(
+        root.join("lib/pkgconfig/python3.pc"),
+        format!("prefix={}\nlibdir={}/lib\n", OLD, OLD),
+    )
+    .unwrap();
+    fs::write(root.join("bin/unrelated"), "#!/bin/sh\n").unwrap();
+
+    // A C string in a binary, and a length-prefixed one that cannot be padded
+    let mut binary = b"\x7fELF\0".to_vec();
+    binary.extend_from_slice(format!("{}/lib:{}/lib64", OLD, OLD).as_bytes());
+    binary.extend_from_slice(b"\0tail\0");
+    fs::write(root.join("lib/libpython3.so"), binary).unwrap();
+
+    let mut marshalled = b"\xe3\0\0\0\x34".to_vec();
+    marshalled.extend_from_slice(format!("{}/lib/os.py", OLD).as_bytes());
+    marshalled.extend_from_slice(b"\0");
+    fs::write(root.join("lib/os.pyc"), marshalled).unwrap();
+}
+
+#[test]
+fn test_scan_classifies_references() {
+    let root = TempDir::new().unwrap();
+    populate(root.path());
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(format!("{}/bin/pip", OLD), root.path().join("bin/pip3")).unwrap();
+
+    let scan = relocate::scan(root.path(), OLD, &ArchiveOptions::default()).unwrap();
+
+    assert_eq!(kind_of(&scan.references, "bin/pip"), Some(PrefixKind::Text));
+    assert_eq!(
+        kind_of(&scan.references, "lib/pkgconfig/python3.pc"),
+        Some(PrefixKind::Text)
+    );
+    assert_eq!(
+        kind_of(&scan.references, "lib/libpython3.so"),
+        Some(PrefixKind::Binary)
+    );
+    #[cfg(unix)]
+    assert_eq!(
+        kind_of(&scan.references, "bin/pip3"),
+        Some(PrefixKind::Symlink)
+    );
+    assert_eq!(kind_of(&scan.references, "bin/unrelated"), None);
+    assert_eq!(scan.unrelocatable, vec!["lib/os.pyc".to_string()]);
+}
+
+#[test]
+fn test_scan_finds_prefix_across_read_buffers() {
+    let root = TempDir::new().unwrap();
+    let split = relocate::SEARCH_BUFFER_SIZE - OLD.len() / 2;
+
+    // The path straddles the end of the first buffer read
+    let mut straddling = vec![b'#'; split];
+    straddling.extend_from_slice(format!("{}/bin/python3\n", OLD).as_bytes());
+    fs::write(root.path().join("straddling"), straddling).unwrap();
+
+    // Only the start of the path ends the first buffer
+    let mut partial = vec![b'#'; split];
+    partial.extend_from_slice(&OLD.as_bytes()[..OLD.len() / 2]);
+    partial.extend_from_slice(&vec![b'#'; relocate::SEARCH_BUFFER_SIZE]);
+    fs::write(root.path().join("partial"), partial).unwrap();
+
+    let scan = relocate::scan(root.path(), OLD, &ArchiveOptions::default()).unwrap();
+
+    assert_eq!(
+        kind_of(&scan.references, "straddling"),
+        Some(PrefixKind::Text)
+    );
+    assert_eq!(kind_of(&scan.references, "partial"), None);
+}
+
+#[test]
+fn test_relocate_rewrites_text_and_pads_binaries() {
+    let root = TempDir::new().unwrap();
+    populate(root.path());
+    let original_size = fs::metadata(root.path().join("lib/libpython3.so"))
+        .unwrap()
+        .len();
+    let scan = relocate::scan(root.path(), OLD, &ArchiveOptions::default()).unwrap();
+
+    let new = "/home/ci/.local/share/mise/installs/python/3.11.0";
+    let skipped = relocate::relocate(root.path(), &scan.references, OLD, new).unwrap();
+    assert!(skipped.is_empty());
+
+    assert_eq!(
+        fs::read_to_string(root.path().join("bin/pip")).unwrap(),
+        format!("#!{}/bin/python3\n", new)
+    );
+    let binary = fs::read(root.path().join("lib/libpython3.so")).unwrap();
+    assert_eq!(binary.len() as u64, original_size);
+    let expected = format!("{}/lib:{}/lib64\0", new, new);
+    assert!(binary
+        .windows(expected.len())
+        .any(|window| window == expected.as_bytes()));
+    assert!(binary.ends_with(b"\0tail\0"));
+}
+
+#[test]
+fn test_relocate_skips_binaries_for_longer_prefix() {
+    let root = TempDir::new().unwrap();
+    populate(root.path());
+    let scan = relocate::scan(root.path(), OLD, &ArchiveOptions::default()).unwrap();
+    let binary = fs::read(root.path().join("lib/libpython3.so")).unwrap();
+
+    let new = "/home/runner-with-a-long-name/.local/share/mise/installs/python/3.11.0";
+    let skipped = relocate::relocate(root.path(), &scan.references, OLD, new).unwrap();
+
+    assert_eq!(skipped, vec!["lib/libpython3.so".to_string()]);
+    assert_eq!(
+        fs::read(root.path().join("lib/libpython3.so")).unwrap(),
+        binary
+    );
+    assert!(fs::read_to_string(root.path().join("bin/pip"))
+        .unwrap()
+        .contains(new));
+}
+
+#[test]
+fn test_scan_finds_references_split_between_reads() {
+    let root = TempDir::new().unwrap();
+    let string = |before: &[u8], after: &[u8]| {
+        let mut data = before.to_vec();
+        data.extend_from_slice(format!("{}/lib", OLD).as_bytes());
+        data.extend_from_slice(after);
+        data
+    };
+    let cases = [
+        (string(b"#!", b"/python3\n"), Some(PrefixKind::Text)),
+        (
+            string(b"\x7fELF\0", b":/usr/lib\0tail\0"),
+            Some(PrefixKind::Binary),
+        ),
+        // Length-prefixed, not NUL-terminated, or with a control byte
+        (string(b"\xe3\0\0\0\x34", b"\0"), None),
+        (string(b"\0", b""), None),
+        (string(b"\0", b"\n\0"), None),
+    ];
+
+    for (data, expected) in &cases {
+        for chunk in [1, 2, 3, OLD.len() - 1, OLD.len(), OLD.len() + 1, data.len()] {
+            let scan = scan_in_chunks(root.path(), data, chunk);
+            assert_eq!(
+                kind_of(&scan.references, "file"),
+                *expected,
+                "{:?} in chunks of {}",
+                String::from_utf8_lossy(data),
+                chunk
+            );
+            assert_eq!(scan.unrelocatable.is_empty(), expected.is_some());
+        }
+    }
+}
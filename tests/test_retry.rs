@@ -0,0 +1,140 @@
+use mise_s3_cache::config::Config;
+use mise_s3_cache::retry::{
+    classify_response, AdaptiveConcurrency, Classified, ErrorClass, RetryPolicy,
+};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+fn fast_policy() -> RetryPolicy {
+    RetryPolicy::from_config(&Config {
+        retry_max_attempts: 3,
+        retry_base_delay_ms: 1,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_classify_response() {
+    assert_eq!(
+        classify_response(Some("SlowDown"), 503),
+        ErrorClass::Throttled
+    );
+    assert_eq!(classify_response(None, 429), ErrorClass::Throttled);
+    assert_eq!(
+        classify_response(Some("InternalError"), 500),
+        ErrorClass::Retryable
+    );
+    assert_eq!(classify_response(None, 502), ErrorClass::Retryable);
+    assert_eq!(
+        classify_response(Some("AccessDenied"), 403),
+        ErrorClass::Fatal
+    );
+    assert_eq!(
+        classify_response(Some("NoSuchBucket"), 404),
+        ErrorClass::Fatal
+    );
+}
+
+#[test]
+fn test_retry_delay_backoff() {
+    let policy = RetryPolicy {
+        max_attempts: 10,
+        base_delay: Duration::from_millis(100),
+        jitter: false,
+    };
+    assert_eq!(policy.delay(1), Duration::from_millis(100));
+    assert_eq!(policy.delay(3), Duration::from_millis(400));
+    assert_eq!(policy.delay(30), Duration::from_secs(20));
+
+    let jittered = RetryPolicy {
+        jitter: true,
+        ..policy
+    };
+    for _ in 0..20 {
+        assert!(jittered.delay(3) <= Duration::from_millis(400));
+    }
+}
+
+#[tokio::test]
+async fn test_retry_recovers_from_transient_errors() {
+    let attempts = AtomicU32::new(0);
+    let result = fast_policy()
+        .run("test", || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(Classified::retryable(anyhow::anyhow!("connection reset")))
+            } else {
+                Ok("done")
+            }
+        })
+        .await;
+
+    assert_eq!(result.unwrap(), "done");
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retry_stops_on_fatal_and_after_max_attempts() {
+    let attempts = AtomicU32::new(0);
+    let result: anyhow::Result<()> = fast_policy()
+        .run("test", || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Classified::fatal(anyhow::anyhow!("access denied")))
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+    let attempts = AtomicU32::new(0);
+    let result: anyhow::Result<()> = fast_policy()
+        .run("test", || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Classified::retryable(anyhow::anyhow!("internal error")))
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_throttling_reduces_concurrency() {
+    let limiter = AdaptiveConcurrency::new(8);
+    let attempts = AtomicU32::new(0);
+
+    fast_policy()
+        .run_limited("test", &limiter, || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(Classified {
+                    class: ErrorClass::Throttled,
+                    error: anyhow::anyhow!("SlowDown"),
+                })
+            } else {
+                Ok(())
+            }
+        })
+        .await
+        .unwrap();
+    assert_eq!(limiter.limit(), 2);
+
+    // Only two requests may run at once now
+    let first = limiter.acquire().await;
+    let _second = limiter.acquire().await;
+    assert!(
+        tokio::time::timeout(Duration::from_millis(20), limiter.acquire())
+            .await
+            .is_err()
+    );
+    drop(first);
+    let _third = limiter.acquire().await;
+}
+
+#[tokio::test]
+async fn test_concurrency_recovers_after_successes() {
+    let limiter = AdaptiveConcurrency::new(4);
+    limiter.on_throttle();
+    assert_eq!(limiter.limit(), 2);
+
+    for _ in 0..8 {
+        limiter.on_success();
+    }
+    assert_eq!(limiter.limit(), 3);
+}
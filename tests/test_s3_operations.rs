@@ -0,0 +1,86 @@
+use bytes::{Bytes, BytesMut};
+use mise_s3_cache::s3_operations::{plan_parts, upload_parts};
+use mise_s3_cache::streaming;
+use std::time::Duration;
+
+const MIB: u64 = 1024 * 1024;
+
+#[test]
+fn test_plan_parts_covers_file() {
+    let parts = plan_parts(150 * MIB, 64 * MIB);
+
+    assert_eq!(
+        parts,
+        vec![
+            (1, 0, 64 * MIB),
+            (2, 64 * MIB, 64 * MIB),
+            (3, 128 * MIB, 22 * MIB)
+        ]
+    );
+}
+
+#[test]
+fn test_plan_parts_exact_multiple() {
+    let parts = plan_parts(128 * MIB, 64 * MIB);
+
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[1], (2, 64 * MIB, 64 * MIB));
+}
+
+#[test]
+fn test_plan_parts_stays_within_part_limit() {
+    // 1 TiB with 5 MiB parts would need ~200k parts
+    let file_size = 1024 * 1024 * MIB;
+    let parts = plan_parts(file_size, 5 * MIB);
+
+    assert!(parts.len() <= 10_000);
+    assert_eq!(parts.iter().map(|(_, _, len)| len).sum::<u64>(), file_size);
+    assert_eq!(parts.last().unwrap().0 as usize, parts.len());
+}
+
+#[tokio::test]
+async fn test_upload_parts_progress_while_reading() {
+    let (tx, mut rx) = streaming::chunk_channel();
+    let (uploaded_tx, mut uploaded_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    // The producer only sends the second part once the first is uploaded,
+    // which never happens if uploads wait for the window to fill
+    let producer = tokio::spawn(async move {
+        tx.send(Ok(Bytes::from_static(b"abcd"))).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), uploaded_rx.recv())
+            .await
+            .expect("first part uploaded while the second was read");
+        tx.send(Ok(Bytes::from_static(b"efgh"))).await.unwrap();
+        tx.send(Ok(Bytes::from_static(b"ij"))).await.unwrap();
+    });
+
+    let mut total = 0;
+    let parts = upload_parts(
+        &mut rx,
+        BytesMut::new(),
+        4,
+        3,
+        &mut total,
+        |part_number, part| {
+            let uploaded_tx = uploaded_tx.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                let _ = uploaded_tx.send(part_number);
+                Ok((part_number, part))
+            }
+        },
+    )
+    .await
+    .unwrap();
+    producer.await.unwrap();
+
+    assert_eq!(total, 10);
+    assert_eq!(
+        parts,
+        vec![
+            (1, Bytes::from_static(b"abcd")),
+            (2, Bytes::from_static(b"efgh")),
+            (3, Bytes::from_static(b"ij")),
+        ]
+    );
+}
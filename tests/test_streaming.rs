@@ -0,0 +1,87 @@
+use mise_s3_cache::streaming::OrderedTasks;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn test_ordered_tasks_run_ahead_of_consumer() {
+    let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+    let tasks = (1..=4u64).map(|n| {
+        let done_tx = done_tx.clone();
+        async move {
+            // Later tasks finish first
+            tokio::time::sleep(Duration::from_millis(40 - n * 10)).await;
+            let _ = done_tx.send(n);
+            Ok(n)
+        }
+    });
+    let mut tasks = OrderedTasks::new(tasks, 2);
+
+    assert_eq!(tasks.next().await.unwrap().unwrap(), 1);
+
+    // While the consumer is busy, the window of two keeps running: tasks 2
+    // and 3 both finish without another call to `next`
+    let mut finished = Vec::new();
+    while finished.len() < 3 {
+        let n = tokio::time::timeout(Duration::from_secs(5), done_rx.recv())
+            .await
+            .expect("tasks progress while the consumer waits")
+            .unwrap();
+        finished.push(n);
+    }
+    finished.sort();
+    assert_eq!(finished, vec![1, 2, 3]);
+
+    let mut rest = Vec::new();
+    while let Some(n) = tasks.next().await {
+        rest.push(n.unwrap());
+    }
+    assert_eq!(rest, vec![2, 3, 4]);
+}
+
+#[tokio::test]
+async fn test_ordered_tasks_report_failures_in_order() {
+    let tasks = (1..=3u64).map(|n| async move {
+        if n == 2 {
+            Err(anyhow::anyhow!("range {} failed", n))
+        } else {
+            Ok(n)
+        }
+    });
+    let mut tasks = OrderedTasks::new(tasks, 3);
+
+    assert_eq!(tasks.next().await.unwrap().unwrap(), 1);
+    assert_eq!(
+        tasks.next().await.unwrap().unwrap_err().to_string(),
+        "range 2 failed"
+    );
+}
+
+#[tokio::test]
+async fn test_ordered_tasks_stop_leaves_queue_unstarted() {
+    let (started_tx, mut started_rx) = mpsc::unbounded_channel();
+    let tasks = (1..=5u64).map(|n| {
+        let started_tx = started_tx.clone();
+        async move {
+            let _ = started_tx.send(n);
+            Ok(n)
+        }
+    });
+    let mut tasks = OrderedTasks::new(tasks, 2);
+
+    assert_eq!(tasks.next().await.unwrap().unwrap(), 1);
+    tasks.stop();
+    let mut rest = Vec::new();
+    while let Some(n) = tasks.next().await {
+        rest.push(n.unwrap());
+    }
+    assert_eq!(rest, vec![2, 3]);
+
+    drop(tasks);
+    drop(started_tx);
+    let mut started = Vec::new();
+    while let Some(n) = started_rx.recv().await {
+        started.push(n);
+    }
+    started.sort();
+    assert_eq!(started, vec![1, 2, 3]);
+}
@@ -112,6 +112,43 @@ fn test_human_readable_size() {
     assert_eq!(human_readable_size(5242880), "5.0 MB");
 }
 
+#[test]
+fn test_parse_human_size() {
+    assert_eq!(parse_human_size("0"), Some(0));
+    assert_eq!(parse_human_size("512"), Some(512));
+    assert_eq!(parse_human_size("512B"), Some(512));
+    assert_eq!(parse_human_size("64KB"), Some(65536));
+    assert_eq!(parse_human_size("16M"), Some(16 * 1024 * 1024));
+    assert_eq!(parse_human_size("1.5 GiB"), Some(1610612736));
+    assert_eq!(parse_human_size("2tb"), Some(2 * 1024 * 1024 * 1024 * 1024));
+
+    assert_eq!(parse_human_size(""), None);
+    assert_eq!(parse_human_size("lots"), None);
+    assert_eq!(parse_human_size("10 parsecs"), None);
+}
+
+#[test]
+fn test_parse_human_rate() {
+    assert_eq!(parse_human_rate("0"), Some(0));
+    assert_eq!(parse_human_rate("2MB"), Some(2 * 1024 * 1024));
+    assert_eq!(parse_human_rate("512KB/s"), Some(512 * 1024));
+    assert_eq!(parse_human_rate(" 1.5 MiB/s "), Some(1572864));
+
+    assert_eq!(parse_human_rate("/s"), None);
+    assert_eq!(parse_human_rate("fast"), None);
+}
+
+#[test]
+fn test_parse_key_value_list() {
+    let tags = parse_key_value_list("team=infra, env = ci,,bad,empty=");
+    assert_eq!(tags.len(), 3);
+    assert_eq!(tags["team"], "infra");
+    assert_eq!(tags["env"], "ci");
+    assert_eq!(tags["empty"], "");
+
+    assert!(parse_key_value_list("").is_empty());
+}
+
 #[test]
 fn test_current_timestamp() {
     let timestamp1 = current_timestamp();